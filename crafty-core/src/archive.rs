@@ -0,0 +1,243 @@
+//! Package archives and their file names.
+
+use openssl::sha::{Sha1, Sha256};
+use regex::Regex;
+use std::{fs::File, io::Read, path::Path, process::Command};
+
+// Metadata entries every package archive carries next to the payload
+const METADATA_FILES: [&str; 4] = [".PKGINFO", ".BUILDINFO", ".MTREE", ".INSTALL"];
+
+/// Regex matching the extension of a package archive in any [`Compression`].
+pub const EXTENSION_PATTERN: &str = r"\.pkg\.tar\.(?:zst|xz|gz)";
+
+/// How a package archive is compressed, going by its file name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Zstd,
+    Xz,
+    Gzip,
+}
+
+impl Compression {
+    const ALL: [Self; 3] = [Self::Zstd, Self::Xz, Self::Gzip];
+
+    /// The format of a file or path ending in ".pkg.tar.zst", ".pkg.tar.xz"
+    /// or ".pkg.tar.gz".
+    pub fn of(file_name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|c| file_name.ends_with(&format!(".pkg.tar{}", c.extension())))
+    }
+
+    /// ".zst", ".xz" or ".gz"
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Zstd => ".zst",
+            Self::Xz => ".xz",
+            Self::Gzip => ".gz",
+        }
+    }
+
+    // Bytes every file in the format starts with
+    fn magic(self) -> &'static [u8] {
+        match self {
+            Self::Zstd => &[0x28, 0xb5, 0x2f, 0xfd],
+            Self::Xz => &[0xfd, b'7', b'z', b'X', b'Z', 0x00],
+            Self::Gzip => &[0x1f, 0x8b],
+        }
+    }
+
+    /// Check the magic bytes of the file at `path`.
+    pub fn matches(self, path: &Path) -> bool {
+        let magic = self.magic();
+        let mut header = vec![0; magic.len()];
+        File::open(path)
+            .and_then(|mut file| file.read_exact(&mut header))
+            .is_ok_and(|_| header == magic)
+    }
+}
+
+/// The parts of a package's .PKGINFO crafty cares about.
+#[derive(Debug, Default, Clone)]
+pub struct PkgInfo {
+    pub name: String,
+    pub version: String,
+    pub arch: String,
+    /// pkgdesc, a one-line summary
+    pub desc: String,
+    /// Installed size in bytes
+    pub size: Option<u64>,
+    pub depends: Vec<String>,
+}
+
+/// Check that `path` holds an archive compressed the way the name of the
+/// package file it was downloaded as says.
+pub fn is_valid_archive(path: &Path, package_file: &str) -> bool {
+    Compression::of(package_file).is_some_and(|c| c.matches(path))
+}
+
+/// SHA-256 of a file as lowercase hex.
+pub fn sha256(path: &Path) -> Option<String> {
+    let mut hasher = Sha256::new();
+    digest_file(File::open(path).ok()?, |chunk| hasher.update(chunk))?;
+    Some(hex(&hasher.finish()))
+}
+
+/// Git blob hash of a file, as GitHub reports it for repository contents:
+/// the SHA-1 of `blob <length>\0` followed by the contents.
+pub fn git_blob_sha(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", file.metadata().ok()?.len()).as_bytes());
+    digest_file(file, |chunk| hasher.update(chunk))?;
+    Some(hex(&hasher.finish()))
+}
+
+fn digest_file(mut file: File, mut update: impl FnMut(&[u8])) -> Option<()> {
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buf) {
+            Ok(0) => return Some(()),
+            Ok(n) => update(&buf[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(_) => return None,
+        }
+    }
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Read the .PKGINFO metadata straight out of the package archive.
+pub fn read_pkginfo(path: &Path) -> Option<PkgInfo> {
+    let output = Command::new("bsdtar")
+        .arg("-xOf")
+        .arg(path)
+        .arg(".PKGINFO")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(parse_pkginfo(&String::from_utf8_lossy(&output.stdout)))
+}
+
+pub fn parse_pkginfo(text: &str) -> PkgInfo {
+    let mut info = PkgInfo::default();
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once(" = ") else {
+            continue;
+        };
+        match key.trim() {
+            "pkgname" => info.name = value.trim().to_string(),
+            "pkgver" => info.version = value.trim().to_string(),
+            "arch" => info.arch = value.trim().to_string(),
+            "pkgdesc" => info.desc = value.trim().to_string(),
+            "size" => info.size = value.trim().parse().ok(),
+            "depend" => info.depends.push(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    info
+}
+
+/// Paths shipped by an archive, without metadata entries or a leading slash.
+pub fn list_archive(path: &Path) -> Option<Vec<String>> {
+    let output = Command::new("bsdtar").arg("-tf").arg(path).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|l| !l.is_empty() && !METADATA_FILES.contains(l))
+            .map(|l| l.to_string())
+            .collect(),
+    )
+}
+
+/// Package name of a repository file, or the file name itself if it doesn't parse.
+pub fn package_name_from_file(package_file: &str) -> String {
+    let re = Regex::new(&format!(
+        r"^(?P<name>.+)-\d+(\.\d+)*-\d+-[^-]+{}$",
+        EXTENSION_PATTERN
+    ))
+    .unwrap();
+    re.captures(package_file)
+        .and_then(|caps| caps.name("name").map(|m| m.as_str().to_string()))
+        .unwrap_or_else(|| package_file.to_string())
+}
+
+/// "archcraft-foo-1.2-3-any.pkg.tar.zst" -> ("archcraft-foo", "1.2-3")
+pub fn parse_package_file(package_file: &str) -> Option<(String, String)> {
+    let re = Regex::new(&format!(
+        r"^(?P<name>.+)-(?P<version>[^-]+-\d+)-(any|x86_64){}$",
+        EXTENSION_PATTERN
+    ))
+    .unwrap();
+    let caps = re.captures(package_file)?;
+    Some((caps["name"].to_string(), caps["version"].to_string()))
+}
+
+/// "foo>=1.2" -> "foo"
+pub fn strip_version_constraint(dep: &str) -> &str {
+    dep.split(['<', '>', '=']).next().unwrap_or(dep).trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+    use std::fs;
+
+    #[test]
+    fn hashes_files_like_sha256sum_and_git() {
+        let dir = TempDir::new("hashes");
+        let empty = dir.join("empty");
+        fs::write(&empty, "").unwrap();
+        let hello = dir.join("hello");
+        fs::write(&hello, "hello\n").unwrap();
+
+        assert_eq!(
+            sha256(&empty).as_deref(),
+            Some("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+        assert_eq!(
+            sha256(&hello).as_deref(),
+            Some("5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03")
+        );
+        assert_eq!(
+            git_blob_sha(&empty).as_deref(),
+            Some("e69de29bb2d1d6434b8b29ae775ad8c2e48c5391")
+        );
+        assert_eq!(
+            git_blob_sha(&hello).as_deref(),
+            Some("ce013625030ba8dba906f756967f9e9ca394464a")
+        );
+        assert_eq!(sha256(&dir.join("missing")), None);
+        assert_eq!(git_blob_sha(&dir.join("missing")), None);
+    }
+
+    #[test]
+    fn hashes_files_larger_than_the_buffer() {
+        let dir = TempDir::new("large");
+        let path = dir.join("large");
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &data).unwrap();
+
+        let sha256sum = Command::new("sha256sum").arg(&path).output().unwrap();
+        let expected = String::from_utf8_lossy(&sha256sum.stdout);
+        assert_eq!(sha256(&path).as_deref(), expected.split_whitespace().next());
+        let git = Command::new("git")
+            .arg("hash-object")
+            .arg("--no-filters")
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert_eq!(
+            git_blob_sha(&path).as_deref(),
+            Some(String::from_utf8_lossy(&git.stdout).trim())
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Packages from the Arch User Repository, for names the ArchCraft repositories don't have.
+
+use crate::{Error, RepoClient, Result, archive, paths};
+use serde_json::Value;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Origin recorded in the database for packages built from the AUR.
+pub const ORIGIN: &str = "aur";
+
+const RPC_URL: &str = "https://aur.archlinux.org/rpc/v5/info";
+const GIT_URL: &str = "https://aur.archlinux.org";
+
+/// What the AUR RPC reports about a package.
+#[derive(Debug, Clone)]
+pub struct AurPackage {
+    pub name: String,
+    /// Full version, "1.2-3"
+    pub version: String,
+    pub description: Option<String>,
+    /// Name of the git repository holding its PKGBUILD
+    pub package_base: String,
+    /// Flagged out of date by an AUR user
+    pub out_of_date: bool,
+}
+
+/// Look `names` up in the AUR; names it doesn't know are left out.
+pub fn info(repo: &dyn RepoClient, names: &[String]) -> Result<Vec<AurPackage>> {
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+    let params: Vec<(&str, &str)> = names.iter().map(|n| ("arg[]", n.as_str())).collect();
+    let url =
+        reqwest::Url::parse_with_params(RPC_URL, &params).map_err(|e| Error::Aur(e.to_string()))?;
+    let text = repo.fetch_text(url.as_str())?;
+    let json: Value = serde_json::from_str(&text).map_err(|e| Error::Aur(e.to_string()))?;
+    if let Some(error) = json.get("error").and_then(|e| e.as_str()) {
+        return Err(Error::Aur(error.to_string()));
+    }
+    Ok(json
+        .get("results")
+        .and_then(|r| r.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|result| {
+            let name = result.get("Name")?.as_str()?.to_string();
+            Some(AurPackage {
+                version: result.get("Version")?.as_str()?.to_string(),
+                description: result
+                    .get("Description")
+                    .and_then(|d| d.as_str())
+                    .map(str::to_string),
+                package_base: result
+                    .get("PackageBase")
+                    .and_then(|b| b.as_str())
+                    .unwrap_or(&name)
+                    .to_string(),
+                out_of_date: result.get("OutOfDate").is_some_and(|o| !o.is_null()),
+                name,
+            })
+        })
+        .collect())
+}
+
+/// Where AUR packages are cloned and built.
+pub fn build_dir() -> PathBuf {
+    paths::cache_dir().join("aur")
+}
+
+/// Clone (or update) the package's AUR repository and build it with makepkg,
+/// installing missing dependencies through pacman. Returns the built archive
+/// of `package`; other packages of a split package base are left out.
+pub fn build(package: &AurPackage, build_dir: &Path) -> Result<PathBuf> {
+    // makepkg refuses to run as root and would only fail halfway through
+    if unsafe { libc::geteuid() } == 0 {
+        return Err(Error::Aur(String::from(
+            "makepkg can't run as root, run crafty as a regular user or set `aur_helper`",
+        )));
+    }
+    std::fs::create_dir_all(build_dir)?;
+    let dir = build_dir.join(&package.package_base);
+    let mut git = Command::new("git");
+    if dir.join(".git").exists() {
+        git.arg("-C").arg(&dir).args(["pull", "--ff-only"]);
+    } else {
+        git.arg("clone")
+            .arg(format!("{}/{}.git", GIT_URL, package.package_base))
+            .arg(&dir);
+    }
+    run(git, "git")?;
+
+    let mut makepkg = Command::new("makepkg");
+    makepkg
+        .current_dir(&dir)
+        .args(["--syncdeps", "--force", "--noconfirm"]);
+    run(makepkg, "makepkg")?;
+
+    let output = Command::new("makepkg")
+        .current_dir(&dir)
+        .arg("--packagelist")
+        .output()
+        .map_err(|e| Error::Aur(format!("Failed to run makepkg: {}", e)))?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(PathBuf::from)
+        .filter(|path| path.exists())
+        .find(|path| archive::read_pkginfo(path).is_some_and(|info| info.name == package.name))
+        .ok_or_else(|| {
+            Error::Aur(format!(
+                "makepkg didn't produce a package for {}",
+                package.name
+            ))
+        })
+}
+
+/// Build and install `package` with an AUR helper such as paru or yay, which
+/// asks for root privileges itself.
+pub fn install_with_helper(helper: &str, package: &AurPackage, noconfirm: bool) -> Result<()> {
+    let mut cmd = Command::new(helper);
+    cmd.args(["-S", "--needed"]);
+    if noconfirm {
+        cmd.arg("--noconfirm");
+    }
+    cmd.arg(format!("aur/{}", package.name));
+    run(cmd, helper)
+}
+
+fn run(mut cmd: Command, what: &str) -> Result<()> {
+    log::debug!("Running {:?}", cmd);
+    let status = cmd
+        .status()
+        .map_err(|e| Error::Aur(format!("Failed to run {}: {}", what, e)))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::Aur(format!("{} failed ({})", what, status)))
+    }
+}
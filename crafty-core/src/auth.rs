@@ -0,0 +1,46 @@
+//! GitHub token lookup, raising the anonymous rate limit for API and raw requests.
+
+use crate::Config;
+use std::{env, process::Command};
+
+/// Attributes of the keyring entry, store one with
+/// `secret-tool store --label='crafty GitHub token' service crafty kind github-token`.
+pub const KEYRING_ATTRIBUTES: [&str; 4] = ["service", "crafty", "kind", "github-token"];
+
+/// The token from the `github_token` config key, `$GITHUB_TOKEN` or the
+/// system keyring, in that order.
+pub fn github_token(config: &Config) -> Option<String> {
+    config
+        .github_token
+        .clone()
+        .or_else(|| env::var("GITHUB_TOKEN").ok())
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+        .or_else(keyring_token)
+}
+
+// Ask the Secret Service through libsecret's CLI, missing when it isn't installed
+fn keyring_token() -> Option<String> {
+    let output = Command::new("secret-tool")
+        .arg("lookup")
+        .args(KEYRING_ATTRIBUTES)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!token.is_empty()).then_some(token)
+}
+
+/// Whether requests to `url` should carry the token; it must never leak to other hosts.
+pub fn is_github_url(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .is_some_and(|host| {
+            host == "github.com"
+                || host.ends_with(".github.com")
+                || host.ends_with(".githubusercontent.com")
+        })
+}
@@ -0,0 +1,175 @@
+//! Snapshots of user configs a package is about to overwrite.
+//!
+//! Before a transaction, every existing file the archive ships under `$HOME` or
+//! `/etc` is copied to `backups/<package>/<timestamp>/` in the state directory,
+//! keeping its path relative to `/`, so `crafty restore` can put it back. With
+//! a root both are looked up inside it.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub package: String,
+    /// Seconds since the Unix epoch, also the name of the backup directory
+    pub timestamp: u64,
+    pub dir: PathBuf,
+    /// The system the files belong to, see [`crate::paths::install_root`]
+    pub root: PathBuf,
+}
+
+impl Backup {
+    /// Live paths of the saved files, sorted.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        walk(&self.dir, &mut files);
+        files.sort();
+        files
+            .into_iter()
+            .filter_map(|f| f.strip_prefix(&self.dir).ok().map(|p| self.root.join(p)))
+            .collect()
+    }
+
+    /// Where the saved copy of the live file `path` is kept.
+    pub fn saved_path(&self, path: &Path) -> PathBuf {
+        let inside = path.strip_prefix(&self.root).unwrap_or(path);
+        self.dir.join(inside.strip_prefix("/").unwrap_or(inside))
+    }
+}
+
+/// Where backups are kept, given the state directory, see [`crate::paths::state_dir`].
+pub fn dir(state_dir: &Path) -> PathBuf {
+    state_dir.join("backups")
+}
+
+/// Copy the existing files among `entries` (archive paths relative to `/`)
+/// that live under `$HOME` or `/etc` in the system under `root`. Returns
+/// `None` when there was nothing to save; files we can't read, like root-only
+/// secrets, are skipped.
+pub fn create<S: AsRef<str>>(
+    state_dir: &Path,
+    root: &Path,
+    package: &str,
+    entries: &[S],
+    timestamp: u64,
+) -> io::Result<Option<Backup>> {
+    let home = dirs::home_dir();
+    let backup = Backup {
+        package: package.to_string(),
+        timestamp,
+        dir: dir(state_dir).join(package).join(timestamp.to_string()),
+        root: root.to_path_buf(),
+    };
+    let mut saved = 0;
+    for entry in entries {
+        let inside = Path::new("/").join(entry.as_ref().trim_start_matches('/'));
+        let watched =
+            inside.starts_with("/etc") || home.as_ref().is_some_and(|h| inside.starts_with(h));
+        let path = root.join(entry.as_ref().trim_start_matches('/'));
+        // Only regular files, the package recreates directories and symlinks itself
+        if !watched || !fs::symlink_metadata(&path).is_ok_and(|m| m.is_file()) {
+            continue;
+        }
+        let target = backup.saved_path(&path);
+        fs::create_dir_all(target.parent().unwrap())?;
+        match fs::copy(&path, &target) {
+            Ok(_) => saved += 1,
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    if saved == 0 {
+        // Directories made for files that turned out unreadable
+        let _ = fs::remove_dir_all(&backup.dir);
+        return Ok(None);
+    }
+    Ok(Some(backup))
+}
+
+/// Backups of `package` in the system under `root`, oldest first.
+pub fn list(state_dir: &Path, root: &Path, package: &str) -> Vec<Backup> {
+    let package_dir = dir(state_dir).join(package);
+    let mut backups: Vec<Backup> = fs::read_dir(&package_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let timestamp = entry.file_name().to_str()?.parse().ok()?;
+            Some(Backup {
+                package: package.to_string(),
+                timestamp,
+                dir: entry.path(),
+                root: root.to_path_buf(),
+            })
+        })
+        .collect();
+    backups.sort_by_key(|b| b.timestamp);
+    backups
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            walk(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn backs_up_configs_inside_the_root() {
+        let root = TempDir::new("root");
+        let state = TempDir::new("state");
+        fs::create_dir_all(root.join("etc/xdg")).unwrap();
+        fs::write(root.join("etc/xdg/panel.conf"), "edited").unwrap();
+        fs::create_dir_all(root.join("usr/bin")).unwrap();
+        fs::write(root.join("usr/bin/panel"), "binary").unwrap();
+        let entries = [
+            "etc/xdg/",
+            "etc/xdg/panel.conf",
+            "etc/xdg/missing.conf",
+            "usr/bin/panel",
+        ];
+
+        let backup = create(state.path(), root.path(), "panel", &entries, 42)
+            .unwrap()
+            .unwrap();
+
+        let live = root.join("etc/xdg/panel.conf");
+        assert_eq!(backup.files(), std::slice::from_ref(&live));
+        assert_eq!(
+            backup.saved_path(&live),
+            dir(state.path()).join("panel/42/etc/xdg/panel.conf")
+        );
+        assert_eq!(
+            fs::read_to_string(backup.saved_path(&live)).unwrap(),
+            "edited"
+        );
+        let listed = list(state.path(), root.path(), "panel");
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].timestamp, 42);
+        assert_eq!(listed[0].files(), [live]);
+    }
+
+    #[test]
+    fn nothing_to_back_up_outside_etc_and_home() {
+        let root = TempDir::new("root");
+        let state = TempDir::new("state");
+        fs::create_dir_all(root.join("usr/bin")).unwrap();
+        fs::write(root.join("usr/bin/panel"), "binary").unwrap();
+
+        let backup = create(state.path(), root.path(), "panel", &["usr/bin/panel"], 1).unwrap();
+
+        assert!(backup.is_none());
+        assert!(list(state.path(), root.path(), "panel").is_empty());
+    }
+}
@@ -0,0 +1,27 @@
+//! What changed for a package in the repository, from its git history.
+
+/// A commit of the package repository, as reported by the GitHub API.
+#[derive(Debug, Clone)]
+pub struct Commit {
+    pub sha: String,
+    /// ISO 8601 commit date, e.g. `2024-05-01T12:00:00Z`
+    pub date: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Changelog {
+    pub name: String,
+    pub installed: Option<String>,
+    pub latest: String,
+    /// Newest first
+    pub commits: Vec<Commit>,
+}
+
+/// Whether the commit message names `package`.
+pub fn mentions(commit: &Commit, package: &str) -> bool {
+    commit
+        .message
+        .to_lowercase()
+        .contains(&package.to_lowercase())
+}
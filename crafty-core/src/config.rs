@@ -0,0 +1,197 @@
+//! User configuration, read from `config.json` in the config directory.
+
+use crate::{Error, Result, paths};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub retry: RetryConfig,
+    /// Proxy URL used for all requests, overriding `http_proxy`/`https_proxy`
+    pub proxy: Option<String>,
+    /// PEM bundle with additional CA certificates to trust
+    pub ca_bundle: Option<PathBuf>,
+    /// Packages (glob patterns allowed) skipped by `crafty upgrade`, like pacman's IgnorePkg
+    pub ignore: Vec<String>,
+    /// Default interval for `crafty service install`, as a systemd time span
+    pub check_interval: String,
+    /// Send a desktop notification when `upgrade --check` finds updates
+    pub notify: bool,
+    /// Privilege escalation tool used to run pacman
+    pub elevate: Elevate,
+    /// Curated package groups for `crafty install @name`, taking precedence
+    /// over the groups inferred from package name prefixes
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// Program used by `crafty pacdiff --merge`, called with the live file and
+    /// the `.pacnew`/`.pacsave` file; falls back to `$DIFFPROG`, then vimdiff
+    pub diff_tool: Option<String>,
+    /// Snapshot files a package ships under `$HOME` or `/etc` before overwriting them
+    pub backup: bool,
+    /// Keep a debug log in `~/.local/state/crafty/crafty.log`
+    pub log_file: bool,
+    /// GitHub token sent with requests to GitHub to raise the rate limit;
+    /// falls back to `$GITHUB_TOKEN`, then the system keyring
+    pub github_token: Option<String>,
+    /// Branch or tag to install from, e.g. an older snapshot; the default for
+    /// repositories that don't set their own
+    pub git_ref: String,
+    /// Repositories packages come from; when several have a package, the one
+    /// with the highest priority wins
+    pub repos: Vec<RepoSource>,
+    /// Where package archives are downloaded before installation, by default
+    /// `pkg` in the cache directory
+    pub download_dir: Option<PathBuf>,
+    /// How many packages are downloaded at the same time, like pacman's ParallelDownloads
+    pub parallel_downloads: usize,
+    /// Cap on the combined download speed in bytes per second, with an
+    /// optional K, M or G suffix (e.g. "500K"); unlimited when unset
+    pub limit_rate: Option<String>,
+    /// Build packages missing from the repositories from the AUR, like `install --aur`
+    pub aur: bool,
+    /// AUR helper (e.g. paru or yay) to build AUR packages with instead of makepkg
+    pub aur_helper: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            retry: RetryConfig::default(),
+            proxy: None,
+            ca_bundle: None,
+            ignore: Vec::new(),
+            check_interval: String::from("6h"),
+            notify: false,
+            elevate: Elevate::default(),
+            groups: BTreeMap::new(),
+            diff_tool: None,
+            backup: true,
+            log_file: false,
+            github_token: None,
+            git_ref: String::from("main"),
+            repos: vec![RepoSource::default()],
+            download_dir: None,
+            parallel_downloads: 4,
+            limit_rate: None,
+            aur: false,
+            aur_helper: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Total number of attempts, including the first one
+    pub attempts: u32,
+    /// Delay before the first retry in milliseconds, doubled after each failure
+    pub backoff_ms: u64,
+    /// Upper bound for the delay between two attempts
+    pub max_backoff_ms: u64,
+    /// Randomize each delay by up to 50% to avoid retrying in lockstep
+    pub jitter: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 4,
+            backoff_ms: 500,
+            max_backoff_ms: 8000,
+            jitter: true,
+        }
+    }
+}
+
+/// A GitHub repository with a directory of package files.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RepoSource {
+    /// Label shown next to its packages
+    pub name: String,
+    /// "owner/repository" on GitHub
+    pub repo: String,
+    /// Directory of the package files in the repository
+    pub dir: String,
+    /// Branch or tag, the `git_ref` key when unset
+    pub git_ref: Option<String>,
+    pub priority: i32,
+}
+
+impl Default for RepoSource {
+    fn default() -> Self {
+        Self {
+            name: String::from("pkgs"),
+            repo: String::from("archcraft-os/pkgs"),
+            dir: String::from("x86_64"),
+            git_ref: None,
+            priority: 0,
+        }
+    }
+}
+
+/// How pacman gets root privileges.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Elevate {
+    #[default]
+    Sudo,
+    Doas,
+    Pkexec,
+    /// Run pacman directly, e.g. when crafty itself runs as root
+    None,
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        paths::config_dir().join("config.json")
+    }
+
+    /// Directory package archives are downloaded to.
+    pub fn staging_dir(&self) -> PathBuf {
+        self.download_dir.clone().unwrap_or_else(paths::staging_dir)
+    }
+
+    /// Load the config file, falling back to defaults when it doesn't exist.
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(&path)?;
+        let config: Self = serde_json::from_str(&data)
+            .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.repos.is_empty() {
+            return Err(Error::Config(String::from("`repos` lists no repository")));
+        }
+        for (i, source) in self.repos.iter().enumerate() {
+            if source
+                .repo
+                .split('/')
+                .filter(|part| !part.is_empty())
+                .count()
+                != 2
+            {
+                return Err(Error::Config(format!(
+                    "repository '{}' of `repos` is not in owner/name form",
+                    source.repo
+                )));
+            }
+            if self.repos[..i]
+                .iter()
+                .any(|other| other.name == source.name)
+            {
+                return Err(Error::Config(format!(
+                    "`repos` names '{}' more than once",
+                    source.name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+//! Checking staged archives against the filesystem before pacman runs.
+//!
+//! pacman refuses a transaction when a file it would write already exists and
+//! doesn't belong to one of the packages being replaced. Finding those up
+//! front turns a failed transaction after a long download into a warning.
+
+use crate::pacman;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// The file belongs to another installed package
+    Owned { path: PathBuf, owner: String },
+    /// The file exists but no package owns it
+    Untracked { path: PathBuf },
+    /// The package writes into a home directory
+    Home { path: PathBuf },
+}
+
+/// Problems found for one staged package.
+#[derive(Debug, Clone)]
+pub struct Inspection {
+    pub package: String,
+    pub problems: Vec<Problem>,
+}
+
+/// Inspect the archive listings (paths relative to `/`) of the packages in a
+/// transaction into the system under `root`, given as (name, entries) pairs.
+/// Only packages with problems are returned.
+pub fn inspect(root: &Path, packages: &[(&str, &[String])]) -> Vec<Inspection> {
+    let home = dirs::home_dir();
+    let mut existing = Vec::new();
+    let mut inspections: Vec<Inspection> = Vec::new();
+    for (package, entries) in packages {
+        let mut problems = Vec::new();
+        for entry in entries.iter() {
+            if entry.ends_with('/') {
+                continue;
+            }
+            let inside = Path::new("/").join(entry.trim_start_matches('/'));
+            let path = root.join(entry.trim_start_matches('/'));
+            if inside.starts_with("/home")
+                || inside.starts_with("/root")
+                || home.as_ref().is_some_and(|h| inside.starts_with(h))
+            {
+                problems.push(Problem::Home { path: path.clone() });
+            }
+            // Directories may be shared freely
+            if path.symlink_metadata().is_ok_and(|m| !m.is_dir()) {
+                existing.push((*package, path));
+            }
+        }
+        inspections.push(Inspection {
+            package: package.to_string(),
+            problems,
+        });
+    }
+
+    let paths: Vec<&PathBuf> = existing.iter().map(|(_, path)| path).collect();
+    let owners = pacman::owners_of(&paths);
+    for (package, path) in existing {
+        let problem = match owners.get(&path) {
+            // Replaced within this transaction
+            Some(owner) if packages.iter().any(|(name, _)| name == owner) => continue,
+            Some(owner) => Problem::Owned {
+                path,
+                owner: owner.clone(),
+            },
+            None => Problem::Untracked { path },
+        };
+        if let Some(inspection) = inspections.iter_mut().find(|i| i.package == package) {
+            inspection.problems.push(problem);
+        }
+    }
+    inspections.retain(|i| !i.problems.is_empty());
+    inspections
+}
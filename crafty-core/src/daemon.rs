@@ -0,0 +1,118 @@
+//! The status `crafty daemon` keeps up to date, served on a local socket for
+//! status bars to poll.
+
+use crate::{Error, Result, paths, updates::UpdateState};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// What clients of the socket get, as JSON.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Status {
+    #[serde(flatten)]
+    pub state: UpdateState,
+    /// Why the last check failed; `state` is then from the one before
+    pub error: Option<String>,
+    /// Pending updates whose archives are already downloaded
+    pub downloaded: Vec<String>,
+}
+
+impl Status {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// The status as a waybar custom module with `return-type: json`: the
+    /// number of updates as text, the updates as tooltip and a class of
+    /// "pending", "updated" or "error".
+    pub fn to_waybar(&self) -> String {
+        let updates = &self.state.updates;
+        let mut tooltip: Vec<String> = updates
+            .iter()
+            .map(|u| format!("{} {} → {}", u.name, u.installed, u.available))
+            .collect();
+        if let Some(error) = &self.error {
+            tooltip.push(format!("Last check failed: {}", error));
+        }
+        let class = if self.error.is_some() {
+            "error"
+        } else if updates.is_empty() {
+            "updated"
+        } else {
+            "pending"
+        };
+        serde_json::json!({
+            "text": updates.len().to_string(),
+            "tooltip": tooltip.join("\n"),
+            "class": class,
+        })
+        .to_string()
+    }
+}
+
+/// Listening socket of a running daemon.
+pub struct Server {
+    listener: UnixListener,
+    status: Arc<Mutex<Status>>,
+}
+
+pub fn socket_path() -> PathBuf {
+    paths::runtime_dir().join("crafty.sock")
+}
+
+impl Server {
+    /// Listen on `path`, replacing a socket a previous daemon left behind.
+    /// Fails when another daemon still answers on it.
+    pub fn bind(path: &Path) -> Result<Self> {
+        if UnixStream::connect(path).is_ok() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::AddrInUse,
+                format!("another crafty daemon is listening on {}", path.display()),
+            )));
+        }
+        let _ = fs::remove_file(path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(Self {
+            listener: UnixListener::bind(path)?,
+            status: Arc::default(),
+        })
+    }
+
+    /// The status handed to clients, to be updated after every check.
+    pub fn status(&self) -> Arc<Mutex<Status>> {
+        self.status.clone()
+    }
+
+    /// Answer clients on a background thread: each connection gets the
+    /// current status and is closed.
+    pub fn serve(self) {
+        thread::spawn(move || {
+            for stream in self.listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let data = serde_json::to_string(&*self.status.lock().unwrap()).unwrap();
+                // A client going away early is its own problem
+                let _ = stream.write_all(data.as_bytes());
+            }
+        });
+    }
+}
+
+/// Ask the daemon listening on `path` for its status.
+pub fn query(path: &Path) -> Result<Status> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut data = String::new();
+    stream.read_to_string(&mut data)?;
+    serde_json::from_str(&data).map_err(|e| Error::InvalidFile(format!("daemon status: {}", e)))
+}
@@ -0,0 +1,386 @@
+//! The database of packages installed through crafty.
+
+use crate::{
+    LocalPackages, Result,
+    pacman::LocalPackage,
+    util::{glob_match, now, write_atomic},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    io,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+};
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PackageDb {
+    /// Managed packages by name
+    pub packages: BTreeMap<String, InstalledPackage>,
+    /// Packages `crafty upgrade` leaves alone until they are unheld
+    #[serde(default)]
+    pub held: HashSet<String>,
+    // Where it was loaded from and is saved to
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+/// What crafty recorded when it installed a package. Entries that were
+/// adopted or migrated from the old name-only database only know what pacman
+/// reports, the rest is left empty.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct InstalledPackage {
+    /// pkgver, including the epoch
+    pub version: Option<String>,
+    /// pkgrel
+    pub release: Option<String>,
+    pub arch: Option<String>,
+    /// Seconds since the epoch
+    pub installed_at: Option<u64>,
+    /// Package file it was installed from
+    pub file: Option<String>,
+    /// SHA-256 of that file
+    pub sha256: Option<String>,
+    /// Size on disk once installed, in bytes
+    pub installed_size: Option<u64>,
+    /// [`aur::ORIGIN`](crate::aur::ORIGIN) for packages built from the AUR,
+    /// unset for those from the ArchCraft repositories
+    pub origin: Option<String>,
+    /// Free-form labels set with `crafty tag`, sorted
+    pub tags: Vec<String>,
+    /// Set with `crafty note`
+    pub note: Option<String>,
+}
+
+impl InstalledPackage {
+    /// What pacman reports about a package crafty didn't install itself.
+    pub fn from_local(local: &LocalPackage) -> Self {
+        Self {
+            arch: Some(local.arch.clone()).filter(|a| !a.is_empty()),
+            ..Self::default()
+        }
+        .with_version(&local.version)
+    }
+
+    /// Split a full "1.2-3" version into version and release.
+    pub fn with_version(mut self, full_version: &str) -> Self {
+        match full_version.rsplit_once('-') {
+            Some((version, release)) => {
+                self.version = Some(version.to_string());
+                self.release = Some(release.to_string());
+            }
+            None => self.version = Some(full_version.to_string()),
+        }
+        self
+    }
+
+    /// "version-release" as pacman prints it, when known.
+    pub fn full_version(&self) -> Option<String> {
+        let version = self.version.as_ref()?;
+        Some(match &self.release {
+            Some(release) => format!("{}-{}", version, release),
+            None => version.clone(),
+        })
+    }
+}
+
+// installed.json before per-package records: just the package names
+#[derive(Deserialize)]
+struct LegacyDb {
+    packages: HashSet<String>,
+    #[serde(default)]
+    held: HashSet<String>,
+}
+
+impl LegacyDb {
+    // Fill in what pacman still knows about each package, `local` being its
+    // details of the tracked ones
+    fn migrate(self, local: &[LocalPackage], path: PathBuf) -> PackageDb {
+        let packages = self
+            .packages
+            .iter()
+            .map(|name| {
+                let record = local
+                    .iter()
+                    .find(|p| &p.name == name)
+                    .map(InstalledPackage::from_local)
+                    .unwrap_or_default();
+                (name.clone(), record)
+            })
+            .collect();
+        PackageDb {
+            packages,
+            held: self.held,
+            path,
+        }
+    }
+}
+
+impl PackageDb {
+    /// The database in the state directory `dir`, see [`crate::paths::state_dir`].
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join("installed.json")
+    }
+
+    /// Load the database kept in `dir`, empty when there is none yet. A legacy
+    /// database is migrated with what `local` knows about its packages; one
+    /// that can't be read is moved aside rather than overwritten by the next save.
+    pub fn load(dir: &Path, local: &dyn LocalPackages) -> Self {
+        let path = Self::path(dir);
+        let empty = || Self {
+            path: path.clone(),
+            ..Self::default()
+        };
+        if !path.exists() {
+            return empty();
+        }
+        let data = fs::read_to_string(&path).unwrap_or_default();
+        if let Ok(db) = serde_json::from_str::<Self>(&data) {
+            return Self { path, ..db };
+        }
+        match serde_json::from_str::<LegacyDb>(&data) {
+            Ok(legacy) => {
+                log::info!("Migrating {} to the new format", path.display());
+                let names: Vec<&str> = legacy.packages.iter().map(String::as_str).collect();
+                let local = local.query_local_packages(&names);
+                let db = legacy.migrate(&local, path);
+                // Migrated again next time when this fails
+                if let Err(e) = db.save() {
+                    log::warn!("Failed to save the migrated database: {}", e);
+                }
+                db
+            }
+            Err(e) => {
+                let mut backup = path.as_os_str().to_owned();
+                backup.push(format!(".{}.corrupt", now()));
+                let backup = PathBuf::from(backup);
+                match fs::rename(&path, &backup) {
+                    Ok(()) => log::warn!(
+                        "{} is unreadable ({}), moved it to {} and started over",
+                        path.display(),
+                        e,
+                        backup.display()
+                    ),
+                    Err(rename) => log::error!(
+                        "{} is unreadable ({}) and couldn't be moved aside: {}",
+                        path.display(),
+                        e,
+                        rename
+                    ),
+                }
+                empty()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let data = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        write_atomic(&self.path, data.as_bytes())?;
+        Ok(())
+    }
+
+    /// Track a package installed outside of crafty with what `local` knows about it.
+    pub fn add(&mut self, local: &dyn LocalPackages, pkg: &str) -> Result<()> {
+        let record = local
+            .query_local_packages(&[pkg])
+            .first()
+            .map(InstalledPackage::from_local)
+            .unwrap_or_default();
+        self.record(pkg, record)
+    }
+
+    /// Track `pkg` with the details of its installation, replacing older ones.
+    /// Tags and the note are the user's, they survive reinstalls and upgrades.
+    pub fn record(&mut self, pkg: &str, mut record: InstalledPackage) -> Result<()> {
+        if let Some(old) = self.packages.get(pkg) {
+            if record.tags.is_empty() {
+                record.tags = old.tags.clone();
+            }
+            record.note = record.note.or_else(|| old.note.clone());
+        }
+        self.packages.insert(pkg.to_string(), record);
+        self.save()
+    }
+
+    pub fn get(&self, pkg: &str) -> Option<&InstalledPackage> {
+        self.packages.get(pkg)
+    }
+
+    pub fn remove(&mut self, pkg: &str) -> Result<()> {
+        self.packages.remove(pkg);
+        self.save()
+    }
+
+    pub fn contains(&self, pkg: &str) -> bool {
+        self.packages.contains_key(pkg)
+    }
+
+    /// Held in the DB or matched by one of the `ignore` patterns.
+    pub fn is_held(&self, pkg: &str, ignore: &[String]) -> bool {
+        self.held.contains(pkg) || ignore.iter().any(|pattern| glob_match(pattern, pkg))
+    }
+
+    /// Managed packages in name order.
+    pub fn sorted(&self) -> Vec<&String> {
+        self.packages.keys().collect()
+    }
+
+    /// Add `tags` to a managed package, or take them off when `remove` is set.
+    /// Returns false when crafty doesn't track `pkg`.
+    pub fn tag(&mut self, pkg: &str, tags: &[String], remove: bool) -> bool {
+        let Some(record) = self.packages.get_mut(pkg) else {
+            return false;
+        };
+        if remove {
+            record.tags.retain(|tag| !tags.contains(tag));
+        } else {
+            record.tags.extend(tags.iter().cloned());
+            record.tags.sort();
+            record.tags.dedup();
+        }
+        true
+    }
+
+    /// Replace the note of a managed package, `None` clears it. Returns false
+    /// when crafty doesn't track `pkg`.
+    pub fn set_note(&mut self, pkg: &str, note: Option<&str>) -> bool {
+        let Some(record) = self.packages.get_mut(pkg) else {
+            return false;
+        };
+        record.note = note.map(str::to_string);
+        true
+    }
+
+    /// Managed packages carrying `tag`, in name order.
+    pub fn tagged(&self, tag: &str) -> Vec<&String> {
+        self.packages
+            .iter()
+            .filter(|(_, record)| record.tags.iter().any(|t| t == tag))
+            .map(|(name, _)| name)
+            .collect()
+    }
+}
+
+/// Exclusive hold on the database across processes, released when dropped.
+/// Taking it again while this process already holds it doesn't block.
+pub struct DbLock {
+    _file: Arc<File>,
+}
+
+// The locks this process holds by path, so nested transactions share them
+static HELD: Mutex<Vec<(PathBuf, Weak<File>)>> = Mutex::new(Vec::new());
+
+impl DbLock {
+    /// The lock of the database in the state directory `dir`.
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join("db.lck")
+    }
+
+    /// Take the lock of the database in `dir`, calling `waiting` first when
+    /// another process holds it.
+    pub fn acquire(dir: &Path, waiting: impl FnOnce(&Path)) -> io::Result<Self> {
+        let path = Self::path(dir);
+        let mut held = HELD.lock().unwrap();
+        held.retain(|(_, file)| file.strong_count() > 0);
+        if let Some(file) = held
+            .iter()
+            .find(|(held_path, _)| *held_path == path)
+            .and_then(|(_, file)| file.upgrade())
+        {
+            return Ok(Self { _file: file });
+        }
+
+        fs::create_dir_all(path.parent().unwrap())?;
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+        let fd = file.as_raw_fd();
+        if unsafe { libc::flock(fd, libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::WouldBlock {
+                return Err(err);
+            }
+            waiting(&path);
+            if unsafe { libc::flock(fd, libc::LOCK_EX) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        let file = Arc::new(file);
+        held.push((path, Arc::downgrade(&file)));
+        Ok(Self { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RecordingInstaller, testutil::TempDir};
+
+    #[test]
+    fn migrates_the_legacy_database() {
+        let dir = TempDir::new("db");
+        fs::write(
+            PackageDb::path(dir.path()),
+            r#"{"packages": ["archcraft-openbox", "archcraft-gone"], "held": ["archcraft-openbox"]}"#,
+        )
+        .unwrap();
+        let local = RecordingInstaller::new().with_installed("archcraft-openbox", "2:3.6.1-14");
+
+        let db = PackageDb::load(dir.path(), &local);
+
+        assert_eq!(db.sorted(), ["archcraft-gone", "archcraft-openbox"]);
+        let openbox = db.get("archcraft-openbox").unwrap();
+        assert_eq!(openbox.version.as_deref(), Some("2:3.6.1"));
+        assert_eq!(openbox.release.as_deref(), Some("14"));
+        assert_eq!(openbox.file, None);
+        assert_eq!(db.get("archcraft-gone"), Some(&InstalledPackage::default()));
+        assert!(db.held.contains("archcraft-openbox"));
+
+        // Saved in the new format, so it loads as is from now on
+        let data = fs::read_to_string(PackageDb::path(dir.path())).unwrap();
+        assert!(serde_json::from_str::<PackageDb>(&data).is_ok());
+        let reloaded = PackageDb::load(dir.path(), &RecordingInstaller::new());
+        assert_eq!(
+            reloaded
+                .get("archcraft-openbox")
+                .unwrap()
+                .full_version()
+                .as_deref(),
+            Some("2:3.6.1-14")
+        );
+    }
+
+    #[test]
+    fn loads_an_empty_database_when_there_is_none() {
+        let dir = TempDir::new("db");
+        let local = RecordingInstaller::new();
+        assert!(PackageDb::load(dir.path(), &local).packages.is_empty());
+
+        fs::write(PackageDb::path(dir.path()), "not json").unwrap();
+        let mut db = PackageDb::load(dir.path(), &local);
+        assert!(db.packages.is_empty());
+
+        // The unreadable one is kept next to the fresh start
+        db.record("archcraft-openbox", InstalledPackage::default())
+            .unwrap();
+        let corrupt: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".corrupt"))
+            .collect();
+        assert_eq!(corrupt.len(), 1);
+        assert_eq!(
+            fs::read_to_string(dir.join(&corrupt[0])).unwrap(),
+            "not json"
+        );
+    }
+}
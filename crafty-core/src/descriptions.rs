@@ -0,0 +1,126 @@
+//! Descriptions of repository packages from their .PKGINFO, cached for `search --desc`.
+
+use crate::{
+    RepoClient, Result, archive::read_pkginfo, paths, search::SearchPattern, util::write_atomic,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct DescriptionIndex {
+    /// Package file name -> pkgdesc
+    pub packages: HashMap<String, String>,
+}
+
+/// A package whose description contains every pattern.
+#[derive(Debug, Clone)]
+pub struct DescriptionMatch {
+    pub file: String,
+    pub description: String,
+    /// Matched character positions in the description
+    pub positions: Vec<usize>,
+}
+
+impl DescriptionIndex {
+    pub fn path() -> PathBuf {
+        paths::cache_dir().join("descriptions.json")
+    }
+
+    pub fn load() -> Self {
+        let path = Self::path();
+        if path.exists() {
+            let data = fs::read_to_string(&path).unwrap_or_default();
+            serde_json::from_str(&data).unwrap_or_default()
+        } else {
+            Self::default()
+        }
+    }
+
+    pub fn save(&self) {
+        let path = Self::path();
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir).unwrap();
+        let data = serde_json::to_string(self).unwrap();
+        write_atomic(&path, data.as_bytes()).unwrap();
+    }
+
+    /// Packages whose description matches all `patterns`, in file name order.
+    /// Fuzzy patterns match literally here, a subsequence of a whole sentence
+    /// matches almost anything.
+    pub fn search(&self, patterns: &[SearchPattern]) -> Vec<DescriptionMatch> {
+        let mut matches: Vec<DescriptionMatch> = self
+            .packages
+            .iter()
+            .filter_map(|(file, description)| {
+                let mut positions = Vec::new();
+                for pattern in patterns {
+                    positions.extend(pattern.find(description)?);
+                }
+                Some(DescriptionMatch {
+                    file: file.clone(),
+                    description: description.clone(),
+                    positions,
+                })
+            })
+            .collect();
+        matches.sort_by(|a, b| a.file.cmp(&b.file));
+        matches
+    }
+
+    /// Download every repository package missing from the index into `staging`,
+    /// read its description and drop entries for files no longer in the
+    /// repository. `progress` is called with (current, total, package file)
+    /// before each download.
+    pub fn update(
+        &mut self,
+        repo: &dyn RepoClient,
+        staging: &Path,
+        mut progress: impl FnMut(usize, usize, &str),
+    ) -> Result<()> {
+        let remote: HashSet<String> = repo.packages()?.into_iter().collect();
+        self.packages.retain(|file, _| remote.contains(file));
+
+        let mut missing: Vec<&String> = remote
+            .iter()
+            .filter(|file| !self.packages.contains_key(*file))
+            .collect();
+        missing.sort();
+
+        for (n, package_file) in missing.iter().enumerate() {
+            progress(n + 1, missing.len(), package_file);
+            let Ok(path) = repo.download_package(package_file, staging) else {
+                continue;
+            };
+            if let Some(info) = read_pkginfo(&path) {
+                self.packages.insert(package_file.to_string(), info.desc);
+            }
+            let _ = fs::remove_file(&path);
+        }
+
+        self.save();
+        Ok(())
+    }
+}
+
+/// Store the description of a freshly downloaded archive in the index.
+pub fn record(package_file: &str, description: &str) {
+    // Parallel downloads record concurrently, don't lose each other's entries
+    static RECORDING: Mutex<()> = Mutex::new(());
+    // Most likely an unreadable .PKGINFO, leave it for `update` to retry
+    if description.is_empty() {
+        return;
+    }
+    let _guard = RECORDING.lock().unwrap();
+    let mut index = DescriptionIndex::load();
+    if index.packages.get(package_file).map(String::as_str) != Some(description) {
+        index
+            .packages
+            .insert(package_file.to_string(), description.to_string());
+        index.save();
+    }
+}
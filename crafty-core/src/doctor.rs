@@ -0,0 +1,288 @@
+//! Environment checks behind `crafty doctor`, each with a suggested fix.
+
+use crate::{
+    Config, Elevate, Error, Manager, PackageDb,
+    archive::Compression,
+    paths,
+    util::{find_program, format_size},
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+// Helpers crafty shells out to, and what breaks without them
+const HELPERS: [(&str, &str); 3] = [
+    ("bsdtar", "reading package metadata and file lists"),
+    ("vercmp", "comparing versions the way pacman does"),
+    ("git", "building packages from the AUR"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about a warning or error
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warning(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Warning,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn error(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            status: Status::Error,
+            ..Self::warning(name, detail, fix)
+        }
+    }
+}
+
+/// Run every check; the network is only touched to fetch the index.
+pub fn diagnose(manager: &Manager) -> Vec<Check> {
+    vec![
+        pacman(),
+        elevation(manager.installer.elevate()),
+        helpers(),
+        config(),
+        repository(manager),
+        database(manager),
+        directory("state", paths::state_dir()),
+        directory("cache", paths::cache_dir()),
+        partial_downloads(&manager.config.staging_dir()),
+        legacy_dir(),
+    ]
+}
+
+fn pacman() -> Check {
+    const NAME: &str = "pacman";
+    let output = Command::new("pacman")
+        .env("LC_ALL", "C")
+        .arg("--version")
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let version = stdout
+                .lines()
+                .find_map(|l| l.split_once("Pacman v").map(|(_, v)| v.trim().to_string()))
+                .unwrap_or_default();
+            Check::ok(NAME, format!("pacman {}", version).trim_end().to_string())
+        }
+        _ => Check::error(
+            NAME,
+            "pacman is not available",
+            "crafty drives pacman and only works on Arch-based systems",
+        ),
+    }
+}
+
+fn elevation(elevate: Elevate) -> Check {
+    const NAME: &str = "privileges";
+    let tool = match elevate {
+        Elevate::Sudo => "sudo",
+        Elevate::Doas => "doas",
+        Elevate::Pkexec => "pkexec",
+        Elevate::None => return Check::ok(NAME, "pacman runs directly"),
+    };
+    match find_program(tool) {
+        Some(path) => Check::ok(NAME, format!("using {}", path.display())),
+        None => Check::error(
+            NAME,
+            format!("{} is not installed", tool),
+            "Install it, or pick another tool with the `elevate` config key or --elevate",
+        ),
+    }
+}
+
+fn helpers() -> Check {
+    const NAME: &str = "helpers";
+    let missing: Vec<String> = HELPERS
+        .iter()
+        .filter(|(program, _)| find_program(program).is_none())
+        .map(|(program, purpose)| format!("{} ({})", program, purpose))
+        .collect();
+    if missing.is_empty() {
+        Check::ok(NAME, "all helper programs found")
+    } else {
+        Check::warning(
+            NAME,
+            format!("missing {}", missing.join(", ")),
+            "Install libarchive, pacman's vercmp, git and coreutils",
+        )
+    }
+}
+
+fn config() -> Check {
+    const NAME: &str = "config";
+    let path = Config::path();
+    match Config::load() {
+        Ok(_) if path.exists() => Check::ok(NAME, path.display().to_string()),
+        Ok(_) => Check::ok(NAME, "no config file, using defaults"),
+        Err(e) => Check::error(
+            NAME,
+            e.to_string(),
+            format!("Fix or remove {}", path.display()),
+        ),
+    }
+}
+
+fn repository(manager: &Manager) -> Check {
+    const NAME: &str = "repository";
+    match manager.repo.index() {
+        Ok(index) if manager.config.repos.len() > 1 => Check::ok(
+            NAME,
+            format!(
+                "{} files in {} repositories",
+                index.len(),
+                manager.config.repos.len()
+            ),
+        ),
+        Ok(index) => Check::ok(
+            NAME,
+            format!("{} files on {}", index.len(), manager.config.git_ref),
+        ),
+        Err(e @ Error::RateLimited { .. }) => Check::error(
+            NAME,
+            e.to_string(),
+            "Set `github_token` in the config or GITHUB_TOKEN, or wait for the reset",
+        ),
+        Err(e) => Check::error(
+            NAME,
+            e.to_string(),
+            format!(
+                "Check the network connection and the `proxy`/`ca_bundle` config keys, \
+                 and that the ref '{}' exists",
+                manager.config.git_ref
+            ),
+        ),
+    }
+}
+
+// Readable and in line with what pacman has installed
+fn database(manager: &Manager) -> Check {
+    const NAME: &str = "database";
+    let path = PackageDb::path(manager.state_dir());
+    if path.exists() {
+        let parsed = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_str::<PackageDb>(&data).map_err(|e| e.to_string()));
+        if let Err(e) = parsed {
+            return Check::error(
+                NAME,
+                format!("{} is unreadable: {}", path.display(), e),
+                "Move it away and run `crafty adopt` to rebuild it from pacman",
+            );
+        }
+    }
+
+    let db = manager.load_db();
+    let stale = manager.stale_packages(&db);
+    if stale.is_empty() {
+        Check::ok(NAME, format!("{} packages tracked", db.packages.len()))
+    } else {
+        Check::warning(
+            NAME,
+            format!("no longer installed: {}", stale.join(", ")),
+            "Run `crafty db sync` to drop them",
+        )
+    }
+}
+
+fn directory(name: &'static str, dir: PathBuf) -> Check {
+    let probe = dir.join(".crafty-doctor");
+    let writable = fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&probe, b""))
+        .and_then(|_| fs::remove_file(&probe));
+    match writable {
+        Ok(()) => Check::ok(name, dir.display().to_string()),
+        Err(e) => Check::error(
+            name,
+            format!("{} is not writable: {}", dir.display(), e),
+            format!("Fix the ownership of {}", dir.display()),
+        ),
+    }
+}
+
+// Archives an interrupted download left behind
+fn partial_downloads(staging: &Path) -> Check {
+    const NAME: &str = "downloads";
+    let Ok(entries) = fs::read_dir(staging) else {
+        return Check::ok(NAME, "no downloads yet");
+    };
+    let broken: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            let name = p.to_string_lossy();
+            name.ends_with(".part") || Compression::of(&name).is_some_and(|c| !c.matches(p))
+        })
+        .collect();
+    if broken.is_empty() {
+        return Check::ok(NAME, staging.display().to_string());
+    }
+    let size: u64 = broken
+        .iter()
+        .filter_map(|p| fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum();
+    Check::warning(
+        NAME,
+        format!(
+            "{} incomplete archive(s) in {} ({})",
+            broken.len(),
+            staging.display(),
+            format_size(size)
+        ),
+        format!(
+            "Delete them: rm {}",
+            broken
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+    )
+}
+
+fn legacy_dir() -> Check {
+    const NAME: &str = "layout";
+    let legacy = paths::legacy_dir();
+    if legacy.exists() {
+        Check::warning(
+            NAME,
+            format!("{} still exists", legacy.display()),
+            format!(
+                "Its files clash with ones in {} or {}; merge them by hand and delete it",
+                paths::config_dir().display(),
+                paths::state_dir().display()
+            ),
+        )
+    } else {
+        Check::ok(NAME, "XDG directories")
+    }
+}
@@ -0,0 +1,174 @@
+//! Error type shared by all crafty-core operations.
+
+use crate::{
+    manager::Unregistered,
+    transaction::TransactionError,
+    util::{format_size, format_timestamp},
+};
+use std::{fmt, io, path::PathBuf};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The requested package does not exist in the ArchCraft repository.
+    NotFound(String),
+    /// The package is not installed on this system.
+    NotInstalled(String),
+    /// The repository index could not be fetched or parsed.
+    Index(String),
+    /// A network request failed, after retries for transient errors.
+    Http(reqwest::Error),
+    /// A download broke off while reading the response body, after retries.
+    Interrupted(io::Error),
+    /// GitHub's rate limit is exhausted until `reset` (seconds since the epoch).
+    RateLimited {
+        reset: Option<u64>,
+        authenticated: bool,
+    },
+    /// A downloaded file is not a valid package archive in the format its name says.
+    InvalidArchive(PathBuf),
+    /// The download directory can't hold a package archive.
+    NoSpace {
+        dir: PathBuf,
+        needed: u64,
+        available: u64,
+    },
+    /// pacman (or a helper it needs) failed.
+    Pacman(String),
+    /// A pacman transaction failed, with the reasons pacman gave.
+    Transaction(TransactionError),
+    /// pacman succeeded but didn't register the package at the installed version.
+    NotRegistered(Unregistered),
+    /// The configuration is invalid.
+    Config(String),
+    /// A pre-transaction hook failed, nothing was changed.
+    Hook(String),
+    /// No package group with this name exists.
+    UnknownGroup(String),
+    /// No theme bundle with this name exists in the repository.
+    UnknownTheme(String),
+    /// The package has no entry in the lock file.
+    NotLocked(String),
+    /// A downloaded file doesn't match the checksum recorded in the lock file.
+    ChecksumMismatch {
+        file: String,
+        expected: String,
+        actual: String,
+    },
+    /// No config backup of the package (at the given timestamp) exists.
+    NoBackup(String),
+    /// crafty couldn't update itself from its GitHub releases.
+    SelfUpdate(String),
+    /// A file handed to crafty (export, manifest, ...) could not be parsed.
+    InvalidFile(String),
+    /// Building or installing a package from the AUR failed.
+    Aur(String),
+    /// An argument given on the command line is malformed, e.g. a bad regular expression.
+    InvalidArgument(String),
+    /// Managed packages `crafty verify` found missing, modified or at another version.
+    Damaged(Vec<String>),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(pkg) => write!(f, "Package '{}' not found in the repository.", pkg),
+            Error::NotInstalled(pkg) => write!(f, "Package '{}' is not installed.", pkg),
+            Error::Index(msg) => write!(f, "Failed to fetch package list: {}", msg),
+            Error::Http(e) => write!(f, "Download failed: {}", e),
+            Error::Interrupted(e) => write!(f, "Download interrupted: {}", e),
+            Error::RateLimited {
+                reset,
+                authenticated,
+            } => {
+                write!(f, "GitHub rate limit exceeded")?;
+                if let Some(reset) = reset {
+                    write!(f, " until {} UTC", format_timestamp(*reset))?;
+                }
+                if *authenticated {
+                    write!(f, ".")
+                } else {
+                    write!(
+                        f,
+                        ". Set `github_token` in the config or GITHUB_TOKEN to raise the limit."
+                    )
+                }
+            }
+            Error::InvalidArchive(path) => {
+                write!(f, "{} is not a valid package archive.", path.display())
+            }
+            Error::NoSpace {
+                dir,
+                needed,
+                available,
+            } => write!(
+                f,
+                "Not enough space in {}: {} needed, {} available. Set `download_dir` in the config to use another directory.",
+                dir.display(),
+                format_size(*needed),
+                format_size(*available)
+            ),
+            Error::Pacman(msg) => write!(f, "{}", msg),
+            Error::Transaction(e) => write!(f, "{}", e),
+            Error::NotRegistered(Unregistered {
+                name,
+                expected,
+                found: Some(found),
+            }) => write!(
+                f,
+                "pacman reports {} {} instead of {}; it skipped or downgraded the package.",
+                name, found, expected
+            ),
+            Error::NotRegistered(Unregistered { name, expected, .. }) => write!(
+                f,
+                "pacman reported success but didn't register {} {}.",
+                name, expected
+            ),
+            Error::Config(msg) => write!(f, "Invalid configuration: {}", msg),
+            Error::Hook(msg) => write!(f, "{}", msg),
+            Error::InvalidFile(msg) => write!(f, "{}", msg),
+            Error::InvalidArgument(msg) => write!(f, "{}", msg),
+            Error::SelfUpdate(msg) => write!(f, "Self-update failed: {}", msg),
+            Error::Aur(msg) => write!(f, "AUR: {}", msg),
+            Error::Damaged(pkgs) => write!(f, "Damaged packages: {}", pkgs.join(", ")),
+            Error::UnknownGroup(name) => write!(f, "Group '{}' not found.", name),
+            Error::UnknownTheme(name) => write!(f, "Theme '{}' not found in the repository.", name),
+            Error::NoBackup(what) => write!(f, "No backup of {} found.", what),
+            Error::NotLocked(pkg) => write!(f, "Package '{}' is not in the lock file.", pkg),
+            Error::ChecksumMismatch {
+                file,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Checksum mismatch for {}: expected {}, got {}",
+                file, expected, actual
+            ),
+            Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
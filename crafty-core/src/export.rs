@@ -0,0 +1,69 @@
+//! Portable snapshot of the managed package set, for replicating a setup elsewhere.
+
+use crate::{Error, LocalPackages, PackageDb, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Export {
+    pub packages: Vec<ExportedPackage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExportedPackage {
+    pub name: String,
+    /// Version installed when exported; informational, imports install the latest
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub held: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl Export {
+    /// Managed packages with the versions `local` has installed, tags and
+    /// notes, in name order.
+    pub fn from_db(db: &PackageDb, local: &dyn LocalPackages) -> Self {
+        let names = db.sorted();
+        let query: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+        let local = local.query_local_packages(&query);
+        let packages = names
+            .into_iter()
+            .map(|name| ExportedPackage {
+                version: local
+                    .iter()
+                    .find(|p| &p.name == name)
+                    .map(|p| p.version.clone()),
+                held: db.held.contains(name),
+                tags: db.packages[name].tags.clone(),
+                note: db.packages[name].note.clone(),
+                name: name.clone(),
+            })
+            .collect();
+        Self { packages }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn from_json(data: &str) -> Result<Self> {
+        serde_json::from_str(data)
+            .map_err(|e| Error::InvalidFile(format!("Not a crafty export: {}", e)))
+    }
+}
+
+/// What importing an [`Export`] would do on this machine.
+#[derive(Debug, Default)]
+pub struct ImportPlan {
+    /// Not installed yet, available in the repository
+    pub install: Vec<String>,
+    /// Already installed but not tracked by crafty
+    pub adopt: Vec<String>,
+    /// Installed and tracked already
+    pub present: Vec<String>,
+    /// No longer in the repository
+    pub unavailable: Vec<String>,
+}
@@ -0,0 +1,133 @@
+//! File listings of repository packages, cached so lookups don't need the archives.
+
+use crate::{RepoClient, Result, archive::list_archive, paths, util::write_atomic};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct FilesIndex {
+    /// Package file name -> paths shipped by that archive, without a leading slash
+    pub packages: HashMap<String, Vec<String>>,
+}
+
+impl FilesIndex {
+    pub fn path() -> PathBuf {
+        paths::cache_dir().join("files.json")
+    }
+
+    pub fn load() -> Self {
+        let path = Self::path();
+        if path.exists() {
+            let data = fs::read_to_string(&path).unwrap_or_default();
+            serde_json::from_str(&data).unwrap_or_default()
+        } else {
+            Self::default()
+        }
+    }
+
+    pub fn save(&self) {
+        let path = Self::path();
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir).unwrap();
+        let data = serde_json::to_string(self).unwrap();
+        write_atomic(&path, data.as_bytes()).unwrap();
+    }
+
+    /// Packages shipping `query` as (package file, matching path) pairs. A path
+    /// matches the entry itself or anything below it, a bare name matches the
+    /// file name in any directory.
+    pub fn owners(&self, query: &str) -> Vec<(String, String)> {
+        let wanted = query.trim_start_matches('/').trim_end_matches('/');
+        let bare = !wanted.contains('/');
+        let mut owners = Vec::new();
+        for (package_file, entries) in &self.packages {
+            for entry in entries {
+                let entry = entry.trim_end_matches('/');
+                let hit = if bare {
+                    entry.rsplit('/').next() == Some(wanted)
+                } else {
+                    entry == wanted || entry.starts_with(&format!("{}/", wanted))
+                };
+                if hit {
+                    owners.push((package_file.clone(), format!("/{}", entry)));
+                    // One hit per package is enough for directories
+                    if !bare {
+                        break;
+                    }
+                }
+            }
+        }
+        owners.sort();
+        owners
+    }
+
+    /// Package files shipping an executable called `command` in a directory
+    /// of the default `$PATH`, sorted.
+    pub fn providers(&self, command: &str) -> Vec<String> {
+        const BIN_DIRS: [&str; 4] = ["usr/bin/", "usr/local/bin/", "bin/", "usr/sbin/"];
+        let mut providers: Vec<String> = self
+            .packages
+            .iter()
+            .filter(|(_, entries)| {
+                entries.iter().any(|entry| {
+                    BIN_DIRS
+                        .iter()
+                        .any(|dir| entry.strip_prefix(dir) == Some(command))
+                })
+            })
+            .map(|(package_file, _)| package_file.clone())
+            .collect();
+        providers.sort();
+        providers
+    }
+
+    /// Download every repository package missing from the index into `staging`,
+    /// list it and drop entries for files no longer in the repository.
+    /// `progress` is called with (current, total, package file) before each download.
+    pub fn update(
+        &mut self,
+        repo: &dyn RepoClient,
+        staging: &Path,
+        mut progress: impl FnMut(usize, usize, &str),
+    ) -> Result<()> {
+        let remote: HashSet<String> = repo.packages()?.into_iter().collect();
+        self.packages.retain(|file, _| remote.contains(file));
+
+        let mut missing: Vec<&String> = remote
+            .iter()
+            .filter(|file| !self.packages.contains_key(*file))
+            .collect();
+        missing.sort();
+
+        for (n, package_file) in missing.iter().enumerate() {
+            progress(n + 1, missing.len(), package_file);
+            let Ok(path) = repo.download_package(package_file, staging) else {
+                continue;
+            };
+            if let Some(entries) = list_archive(&path) {
+                self.packages.insert(package_file.to_string(), entries);
+            }
+            let _ = fs::remove_file(&path);
+        }
+
+        self.save();
+        Ok(())
+    }
+}
+
+/// Store the listing of a freshly downloaded archive in the index.
+pub fn record(package_file: &str, archive: &Path) {
+    // Parallel downloads record concurrently, don't lose each other's entries
+    static RECORDING: Mutex<()> = Mutex::new(());
+    if let Some(entries) = list_archive(archive) {
+        let _guard = RECORDING.lock().unwrap();
+        let mut index = FilesIndex::load();
+        index.packages.insert(package_file.to_string(), entries);
+        index.save();
+    }
+}
@@ -0,0 +1,108 @@
+//! Fuzzy subsequence matching in the spirit of fzf/skim.
+
+const MATCH: i64 = 16;
+const BOUNDARY_BONUS: i64 = 8;
+const CONSECUTIVE_BONUS: i64 = 12;
+const GAP_PENALTY: i64 = 1;
+
+/// Score `pattern` against `text`, returning the score and the matched character
+/// positions, or `None` when the pattern is not a subsequence of the text.
+/// Matching is case-insensitive; higher scores are better.
+pub fn score(pattern: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    if pattern.is_empty() || pattern.len() > text.len() {
+        return None;
+    }
+
+    let n = text.len();
+    // best[i][j]: best score with pattern[i] matched at text[j], plus the previous match
+    let mut best: Vec<Vec<Option<(i64, usize)>>> = vec![vec![None; n]; pattern.len()];
+
+    for (i, &pc) in pattern.iter().enumerate() {
+        for j in 0..n {
+            if text[j] != pc {
+                continue;
+            }
+            let base = MATCH
+                + if is_boundary(&text, j) {
+                    BOUNDARY_BONUS
+                } else {
+                    0
+                };
+            if i == 0 {
+                best[i][j] = Some((base, usize::MAX));
+                continue;
+            }
+            best[i][j] = (0..j)
+                .filter_map(|k| {
+                    let (prev, _) = best[i - 1][k]?;
+                    let link = if k + 1 == j {
+                        CONSECUTIVE_BONUS
+                    } else {
+                        -GAP_PENALTY * (j - k - 1) as i64
+                    };
+                    Some((prev + base + link, k))
+                })
+                .max_by_key(|(s, _)| *s);
+        }
+    }
+
+    let last = pattern.len() - 1;
+    let (end, (total, _)) = best[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, cell)| cell.map(|c| (j, c)))
+        .max_by_key(|(_, (s, _))| *s)?;
+
+    // Walk the back-pointers to recover the matched positions
+    let mut positions = vec![end];
+    let mut j = end;
+    for i in (1..=last).rev() {
+        let (_, prev) = best[i][j]?;
+        positions.push(prev);
+        j = prev;
+    }
+    positions.reverse();
+
+    // Prefer shorter names when patterns match equally well
+    Some((total - (n as i64 / 8), positions))
+}
+
+fn is_boundary(text: &[char], j: usize) -> bool {
+    j == 0 || matches!(text[j - 1], '-' | '_' | '.' | ' ' | '/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matched(pattern: &str, text: &str) -> String {
+        let (_, positions) = score(pattern, text).unwrap();
+        let chars: Vec<char> = text.chars().collect();
+        positions.into_iter().map(|i| chars[i]).collect()
+    }
+
+    #[test]
+    fn matches_subsequences_ignoring_case() {
+        assert_eq!(score("ob", "openbox").unwrap().1, [0, 4]);
+        assert_eq!(score("ac", "archcraft-openbox").unwrap().1, [0, 2]);
+        assert_eq!(matched("OBX", "archcraft-openbox"), "obx");
+        assert_eq!(
+            score("poly", "archcraft-polybar").unwrap().1,
+            [10, 11, 12, 13]
+        );
+        assert!(score("xo", "openbox").is_none());
+        assert!(score("", "openbox").is_none());
+        assert!(score("openboxes", "openbox").is_none());
+    }
+
+    #[test]
+    fn prefers_consecutive_matches_at_word_boundaries() {
+        let rank = |text| score("poly", text).unwrap().0;
+        assert!(rank("archcraft-polybar") > rank("archcraft-pkg-only-yes"));
+        assert!(rank("archcraft-polybar") > rank("archcraft-xpolybar"));
+        // Shorter names win when the match itself is as good
+        assert!(rank("polybar") > rank("polybar-extended-configuration"));
+    }
+}
@@ -0,0 +1,68 @@
+//! Package groups, installed together with `crafty install @group`.
+//!
+//! Groups are inferred from the first name segment after `archcraft-`, so
+//! `archcraft-bspwm` and `archcraft-bspwm-config` form the `bspwm` group.
+//! Curated groups from the config replace inferred groups of the same name.
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone)]
+pub struct Group {
+    pub name: String,
+    /// Package names, sorted
+    pub packages: Vec<String>,
+    /// Defined in the config rather than inferred
+    pub curated: bool,
+}
+
+// "archcraft-bspwm-config" -> "bspwm"
+fn prefix_of(package: &str) -> Option<&str> {
+    let rest = package.strip_prefix("archcraft-")?;
+    rest.split('-').next().filter(|s| !s.is_empty())
+}
+
+/// Inferred groups of at least two packages merged with the `curated` ones,
+/// sorted by name.
+pub fn build<'a>(
+    packages: impl IntoIterator<Item = &'a str>,
+    curated: &BTreeMap<String, Vec<String>>,
+) -> Vec<Group> {
+    let mut inferred: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for package in packages {
+        if let Some(prefix) = prefix_of(package) {
+            inferred
+                .entry(prefix)
+                .or_default()
+                .push(package.to_string());
+        }
+    }
+
+    let mut groups: BTreeMap<String, Group> = inferred
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(name, packages)| {
+            let group = Group {
+                name: name.to_string(),
+                packages,
+                curated: false,
+            };
+            (name.to_string(), group)
+        })
+        .collect();
+    for (name, packages) in curated {
+        let group = Group {
+            name: name.clone(),
+            packages: packages.clone(),
+            curated: true,
+        };
+        groups.insert(name.clone(), group);
+    }
+
+    groups
+        .into_values()
+        .map(|mut group| {
+            group.packages.sort();
+            group
+        })
+        .collect()
+}
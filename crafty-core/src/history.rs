@@ -0,0 +1,67 @@
+//! Append-only transaction log kept next to the package database.
+
+use crate::util::now;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Install,
+    Upgrade,
+    Remove,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Entry {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub action: Action,
+    pub package: String,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    pub success: bool,
+}
+
+/// The log in the state directory `dir`, see [`crate::paths::state_dir`].
+pub fn path(dir: &Path) -> PathBuf {
+    dir.join("history.log")
+}
+
+/// Append an entry to the log in `dir`; one JSON object per line, so a crash
+/// can at most lose the entry being written.
+pub fn record(
+    dir: &Path,
+    action: Action,
+    package: &str,
+    old_version: Option<&str>,
+    new_version: Option<&str>,
+    success: bool,
+) -> io::Result<()> {
+    let entry = Entry {
+        timestamp: now(),
+        action,
+        package: package.to_string(),
+        old_version: old_version.map(str::to_string),
+        new_version: new_version.map(str::to_string),
+        success,
+    };
+    let path = path(dir);
+    fs::create_dir_all(dir)?;
+    let line = serde_json::to_string(&entry).unwrap();
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", line)
+}
+
+/// All entries recorded in `dir`, oldest first; unreadable lines are skipped.
+pub fn load(dir: &Path) -> Vec<Entry> {
+    fs::read_to_string(path(dir))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
@@ -0,0 +1,101 @@
+//! User-defined scripts run around transactions.
+//!
+//! A hook is an executable named after its stage in the hooks directory, e.g.
+//! `hooks/post-install`, or any number of executables in `hooks/post-install.d/`,
+//! run in name order. Hooks run once per package as the invoking user with
+//! `CRAFTY_HOOK`, `CRAFTY_PACKAGE`, `CRAFTY_VERSION` and `CRAFTY_OLD_VERSION`
+//! (empty when unknown) in their environment, and `CRAFTY_ROOT`, the system
+//! being changed ("/" unless `--root` was given). A failing `pre-*` hook aborts the
+//! transaction before pacman runs.
+
+use crate::{Error, Result, paths};
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    PreInstall,
+    PostInstall,
+    PreUpgrade,
+    PostUpgrade,
+    PreRemove,
+    PostRemove,
+}
+
+impl Stage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::PreInstall => "pre-install",
+            Stage::PostInstall => "post-install",
+            Stage::PreUpgrade => "pre-upgrade",
+            Stage::PostUpgrade => "post-upgrade",
+            Stage::PreRemove => "pre-remove",
+            Stage::PostRemove => "post-remove",
+        }
+    }
+}
+
+/// The package a hook runs for.
+#[derive(Debug, Clone, Copy)]
+pub struct HookTarget<'a> {
+    pub package: &'a str,
+    pub version: Option<&'a str>,
+    pub old_version: Option<&'a str>,
+}
+
+pub fn dir() -> PathBuf {
+    paths::config_dir().join("hooks")
+}
+
+/// Executables registered for `stage`, in the order they run.
+pub fn scripts(stage: Stage) -> Vec<PathBuf> {
+    let dir = dir();
+    let mut scripts = Vec::new();
+    let single = dir.join(stage.name());
+    if is_executable(&single) {
+        scripts.push(single);
+    }
+    if let Ok(entries) = fs::read_dir(dir.join(format!("{}.d", stage.name()))) {
+        let mut found: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| is_executable(p))
+            .collect();
+        found.sort();
+        scripts.extend(found);
+    }
+    scripts
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+/// Run every script of `stage` for `target`. Stops at the first failing script
+/// and reports it as [`Error::Hook`].
+pub fn run(stage: Stage, target: HookTarget) -> Result<()> {
+    for script in scripts(stage) {
+        log::debug!("Running {} hook {}", stage.name(), script.display());
+        let status = Command::new(&script)
+            .env("CRAFTY_HOOK", stage.name())
+            .env("CRAFTY_PACKAGE", target.package)
+            .env("CRAFTY_VERSION", target.version.unwrap_or_default())
+            .env("CRAFTY_OLD_VERSION", target.old_version.unwrap_or_default())
+            .env("CRAFTY_ROOT", paths::root().unwrap_or(Path::new("/")))
+            .status()
+            .map_err(|e| Error::Hook(format!("{}: {}", script.display(), e)))?;
+        if !status.success() {
+            return Err(Error::Hook(format!(
+                "{} hook {} failed for {} ({})",
+                stage.name(),
+                script.display(),
+                target.package,
+                status
+            )));
+        }
+    }
+    Ok(())
+}
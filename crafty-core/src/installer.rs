@@ -0,0 +1,589 @@
+//! Carrying out transactions: the [`PackageInstaller`] trait, pacman run with
+//! root privileges, and an installer that only records what it was asked to do.
+//!
+//! There is no libalpm backend: the `alpm` bindings aren't a dependency of
+//! this crate. One would implement [`PackageInstaller`] over an `alpm::Alpm`
+//! handle behind an `alpm` cargo feature, overriding the [`LocalPackages`]
+//! queries with reads of the local database, and be handed to
+//! [`crate::Manager::with_backends`] like the installers here.
+
+use crate::{
+    Elevate, Error, Event, EventHook, Result,
+    archive::{self, Compression, strip_version_constraint},
+    pacman::{self, FileProblem, LocalPackage},
+    paths,
+    transaction::TransactionError,
+    zstd,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+};
+
+/// Flags forwarded to every pacman transaction.
+#[derive(Debug, Clone, Default)]
+pub struct PacmanOptions {
+    /// Pass --noconfirm so pacman asks no questions
+    pub noconfirm: bool,
+    /// Extra arguments handed to pacman verbatim
+    pub extra_args: Vec<String>,
+}
+
+/// What is installed, as far as the package manager knows. The defaults ask
+/// pacman through [`crate::pacman`]; an installer that doesn't run pacman
+/// answers from the transactions it carried out instead.
+pub trait LocalPackages: Send + Sync {
+    /// Installed version of `pkg`, `None` when it isn't installed.
+    fn installed_version(&self, pkg: &str) -> Option<String> {
+        pacman::installed_version(pkg)
+    }
+
+    /// Names of all installed packages, `None` if they can't be listed.
+    fn installed_package_names(&self) -> Option<HashSet<String>> {
+        pacman::installed_package_names()
+    }
+
+    /// Installed (name, version) pairs, optionally limited to foreign packages.
+    fn installed_package_versions(&self, foreign_only: bool) -> Option<Vec<(String, String)>> {
+        pacman::installed_package_versions(foreign_only)
+    }
+
+    /// Details of the given packages; unknown names are skipped.
+    fn query_local_packages(&self, names: &[&str]) -> Vec<LocalPackage> {
+        pacman::query_local_packages(names)
+    }
+
+    /// Packages installed as dependencies that nothing requires anymore.
+    fn orphaned_packages(&self) -> Vec<String> {
+        pacman::orphaned_packages()
+    }
+
+    /// The entries of `depends` no installed package satisfies.
+    fn unsatisfied_dependencies(&self, depends: &[String]) -> Vec<String> {
+        pacman::unsatisfied_dependencies(depends)
+    }
+
+    /// Absolute paths installed by `pkg`, `None` when it isn't installed.
+    fn package_files(&self, pkg: &str) -> Option<Vec<String>> {
+        pacman::package_files(pkg)
+    }
+
+    /// Files of `pkgs` differing from what was installed, `None` when they
+    /// can't be checked.
+    fn check_files(&self, pkgs: &[&str]) -> Option<Vec<FileProblem>> {
+        pacman::check_files(pkgs)
+    }
+
+    /// Installed packages owning `path`.
+    fn owners(&self, path: &str) -> Vec<String> {
+        pacman::owners(path)
+    }
+}
+
+/// Installs and removes package archives, and knows what is installed
+/// through [`LocalPackages`].
+pub trait PackageInstaller: LocalPackages {
+    /// Install all files in a single transaction.
+    fn install_files(&self, paths: &[PathBuf]) -> Result<()>;
+
+    /// Mark packages as installed as dependencies, so `pacman -Qdt` can find them later.
+    fn mark_as_dependencies(&self, names: &[String]) -> Result<()>;
+
+    /// Remove packages together with their unneeded dependencies.
+    fn remove(&self, names: &[String]) -> Result<()>;
+
+    fn options(&self) -> &PacmanOptions;
+
+    fn set_options(&mut self, options: PacmanOptions);
+
+    /// Report transactions to `hook`.
+    fn set_events(&mut self, _hook: EventHook) {}
+
+    /// How root privileges are obtained, [`Elevate::None`] when they aren't needed.
+    fn elevate(&self) -> Elevate {
+        Elevate::None
+    }
+
+    /// Run `program` with root privileges through the configured tool.
+    fn privileged_command(&self, program: &str) -> Command {
+        Command::new(program)
+    }
+}
+
+/// Runs pacman, through sudo, doas or pkexec unless crafty runs as root.
+pub struct PacmanInstaller {
+    elevate: Elevate,
+    options: PacmanOptions,
+    events: Option<EventHook>,
+}
+
+impl PacmanInstaller {
+    /// Root never needs a helper, so `elevate` is ignored when running as root.
+    pub fn new(elevate: Elevate) -> Self {
+        let elevate = if unsafe { libc::geteuid() } == 0 {
+            Elevate::None
+        } else {
+            elevate
+        };
+        Self {
+            elevate,
+            options: PacmanOptions::default(),
+            events: None,
+        }
+    }
+
+    fn emit(&self, event: Event) {
+        if let Some(hook) = &self.events {
+            hook(&event);
+        }
+    }
+
+    // Privileged pacman in the C locale, since its diagnostics are parsed into
+    // a TransactionError. sudo and pkexec reset the environment, so the
+    // variable is set through env(1) rather than on the Command.
+    fn pacman(&self) -> Command {
+        let mut cmd = self.privileged_command("env");
+        cmd.arg("LC_ALL=C").arg("pacman").args(pacman::root_args());
+        cmd
+    }
+
+    // `pacman <operation>` as root, followed by the pass-through flags
+    fn pacman_command(&self, operation: &str) -> Command {
+        let mut cmd = self.pacman();
+        cmd.arg(operation);
+        if self.options.noconfirm {
+            cmd.arg("--noconfirm");
+        }
+        cmd.args(&self.options.extra_args);
+        cmd
+    }
+
+    // A root crafty set up starts without sync databases, so pacman couldn't
+    // pull dependencies from the sync repositories; fetch them first, like
+    // pacstrap does
+    fn sync_fresh_root(&self) -> Result<()> {
+        let Some(root) = paths::root() else {
+            return Ok(());
+        };
+        let sync = paths::root_dbpath(root).join("sync");
+        let synced = fs::read_dir(&sync)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .any(|entry| entry.path().extension().is_some_and(|ext| ext == "db"));
+        if synced {
+            return Ok(());
+        }
+        let mut cmd = self.pacman();
+        cmd.arg("-Sy");
+        if !Self::run(cmd, "pacman")? {
+            return Err(Error::Pacman(format!(
+                "Failed to sync the package databases in {}",
+                root.display()
+            )));
+        }
+        Ok(())
+    }
+
+    fn run(mut cmd: Command, what: &str) -> Result<bool> {
+        log::debug!("Running {:?}", cmd);
+        cmd.status()
+            .map(|status| status.success())
+            .map_err(|e| Error::Pacman(format!("Failed to run {}: {}", what, e)))
+    }
+
+    // Whether pacman may ask questions the user is there to answer
+    fn interactive(&self) -> bool {
+        !self.options.noconfirm && unsafe { libc::isatty(libc::STDIN_FILENO) } == 1
+    }
+
+    // Run a pacman transaction, keeping a copy of its stderr to explain a
+    // failure. Interactively it is passed through as it comes, since prompts
+    // don't end in a newline; otherwise it is logged line by line.
+    fn run_transaction(&self, mut cmd: Command, operation: &str) -> Result<()> {
+        log::debug!("Running {:?}", cmd);
+        let mut child = cmd
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::Pacman(format!("Failed to run pacman: {}", e)))?;
+        let mut stderr = String::new();
+        if self.interactive()
+            && let Some(mut pipe) = child.stderr.take()
+        {
+            let mut copy = Vec::new();
+            let mut buf = [0; 4096];
+            let mut out = io::stderr();
+            loop {
+                let n = match pipe.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(_) => break,
+                };
+                let _ = out.write_all(&buf[..n]);
+                let _ = out.flush();
+                copy.extend_from_slice(&buf[..n]);
+            }
+            stderr = String::from_utf8_lossy(&copy).into_owned();
+        } else if let Some(pipe) = child.stderr.take() {
+            for line in BufReader::new(pipe).lines().map_while(|l| l.ok()) {
+                if let Some(message) = line.strip_prefix("error: ") {
+                    log::error!("pacman: {}", message);
+                } else if let Some(message) = line.strip_prefix("warning: ") {
+                    log::warn!("pacman: {}", message);
+                } else {
+                    log::info!("{}", line);
+                }
+                stderr.push_str(&line);
+                stderr.push('\n');
+            }
+        }
+        let status = child.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::Transaction(TransactionError::parse(
+                operation, &stderr,
+            )))
+        }
+    }
+}
+
+impl LocalPackages for PacmanInstaller {}
+
+impl PackageInstaller for PacmanInstaller {
+    fn set_events(&mut self, hook: EventHook) {
+        self.events = Some(hook);
+    }
+
+    fn set_options(&mut self, options: PacmanOptions) {
+        self.options = options;
+    }
+
+    fn options(&self) -> &PacmanOptions {
+        &self.options
+    }
+
+    fn elevate(&self) -> Elevate {
+        self.elevate
+    }
+
+    fn privileged_command(&self, program: &str) -> Command {
+        let tool = match self.elevate {
+            Elevate::Sudo => "sudo",
+            Elevate::Doas => "doas",
+            Elevate::Pkexec => "pkexec",
+            Elevate::None => return Command::new(program),
+        };
+        let mut cmd = Command::new(tool);
+        cmd.arg(program);
+        cmd
+    }
+
+    // A single `pacman -U` transaction. pacman older than 5.2 can't read zstd
+    // archives, for it the transaction is retried with those decompressed to
+    // plain tar in-process; xz and gzip archives are passed on as they are.
+    fn install_files(&self, paths: &[PathBuf]) -> Result<()> {
+        self.sync_fresh_root()?;
+        self.emit(Event::Installing { files: paths.len() });
+        let mut cmd = self.pacman_command("-U");
+        cmd.args(paths);
+        let Err(error) = self.run_transaction(cmd, "install") else {
+            return Ok(());
+        };
+        let is_zstd = |path: &PathBuf| {
+            Compression::of(&path.to_string_lossy()) == Some(Compression::Zstd)
+                && Compression::Zstd.matches(path)
+        };
+        // Decompressing can't fix a conflict or a declined prompt
+        let diagnosed = matches!(&error, Error::Transaction(e) if e.is_diagnosed());
+        if diagnosed || pacman::supports_zstd() || !paths.iter().any(is_zstd) {
+            return Err(error);
+        }
+
+        self.emit(Event::RetryingDecompressed);
+        let mut tar_paths = Vec::new();
+        for path in paths {
+            if !is_zstd(path) {
+                tar_paths.push(path.clone());
+                continue;
+            }
+            let tar_path = decompressed_path(path);
+            zstd::decompress_file(path, &tar_path).map_err(|e| {
+                Error::Pacman(format!("Failed to decompress {}: {}", path.display(), e))
+            })?;
+            tar_paths.push(tar_path);
+        }
+
+        let mut retry = self.pacman_command("-U");
+        retry.args(&tar_paths);
+        self.run_transaction(retry, "install")
+    }
+
+    fn mark_as_dependencies(&self, names: &[String]) -> Result<()> {
+        if names.is_empty() {
+            return Ok(());
+        }
+        let mut cmd = self.pacman();
+        cmd.arg("-D").arg("--asdeps").args(names);
+        Self::run(cmd, "pacman")?;
+        Ok(())
+    }
+
+    // `pacman -Rns`
+    fn remove(&self, names: &[String]) -> Result<()> {
+        let mut cmd = self.pacman_command("-Rns");
+        cmd.args(names);
+        self.run_transaction(cmd, "remove")
+    }
+}
+
+/// A transaction [`RecordingInstaller`] was asked to carry out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recorded {
+    Install(Vec<PathBuf>),
+    MarkAsDependencies(Vec<String>),
+    Remove(Vec<String>),
+}
+
+/// Records transactions instead of running pacman, so install and remove
+/// flows can run without root. What it installed is what it reports as
+/// installed, read from the archives' .PKGINFO. Clones share the record, keep
+/// one to inspect it after handing the installer to a [`crate::Manager`].
+#[derive(Clone, Default)]
+pub struct RecordingInstaller {
+    options: PacmanOptions,
+    transactions: Arc<Mutex<Vec<Recorded>>>,
+    installed: Arc<Mutex<BTreeMap<String, Installed>>>,
+}
+
+// A package as the recording installer keeps it
+#[derive(Clone)]
+struct Installed {
+    package: LocalPackage,
+    files: Vec<String>,
+}
+
+impl RecordingInstaller {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start out with `name` installed explicitly at `version`, e.g. a
+    /// package from the sync repositories.
+    pub fn with_installed(self, name: &str, version: &str) -> Self {
+        let package = LocalPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            arch: String::new(),
+            install_date: String::new(),
+            explicit: true,
+            depends: Vec::new(),
+            required_by: Vec::new(),
+            optional_for: Vec::new(),
+        };
+        self.installed.lock().unwrap().insert(
+            name.to_string(),
+            Installed {
+                package,
+                files: Vec::new(),
+            },
+        );
+        self
+    }
+
+    /// Everything asked for so far, oldest first.
+    pub fn transactions(&self) -> Vec<Recorded> {
+        self.transactions.lock().unwrap().clone()
+    }
+
+    fn record(&self, transaction: Recorded) {
+        self.transactions.lock().unwrap().push(transaction);
+    }
+
+    // `name` with the installed packages depending on it filled in
+    fn local(installed: &BTreeMap<String, Installed>, name: &str) -> Option<LocalPackage> {
+        let mut package = installed.get(name)?.package.clone();
+        package.required_by = installed
+            .values()
+            .filter(|other| {
+                other
+                    .package
+                    .depends
+                    .iter()
+                    .any(|dep| strip_version_constraint(dep) == name)
+            })
+            .map(|other| other.package.name.clone())
+            .collect();
+        Some(package)
+    }
+}
+
+impl LocalPackages for RecordingInstaller {
+    fn installed_version(&self, pkg: &str) -> Option<String> {
+        let installed = self.installed.lock().unwrap();
+        installed.get(pkg).map(|p| p.package.version.clone())
+    }
+
+    fn installed_package_names(&self) -> Option<HashSet<String>> {
+        Some(self.installed.lock().unwrap().keys().cloned().collect())
+    }
+
+    // Everything counts as foreign, none of it comes from a sync repository
+    fn installed_package_versions(&self, _foreign_only: bool) -> Option<Vec<(String, String)>> {
+        let installed = self.installed.lock().unwrap();
+        Some(
+            installed
+                .values()
+                .map(|p| (p.package.name.clone(), p.package.version.clone()))
+                .collect(),
+        )
+    }
+
+    fn query_local_packages(&self, names: &[&str]) -> Vec<LocalPackage> {
+        let installed = self.installed.lock().unwrap();
+        names
+            .iter()
+            .filter_map(|name| Self::local(&installed, name))
+            .collect()
+    }
+
+    fn orphaned_packages(&self) -> Vec<String> {
+        let installed = self.installed.lock().unwrap();
+        installed
+            .keys()
+            .filter_map(|name| Self::local(&installed, name))
+            .filter(|p| !p.explicit && p.required_by.is_empty())
+            .map(|p| p.name)
+            .collect()
+    }
+
+    // Version constraints aren't checked, any installed version satisfies them
+    fn unsatisfied_dependencies(&self, depends: &[String]) -> Vec<String> {
+        let installed = self.installed.lock().unwrap();
+        depends
+            .iter()
+            .filter(|dep| !installed.contains_key(strip_version_constraint(dep)))
+            .cloned()
+            .collect()
+    }
+
+    fn package_files(&self, pkg: &str) -> Option<Vec<String>> {
+        let installed = self.installed.lock().unwrap();
+        installed.get(pkg).map(|p| p.files.clone())
+    }
+
+    // Nothing touches the files it pretends to install
+    fn check_files(&self, _pkgs: &[&str]) -> Option<Vec<FileProblem>> {
+        Some(Vec::new())
+    }
+
+    fn owners(&self, path: &str) -> Vec<String> {
+        let installed = self.installed.lock().unwrap();
+        installed
+            .values()
+            .filter(|p| p.files.iter().any(|file| file == path))
+            .map(|p| p.package.name.clone())
+            .collect()
+    }
+}
+
+impl PackageInstaller for RecordingInstaller {
+    fn install_files(&self, paths: &[PathBuf]) -> Result<()> {
+        let mut packages = Vec::new();
+        for path in paths {
+            let info = archive::read_pkginfo(path)
+                .filter(|info| !info.name.is_empty())
+                .ok_or_else(|| Error::InvalidArchive(path.clone()))?;
+            let files = archive::list_archive(path)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|entry| format!("/{}", entry))
+                .collect();
+            packages.push((info, files));
+        }
+        self.record(Recorded::Install(paths.to_vec()));
+
+        let mut installed = self.installed.lock().unwrap();
+        for (info, files) in packages {
+            // Like pacman, an upgrade keeps the install reason
+            let explicit = installed
+                .get(&info.name)
+                .is_none_or(|old| old.package.explicit);
+            let package = LocalPackage {
+                name: info.name.clone(),
+                version: info.version,
+                arch: info.arch,
+                install_date: String::new(),
+                explicit,
+                depends: info.depends,
+                required_by: Vec::new(),
+                optional_for: Vec::new(),
+            };
+            installed.insert(info.name, Installed { package, files });
+        }
+        Ok(())
+    }
+
+    fn mark_as_dependencies(&self, names: &[String]) -> Result<()> {
+        if names.is_empty() {
+            return Ok(());
+        }
+        self.record(Recorded::MarkAsDependencies(names.to_vec()));
+        let mut installed = self.installed.lock().unwrap();
+        for name in names {
+            if let Some(package) = installed.get_mut(name) {
+                package.package.explicit = false;
+            }
+        }
+        Ok(())
+    }
+
+    // Like `pacman -Rns`: dependencies of the removed packages go with them
+    // unless they were installed explicitly or something else needs them
+    fn remove(&self, names: &[String]) -> Result<()> {
+        let mut installed = self.installed.lock().unwrap();
+        if let Some(missing) = names.iter().find(|name| !installed.contains_key(*name)) {
+            return Err(Error::NotInstalled(missing.clone()));
+        }
+        self.record(Recorded::Remove(names.to_vec()));
+        let mut queue: Vec<String> = names.to_vec();
+        while let Some(name) = queue.pop() {
+            let Some(removed) = installed.remove(&name) else {
+                continue;
+            };
+            for dep in &removed.package.depends {
+                let dep = strip_version_constraint(dep);
+                let orphaned = Self::local(&installed, dep)
+                    .is_some_and(|p| !p.explicit && p.required_by.is_empty());
+                if orphaned {
+                    queue.push(dep.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn options(&self) -> &PacmanOptions {
+        &self.options
+    }
+
+    fn set_options(&mut self, options: PacmanOptions) {
+        self.options = options;
+    }
+}
+
+// "foo.pkg.tar.zst" -> "foo.pkg.tar" (likewise .xz and .gz), anything else
+// gets ".tar" appended
+fn decompressed_path(archive: &Path) -> PathBuf {
+    let path = archive.as_os_str().to_string_lossy();
+    let stem = Compression::of(&path).and_then(|c| path.strip_suffix(c.extension()));
+    match stem {
+        Some(stem) => PathBuf::from(stem),
+        None => PathBuf::from(format!("{}.tar", path)),
+    }
+}
@@ -0,0 +1,111 @@
+//! Core of crafty: talks to the ArchCraft package repository on GitHub, resolves
+//! dependencies, drives pacman and keeps track of the packages crafty installed.
+//!
+//! Nothing in here prints; operations return data and report progress through
+//! [`Event`] hooks so front-ends decide how to present it.
+//!
+//! ```no_run
+//! use crafty_core::{Config, Manager};
+//!
+//! let manager = Manager::new(Config::load()?)?;
+//! for update in manager.pending_updates(&manager.load_db())? {
+//!     println!("{} {} -> {}", update.name, update.installed, update.available);
+//! }
+//! # Ok::<(), crafty_core::Error>(())
+//! ```
+
+pub mod archive;
+pub mod aur;
+pub mod auth;
+pub mod backup;
+pub mod changelog;
+pub mod config;
+pub mod conflicts;
+pub mod daemon;
+pub mod db;
+pub mod descriptions;
+pub mod doctor;
+pub mod error;
+pub mod export;
+pub mod files;
+pub mod fuzzy;
+pub mod github;
+pub mod groups;
+pub mod history;
+pub mod hooks;
+pub mod installer;
+pub mod lock;
+pub mod manager;
+pub mod manifest;
+pub mod pacman;
+pub mod pacnew;
+pub mod paths;
+pub mod repo;
+pub mod resolver;
+pub mod search;
+pub mod selfupdate;
+pub mod stats;
+pub mod summary;
+pub mod themes;
+pub mod transaction;
+pub mod updates;
+pub mod util;
+pub mod verify;
+pub mod zstd;
+
+#[cfg(test)]
+mod testutil;
+
+pub use config::{Config, Elevate, RetryConfig};
+pub use db::{DbLock, PackageDb};
+pub use error::{Error, Result};
+pub use github::GitHubClient;
+pub use installer::{
+    LocalPackages, PackageInstaller, PacmanInstaller, PacmanOptions, RecordingInstaller,
+};
+pub use manager::Manager;
+pub use repo::{LocalRepo, RepoClient};
+
+use std::{path::Path, sync::Arc, time::Duration};
+
+/// Progress notifications emitted while talking to the network or pacman.
+#[derive(Debug)]
+pub enum Event<'a> {
+    /// A request failed with a transient error and will be retried after `wait`.
+    Retrying {
+        url: &'a str,
+        error: &'a Error,
+        attempt: u32,
+        attempts: u32,
+        wait: Duration,
+    },
+    /// A package archive is about to be downloaded.
+    Downloading { url: &'a str },
+    /// A verified earlier download at `path` is used instead of downloading again.
+    Cached { path: &'a Path },
+    /// Downloading from `failed` didn't work, trying the `next` endpoint.
+    FallingBack {
+        failed: &'a str,
+        error: &'a Error,
+        next: &'a str,
+    },
+    /// Another crafty process holds the database lock at `path`, waiting for it.
+    WaitingForLock { path: &'a Path },
+    /// A pacman transaction is about to start.
+    Installing { files: usize },
+    /// pacman is too old for zstd archives, retrying with decompressed tarballs.
+    RetryingDecompressed,
+    /// `package` is about to be built from the AUR, `helper` when an AUR helper does it.
+    Building {
+        package: &'a str,
+        helper: Option<&'a str>,
+    },
+    /// User hooks for `stage` are about to run for `package`.
+    RunningHooks {
+        stage: hooks::Stage,
+        package: &'a str,
+    },
+}
+
+/// Callback receiving [`Event`]s, shared between the components of a [`Manager`].
+pub type EventHook = Arc<dyn Fn(&Event) + Send + Sync>;
@@ -0,0 +1,72 @@
+//! Lock files pinning the exact repository artifacts of an installation.
+
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fs, path::Path};
+
+/// Default name of the lock file, looked up in the current directory.
+pub const FILE_NAME: &str = "crafty.lock";
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct LockFile {
+    /// Sorted by name
+    pub packages: Vec<LockedPackage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    /// File name in the repository
+    pub file: String,
+    pub sha256: String,
+    /// Locked ArchCraft packages this one depends on
+    #[serde(default)]
+    pub depends: Vec<String>,
+}
+
+impl LockFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| Error::InvalidFile(format!("Failed to read {}: {}", path.display(), e)))?;
+        serde_json::from_str(&data).map_err(|e| {
+            Error::InvalidFile(format!(
+                "{} is not a crafty lock file: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_string_pretty(self).unwrap();
+        fs::write(path, data + "\n")?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages.iter().find(|p| p.name == name)
+    }
+
+    /// `name` followed by every locked package it depends on, directly or not.
+    pub fn closure(&self, name: &str) -> Result<Vec<&LockedPackage>> {
+        let root = self
+            .get(name)
+            .ok_or_else(|| Error::NotLocked(name.to_string()))?;
+        let mut result = vec![root];
+        let mut seen = HashSet::from([name]);
+        let mut i = 0;
+        while i < result.len() {
+            let package = result[i];
+            for dep in &package.depends {
+                if seen.insert(dep.as_str())
+                    && let Some(locked) = self.get(dep)
+                {
+                    result.push(locked);
+                }
+            }
+            i += 1;
+        }
+        Ok(result)
+    }
+}
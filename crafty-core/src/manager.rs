@@ -0,0 +1,1670 @@
+//! High-level package operations tying the repository, pacman and the database together.
+
+use crate::{
+    Config, DbLock, Error, Event, EventHook, GitHubClient, PackageDb, PackageInstaller,
+    PacmanInstaller, PacmanOptions, RepoClient, Result,
+    archive::{self, parse_package_file, strip_version_constraint},
+    aur::{self, AurPackage},
+    backup::{self, Backup},
+    changelog::{self, Changelog},
+    conflicts::{self, Inspection},
+    db::InstalledPackage,
+    descriptions,
+    doctor::{self, Check},
+    export::{Export, ImportPlan},
+    files::{self, FilesIndex},
+    groups::{self, Group},
+    history::{self, Action},
+    hooks::{self, HookTarget, Stage},
+    lock::{LockFile, LockedPackage},
+    manifest::{ApplyPlan, Manifest},
+    pacman,
+    pacnew::{self, ConfigConflict},
+    paths,
+    resolver::{self, StagedPackage},
+    selfupdate::{self, Release},
+    stats::{self, Stats},
+    themes::{self, Theme},
+    updates::{PendingUpdate, UpdateState},
+    util::{glob_match, now},
+    verify::{self, ArchiveState, PackageCheck, Reinstall},
+};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+    thread,
+};
+
+/// Entry point for embedding crafty: owns the config, repository client and installer.
+pub struct Manager {
+    pub config: Config,
+    pub repo: Box<dyn RepoClient>,
+    pub installer: Box<dyn PackageInstaller>,
+    events: Option<EventHook>,
+    state_dir: PathBuf,
+}
+
+/// A package archive fetched by [`Manager::download`].
+#[derive(Debug, Clone)]
+pub struct Download {
+    pub file: String,
+    pub path: PathBuf,
+    pub sha256: String,
+    pub verified: Verification,
+}
+
+/// What a downloaded archive was checked against.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verification {
+    /// The SHA-256 recorded in the lock file
+    Lock,
+    /// The git blob hash the repository reports for the file
+    Repository,
+    /// Only the archive format could be checked, with the reason
+    Unverified(String),
+}
+
+/// A package touched by a transaction.
+#[derive(Serialize, Debug, Clone)]
+pub struct PackageChange {
+    pub name: String,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    /// Pulled in to satisfy another package rather than requested
+    pub dependency: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct InstallReport {
+    pub packages: Vec<PackageChange>,
+    /// `.pacnew`/`.pacsave` files that appeared during the transaction
+    pub config_conflicts: Vec<ConfigConflict>,
+    /// Snapshots of the configs the packages overwrote, see [`backup`]
+    pub backups: Vec<Backup>,
+    /// Non-fatal problems, e.g. the history couldn't be written
+    pub warnings: Vec<String>,
+    /// Packages pacman didn't register at the staged version even though the
+    /// transaction succeeded; they are left out of `packages` and the database
+    pub unregistered: Vec<Unregistered>,
+}
+
+/// A staged package missing from pacman's database after a successful
+/// transaction, see [`InstallReport::unregistered`].
+#[derive(Debug, Clone)]
+pub struct Unregistered {
+    pub name: String,
+    pub expected: String,
+    /// The version pacman has installed instead, if any
+    pub found: Option<String>,
+}
+
+/// A requested package before anything is downloaded, see [`Manager::preview`].
+#[derive(Debug, Clone)]
+pub struct Preview {
+    pub name: String,
+    pub file: String,
+    /// Installed version, if any
+    pub installed: Option<String>,
+    pub version: String,
+    /// Download size from the repository metadata, when known
+    pub size: Option<u64>,
+    /// A verified copy is already in the download directory
+    pub cached: bool,
+}
+
+/// Why a package is installed, see [`Manager::why`].
+#[derive(Debug, Clone)]
+pub struct Reason {
+    pub name: String,
+    pub version: String,
+    /// Tracked in crafty's database
+    pub managed: bool,
+    /// Installed explicitly rather than as a dependency, according to pacman
+    pub explicit: bool,
+    /// Installed packages depending on it directly
+    pub required_by: Vec<Dependent>,
+    /// Installed packages that can use it but don't need it
+    pub optional_for: Vec<String>,
+    /// Explicitly installed packages that need it through a chain of dependencies
+    pub needed_by: Vec<String>,
+}
+
+impl Reason {
+    /// Removing the package on its own would leave dependencies unsatisfied.
+    pub fn removal_breaks(&self) -> bool {
+        !self.required_by.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Dependent {
+    pub name: String,
+    /// An ArchCraft package tracked by crafty
+    pub managed: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct RemoveReport {
+    pub removed: Vec<PackageChange>,
+    /// Database entries dropped because pacman removed them along the way
+    pub pruned: Vec<String>,
+    /// Crafty-managed packages no longer required by anything
+    pub orphans: Vec<String>,
+    /// `.pacsave` files left behind by the removed packages
+    pub config_conflicts: Vec<ConfigConflict>,
+    pub warnings: Vec<String>,
+}
+
+impl Manager {
+    /// The ArchCraft repositories on GitHub and pacman.
+    pub fn new(config: Config) -> Result<Self> {
+        let repo = GitHubClient::new(&config)?;
+        let installer = PacmanInstaller::new(config.elevate);
+        Ok(Self::with_backends(config, repo, installer))
+    }
+
+    /// Packages from `repo`, installed by `installer`, e.g. a [`crate::LocalRepo`]
+    /// and a [`crate::RecordingInstaller`] to run without network or root.
+    pub fn with_backends(
+        config: Config,
+        repo: impl RepoClient + 'static,
+        installer: impl PackageInstaller + 'static,
+    ) -> Self {
+        Self {
+            config,
+            repo: Box::new(repo),
+            installer: Box::new(installer),
+            events: None,
+            state_dir: paths::state_dir(),
+        }
+    }
+
+    /// Keep the database, history and config backups in `dir` instead of the
+    /// state directory, see [`paths::state_dir`].
+    pub fn with_state_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.state_dir = dir.into();
+        self
+    }
+
+    /// Where the database, history and config backups are kept.
+    pub fn state_dir(&self) -> &Path {
+        &self.state_dir
+    }
+
+    /// Report progress of network and pacman operations to `hook`.
+    pub fn with_events(mut self, hook: EventHook) -> Self {
+        self.repo.set_events(hook.clone());
+        self.installer.set_events(hook.clone());
+        self.events = Some(hook);
+        self
+    }
+
+    pub fn with_pacman_options(mut self, options: PacmanOptions) -> Self {
+        self.installer.set_options(options);
+        self
+    }
+
+    fn emit(&self, event: Event) {
+        if let Some(hook) = &self.events {
+            hook(&event);
+        }
+    }
+
+    // Run the user hooks of `stage`, see [`hooks`]
+    fn run_hooks(&self, stage: Stage, target: HookTarget) -> Result<()> {
+        if hooks::scripts(stage).is_empty() {
+            return Ok(());
+        }
+        self.emit(Event::RunningHooks {
+            stage,
+            package: target.package,
+        });
+        hooks::run(stage, target)
+    }
+
+    pub fn load_db(&self) -> PackageDb {
+        PackageDb::load(&self.state_dir, self.installer.as_ref())
+    }
+
+    /// The latest crafty release for this architecture.
+    pub fn latest_release(&self) -> Result<Release> {
+        selfupdate::latest_release(self.repo.as_ref())
+    }
+
+    /// Replace the running executable with the binary of `release`.
+    pub fn self_update(&self, release: &Release) -> Result<()> {
+        let exe = std::env::current_exe()?;
+        selfupdate::install(self.repo.as_ref(), release, &exe)
+    }
+
+    /// Check the environment crafty depends on, see [`doctor`].
+    pub fn diagnose(&self) -> Vec<Check> {
+        doctor::diagnose(self)
+    }
+
+    /// Counts, sizes and timestamps of the managed packages, see [`stats`].
+    pub fn stats(&self) -> Stats {
+        stats::collect(self)
+    }
+
+    /// Check `pkg`, or every managed package, against pacman and the recorded
+    /// checksums, see [`verify`].
+    pub fn verify(&self, pkg: Option<&str>) -> Result<Vec<PackageCheck>> {
+        let db = self.load_db();
+        let names = match pkg {
+            Some(pkg) => vec![
+                db.packages
+                    .get_key_value(pkg)
+                    .ok_or_else(|| Error::NotInstalled(pkg.to_string()))?
+                    .0,
+            ],
+            None => db.sorted(),
+        };
+        verify::check(
+            self.installer.as_ref(),
+            &db,
+            &names,
+            &self.config.staging_dir(),
+        )
+    }
+
+    /// Stage the broken packages among `checks` for reinstalling, from the
+    /// archives they were installed from where possible.
+    pub fn stage_reinstall(&self, checks: &[PackageCheck]) -> Result<Reinstall> {
+        let db = self.load_db();
+        let staging = self.config.staging_dir();
+        std::fs::create_dir_all(&staging)?;
+        let mut reinstall = Reinstall::default();
+        for check in checks.iter().filter(|c| c.is_broken()) {
+            match self.stage_original(&db, check, &staging) {
+                Ok(package) => reinstall.staged.push(package),
+                Err(e) => reinstall.failed.push((check.name.clone(), e)),
+            }
+        }
+        Ok(reinstall)
+    }
+
+    // The archive the package was installed from: the cached copy if it still
+    // matches the recorded checksum, else the same file from the repository,
+    // else the latest version when the repository moved on
+    fn stage_original(
+        &self,
+        db: &PackageDb,
+        check: &PackageCheck,
+        staging: &Path,
+    ) -> Result<StagedPackage> {
+        let record = db
+            .get(&check.name)
+            .ok_or_else(|| Error::NotInstalled(check.name.clone()))?;
+        let (file, path) = match (&check.archive, &record.file, &record.sha256) {
+            (ArchiveState::Intact(path), Some(file), _) => (file.clone(), path.clone()),
+            _ if record.origin.as_deref() == Some(aur::ORIGIN) => {
+                return Err(Error::Aur(format!(
+                    "{} was built from the AUR and its archive is gone, reinstall it with `crafty install --aur`",
+                    check.name
+                )));
+            }
+            (state, Some(file), Some(sha256)) if self.repo.packages()?.contains(file) => {
+                // A download would reuse the damaged copy
+                if let ArchiveState::Corrupt(path) = state {
+                    std::fs::remove_file(path)?;
+                }
+                let path = self.repo.download_package(file, staging)?;
+                let actual = archive::sha256(&path).unwrap_or_default();
+                if actual != *sha256 {
+                    return Err(Error::ChecksumMismatch {
+                        file: file.clone(),
+                        expected: sha256.clone(),
+                        actual,
+                    });
+                }
+                (file.clone(), path)
+            }
+            _ => {
+                let download = self.download(&check.name, staging, None)?;
+                (download.file, download.path)
+            }
+        };
+        let (name, version) =
+            parse_package_file(&file).ok_or_else(|| Error::InvalidArchive(path.clone()))?;
+        files::record(&file, &path);
+        let info = archive::read_pkginfo(&path).unwrap_or_default();
+        descriptions::record(&file, &info.desc);
+        Ok(StagedPackage {
+            name,
+            version,
+            file,
+            path,
+            info,
+            dependency: false,
+        })
+    }
+
+    /// Keep other crafty processes from changing the database until the
+    /// returned guard is dropped; hold it across load, modify and save.
+    pub fn lock_db(&self) -> Result<DbLock> {
+        DbLock::acquire(&self.state_dir, |path| {
+            self.emit(Event::WaitingForLock { path })
+        })
+        .map_err(Error::Io)
+    }
+
+    /// Held in the database or ignored through the config.
+    pub fn is_held(&self, db: &PackageDb, pkg: &str) -> bool {
+        db.is_held(pkg, &self.config.ignore)
+    }
+
+    /// Download `pkg` and the ArchCraft dependencies it needs, see [`resolver::resolve`].
+    pub fn stage(&self, pkg: &str) -> Result<Vec<StagedPackage>> {
+        resolver::resolve(
+            self.repo.as_ref(),
+            self.installer.as_ref(),
+            pkg,
+            &self.config.staging_dir(),
+        )
+    }
+
+    /// What installing `pkgs` would fetch, from the repository index and
+    /// `lock` when given. ArchCraft dependencies are only known once the
+    /// archives are read and aren't part of it.
+    pub fn preview(&self, pkgs: &[String], lock: Option<&LockFile>) -> Result<Vec<Preview>> {
+        let staging = self.config.staging_dir();
+        let remote = self.repo.remote_files().ok();
+        let mut previews = Vec::new();
+        for pkg in pkgs {
+            let (file, version, sha256) = match lock {
+                Some(lock) => {
+                    let locked = lock
+                        .get(pkg)
+                        .ok_or_else(|| Error::NotLocked(pkg.to_string()))?;
+                    (
+                        locked.file.clone(),
+                        locked.version.clone(),
+                        Some(locked.sha256.as_str()),
+                    )
+                }
+                None => {
+                    let file = self
+                        .repo
+                        .find_package_file(pkg)?
+                        .ok_or_else(|| Error::NotFound(pkg.to_string()))?;
+                    let version = parse_package_file(&file)
+                        .map(|(_, version)| version)
+                        .unwrap_or_default();
+                    (file, version, None)
+                }
+            };
+            let cached = self.repo.is_cached(&file, &staging, sha256);
+            previews.push(Preview {
+                name: pkg.clone(),
+                installed: self.installer.installed_version(pkg),
+                size: remote.and_then(|files| files.get(&file)).map(|f| f.size),
+                cached,
+                version,
+                file,
+            });
+        }
+        Ok(previews)
+    }
+
+    /// What installing the package file at `path` would do; nothing needs
+    /// downloading, so it counts as cached.
+    pub fn preview_file(&self, path: &Path) -> Result<Preview> {
+        let info = archive::read_pkginfo(path).ok_or_else(|| Error::InvalidArchive(path.into()))?;
+        let file = path
+            .file_name()
+            .map(|f| f.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (name, version) = if info.name.is_empty() {
+            parse_package_file(&file).ok_or_else(|| Error::InvalidArchive(path.into()))?
+        } else {
+            (info.name, info.version)
+        };
+        Ok(Preview {
+            installed: self.installer.installed_version(&name),
+            size: std::fs::metadata(path).ok().map(|m| m.len()),
+            cached: true,
+            name,
+            version,
+            file,
+        })
+    }
+
+    /// Names of the repository packages matching a shell-style `pattern`
+    /// (`*` and `?`), sorted.
+    pub fn expand_glob(&self, pattern: &str) -> Result<Vec<String>> {
+        let mut names: Vec<String> = self
+            .repo
+            .remote_versions()?
+            .into_keys()
+            .filter(|name| glob_match(pattern, name))
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Stage a local package file and its ArchCraft dependencies, see [`resolver::resolve_file`].
+    pub fn stage_file(&self, path: &Path) -> Result<Vec<StagedPackage>> {
+        resolver::resolve_file(
+            self.repo.as_ref(),
+            self.installer.as_ref(),
+            path,
+            &self.config.staging_dir(),
+        )
+    }
+
+    /// Files the staged packages would write over, checked before pacman runs,
+    /// see [`conflicts`].
+    pub fn inspect(&self, staged: &[StagedPackage]) -> Vec<Inspection> {
+        let listings = listings(staged);
+        let packages: Vec<(&str, &[String])> = staged
+            .iter()
+            .zip(&listings)
+            .map(|(p, files)| (p.name.as_str(), files.as_slice()))
+            .collect();
+        conflicts::inspect(paths::install_root(), &packages)
+    }
+
+    /// Install staged packages in one pacman transaction and record them.
+    /// Staged dependencies are marked as such in pacman's database.
+    pub fn commit(&self, staged: &[StagedPackage]) -> Result<InstallReport> {
+        let _lock = self.lock_db()?;
+        let mut report = InstallReport::default();
+        // Remember what was there before so the history can show old -> new
+        let previous: Vec<Option<String>> = staged
+            .iter()
+            .map(|p| self.installer.installed_version(&p.name))
+            .collect();
+        let targets: Vec<(Stage, HookTarget)> = staged
+            .iter()
+            .zip(&previous)
+            .map(|(package, old)| {
+                let stage = if old.is_some() {
+                    Stage::PreUpgrade
+                } else {
+                    Stage::PreInstall
+                };
+                let target = HookTarget {
+                    package: &package.name,
+                    version: Some(&package.version),
+                    old_version: old.as_deref(),
+                };
+                (stage, target)
+            })
+            .collect();
+        for (stage, target) in &targets {
+            self.run_hooks(*stage, *target)?;
+        }
+
+        // Remember existing conflicts so only the ones from this transaction are reported
+        let listings = listings(staged);
+        let conflicts_before: Vec<ConfigConflict> = staged
+            .iter()
+            .zip(&listings)
+            .flat_map(|(p, files)| pacnew::scan(paths::install_root(), &p.name, files))
+            .collect();
+        if self.config.backup {
+            let timestamp = now();
+            for (package, files) in staged.iter().zip(&listings) {
+                match backup::create(
+                    &self.state_dir,
+                    paths::install_root(),
+                    &package.name,
+                    files,
+                    timestamp,
+                ) {
+                    Ok(Some(backup)) => report.backups.push(backup),
+                    Ok(None) => {}
+                    Err(e) => report.warnings.push(format!(
+                        "Failed to back up the configs of {}: {}",
+                        package.name, e
+                    )),
+                }
+            }
+        }
+
+        let paths: Vec<_> = staged.iter().map(|p| p.path.clone()).collect();
+        let result = self.installer.install_files(&paths);
+        for (package, old) in staged.iter().zip(&previous) {
+            let action = if old.is_some() {
+                Action::Upgrade
+            } else {
+                Action::Install
+            };
+            if let Err(e) = history::record(
+                &self.state_dir,
+                action,
+                &package.name,
+                old.as_deref(),
+                Some(&package.version),
+                result.is_ok(),
+            ) {
+                report
+                    .warnings
+                    .push(format!("Failed to write history: {}", e));
+            }
+        }
+        result?;
+
+        // Only the requested packages count as explicitly installed
+        let dependencies: Vec<String> = staged
+            .iter()
+            .filter(|p| p.dependency)
+            .map(|p| p.name.clone())
+            .collect();
+        if let Err(e) = self.installer.mark_as_dependencies(&dependencies) {
+            report.warnings.push(e.to_string());
+        }
+
+        // pacman can succeed without installing a package, e.g. when it skips
+        // one or keeps a newer version, so only what it registered is recorded
+        let names: Vec<&str> = staged.iter().map(|p| p.name.as_str()).collect();
+        let registered = self.installer.query_local_packages(&names);
+        let verified: Vec<bool> = staged
+            .iter()
+            .map(|package| {
+                let found = registered
+                    .iter()
+                    .find(|local| local.name == package.name)
+                    .map(|local| local.version.clone());
+                if found.as_deref() == Some(package.version.as_str()) {
+                    return true;
+                }
+                report.unregistered.push(Unregistered {
+                    name: package.name.clone(),
+                    expected: package.version.clone(),
+                    found,
+                });
+                false
+            })
+            .collect();
+
+        let mut db = self.load_db();
+        let installed_at = now();
+        for package in staged
+            .iter()
+            .zip(&verified)
+            .filter_map(|(p, ok)| ok.then_some(p))
+        {
+            let record = InstalledPackage {
+                arch: Some(package.info.arch.clone()).filter(|a| !a.is_empty()),
+                installed_at: Some(installed_at),
+                file: Some(package.file.clone()),
+                sha256: archive::sha256(&package.path),
+                installed_size: package.info.size,
+                ..InstalledPackage::default()
+            }
+            .with_version(&package.version);
+            db.record(&package.name, record)?;
+        }
+        report.config_conflicts = staged
+            .iter()
+            .zip(&listings)
+            .flat_map(|(p, files)| pacnew::scan(paths::install_root(), &p.name, files))
+            .filter(|c| !conflicts_before.contains(c))
+            .collect();
+
+        // The packages are in place, a failing post hook is only worth a warning
+        for (stage, target) in &targets {
+            let stage = if *stage == Stage::PreUpgrade {
+                Stage::PostUpgrade
+            } else {
+                Stage::PostInstall
+            };
+            if let Err(e) = self.run_hooks(stage, *target) {
+                report.warnings.push(e.to_string());
+            }
+        }
+
+        for ((package, old), _) in staged
+            .iter()
+            .zip(previous)
+            .zip(&verified)
+            .filter(|(_, ok)| **ok)
+        {
+            report.packages.push(PackageChange {
+                name: package.name.clone(),
+                old_version: old,
+                new_version: Some(package.version.clone()),
+                dependency: package.dependency,
+            });
+        }
+        Ok(report)
+    }
+
+    /// Stage several packages for a single transaction; shared dependencies
+    /// are downloaded once.
+    pub fn stage_many(&self, pkgs: &[String]) -> Result<Vec<StagedPackage>> {
+        // Fetched once up front rather than by every worker at the same time
+        self.repo.index()?;
+        let workers = self.config.parallel_downloads.clamp(1, pkgs.len().max(1));
+        let next = AtomicUsize::new(0);
+        let mut results: Vec<(usize, Result<Vec<StagedPackage>>)> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let i = next.fetch_add(1, AtomicOrdering::Relaxed);
+                            let Some(pkg) = pkgs.get(i) else {
+                                return done;
+                            };
+                            done.push((i, self.stage(pkg)));
+                        }
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        results.sort_by_key(|(i, _)| *i);
+
+        let mut staged: Vec<StagedPackage> = Vec::new();
+        for (_, result) in results {
+            for package in result? {
+                match staged.iter_mut().find(|p| p.name == package.name) {
+                    // Requested explicitly after being pulled in by an earlier package
+                    Some(existing) => existing.dependency &= package.dependency,
+                    None => staged.push(package),
+                }
+            }
+        }
+        Ok(resolver::dependency_order(staged))
+    }
+
+    /// Download the latest archive of `pkg` into the download directory
+    /// without installing it, so a later install uses the cached copy.
+    pub fn prefetch(&self, pkg: &str) -> Result<PathBuf> {
+        let file = self
+            .repo
+            .find_package_file(pkg)?
+            .ok_or_else(|| Error::NotFound(pkg.to_string()))?;
+        self.repo
+            .download_package(&file, &self.config.staging_dir())
+    }
+
+    /// Look up `pkgs` in the AUR, see [`aur::info`].
+    pub fn aur_info(&self, pkgs: &[String]) -> Result<Vec<AurPackage>> {
+        aur::info(self.repo.as_ref(), pkgs)
+    }
+
+    /// Build `package` from the AUR, with the `aur_helper` from the config
+    /// or makepkg, install it and record it with the [`aur::ORIGIN`].
+    pub fn install_aur(&self, package: &AurPackage) -> Result<InstallReport> {
+        // makepkg installs missing dependencies into the live system
+        if let Some(root) = paths::root() {
+            return Err(Error::Aur(format!(
+                "AUR packages can't be installed into {}",
+                root.display()
+            )));
+        }
+        let _lock = self.lock_db()?;
+        let mut report = InstallReport::default();
+        let old = self.installer.installed_version(&package.name);
+        let (stage, action) = match old {
+            Some(_) => (Stage::PreUpgrade, Action::Upgrade),
+            None => (Stage::PreInstall, Action::Install),
+        };
+        let target = HookTarget {
+            package: &package.name,
+            version: Some(&package.version),
+            old_version: old.as_deref(),
+        };
+        self.run_hooks(stage, target)?;
+
+        let helper = self.config.aur_helper.as_deref();
+        self.emit(Event::Building {
+            package: &package.name,
+            helper,
+        });
+        let mut built = None;
+        let result = match helper {
+            Some(helper) => {
+                aur::install_with_helper(helper, package, self.installer.options().noconfirm)
+            }
+            None => aur::build(package, &aur::build_dir()).and_then(|path| {
+                let installed = self.installer.install_files(std::slice::from_ref(&path));
+                built = Some(path);
+                installed
+            }),
+        };
+        let version = self.installer.installed_version(&package.name);
+        if let Err(e) = history::record(
+            &self.state_dir,
+            action,
+            &package.name,
+            old.as_deref(),
+            version.as_deref(),
+            result.is_ok(),
+        ) {
+            report
+                .warnings
+                .push(format!("Failed to write history: {}", e));
+        }
+        result?;
+        let Some(version) = version else {
+            return Err(Error::NotRegistered(Unregistered {
+                name: package.name.clone(),
+                expected: package.version.clone(),
+                found: None,
+            }));
+        };
+
+        let local = self.installer.query_local_packages(&[&package.name]);
+        let record = InstalledPackage {
+            installed_at: Some(now()),
+            file: built
+                .as_ref()
+                .and_then(|p| p.file_name())
+                .map(|f| f.to_string_lossy().into_owned()),
+            sha256: built.as_deref().and_then(archive::sha256),
+            installed_size: built
+                .as_deref()
+                .and_then(archive::read_pkginfo)
+                .and_then(|info| info.size),
+            origin: Some(aur::ORIGIN.to_string()),
+            ..local
+                .first()
+                .map(InstalledPackage::from_local)
+                .unwrap_or_default()
+        };
+        self.load_db().record(&package.name, record)?;
+
+        let post = match stage {
+            Stage::PreUpgrade => Stage::PostUpgrade,
+            _ => Stage::PostInstall,
+        };
+        if let Err(e) = self.run_hooks(post, target) {
+            report.warnings.push(e.to_string());
+        }
+        report.packages.push(PackageChange {
+            name: package.name.clone(),
+            old_version: old,
+            new_version: Some(version),
+            dependency: false,
+        });
+        Ok(report)
+    }
+
+    /// Resolve, download and install `pkg` with its ArchCraft dependencies.
+    pub fn install(&self, pkg: &str) -> Result<InstallReport> {
+        let staged = self.stage(pkg)?;
+        self.commit(&staged)
+    }
+
+    /// Remove `pkg` with `pacman -Rns` and report crafty-managed packages left orphaned.
+    pub fn remove(&self, pkg: &str) -> Result<RemoveReport> {
+        self.remove_many(&[pkg.to_string()])
+    }
+
+    /// Remove several packages in one `pacman -Rns` transaction, see [`Manager::remove`].
+    pub fn remove_many(&self, pkgs: &[String]) -> Result<RemoveReport> {
+        let mut report = self.remove_packages(pkgs, false)?;
+        let db = self.load_db();
+        report.orphans = self
+            .installer
+            .orphaned_packages()
+            .into_iter()
+            .filter(|p| db.contains(p))
+            .collect();
+        Ok(report)
+    }
+
+    /// Remove orphaned packages found by [`Manager::remove`] in one transaction.
+    pub fn remove_orphans(&self, orphans: &[String]) -> Result<RemoveReport> {
+        self.remove_packages(orphans, true)
+    }
+
+    fn remove_packages(&self, pkgs: &[String], dependency: bool) -> Result<RemoveReport> {
+        let _lock = self.lock_db()?;
+        let mut report = RemoveReport::default();
+        let old_versions: Vec<Option<String>> = pkgs
+            .iter()
+            .map(|p| self.installer.installed_version(p))
+            .collect();
+        for (pkg, old) in pkgs.iter().zip(&old_versions) {
+            self.run_hooks(Stage::PreRemove, removal_target(pkg, old.as_deref()))?;
+        }
+        // pacman forgets the file lists with the packages, collect them first
+        let listings: Vec<Vec<String>> = pkgs
+            .iter()
+            .map(|p| self.installer.package_files(p).unwrap_or_default())
+            .collect();
+        let conflicts_before: Vec<ConfigConflict> = pkgs
+            .iter()
+            .zip(&listings)
+            .flat_map(|(p, files)| pacnew::scan(paths::install_root(), p, files))
+            .collect();
+        let result = self.installer.remove(pkgs);
+
+        for (pkg, old) in pkgs.iter().zip(old_versions) {
+            if let Err(e) = history::record(
+                &self.state_dir,
+                Action::Remove,
+                pkg,
+                old.as_deref(),
+                None,
+                result.is_ok(),
+            ) {
+                report
+                    .warnings
+                    .push(format!("Failed to write history: {}", e));
+            }
+            if result.is_ok()
+                && let Err(e) =
+                    self.run_hooks(Stage::PostRemove, removal_target(pkg, old.as_deref()))
+            {
+                report.warnings.push(e.to_string());
+            }
+            report.removed.push(PackageChange {
+                name: pkg.clone(),
+                old_version: old,
+                new_version: None,
+                dependency,
+            });
+        }
+        result?;
+        report.config_conflicts = pkgs
+            .iter()
+            .zip(&listings)
+            .flat_map(|(p, files)| pacnew::scan(paths::install_root(), p, files))
+            .filter(|c| !conflicts_before.contains(c))
+            .collect();
+
+        let mut db = self.load_db();
+        for pkg in pkgs {
+            db.remove(pkg)?;
+        }
+        // -Rns may have taken crafty-installed dependencies along with it
+        report.pruned = self.prune_uninstalled(&mut db)?;
+        Ok(report)
+    }
+
+    /// Explain why `pkg` is installed from pacman's dependency data and
+    /// crafty's database.
+    pub fn why(&self, pkg: &str) -> Result<Reason> {
+        let local = self
+            .installer
+            .query_local_packages(&[pkg])
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::NotInstalled(pkg.to_string()))?;
+        let db = self.load_db();
+
+        // Walk up the reverse dependencies one level per pacman call
+        let mut needed_by = Vec::new();
+        let mut seen = HashSet::from([local.name.clone()]);
+        let mut level: Vec<String> = local.required_by.clone();
+        while !level.is_empty() {
+            seen.extend(level.iter().cloned());
+            let mut next = Vec::new();
+            let names: Vec<&str> = level.iter().map(String::as_str).collect();
+            for dependent in self.installer.query_local_packages(&names) {
+                if dependent.explicit {
+                    needed_by.push(dependent.name);
+                }
+                next.extend(
+                    dependent
+                        .required_by
+                        .into_iter()
+                        .filter(|p| !seen.contains(p)),
+                );
+            }
+            next.sort();
+            next.dedup();
+            level = next;
+        }
+        needed_by.sort();
+
+        Ok(Reason {
+            managed: db.contains(&local.name),
+            explicit: local.explicit,
+            required_by: local
+                .required_by
+                .iter()
+                .map(|name| Dependent {
+                    managed: db.contains(name),
+                    name: name.clone(),
+                })
+                .collect(),
+            optional_for: local.optional_for,
+            needed_by,
+            version: local.version,
+            name: local.name,
+        })
+    }
+
+    /// Database entries pacman no longer knows about, in name order.
+    pub fn stale_packages(&self, db: &PackageDb) -> Vec<String> {
+        // If pacman can't be queried nothing is considered stale
+        let Some(installed) = self.installer.installed_package_names() else {
+            return Vec::new();
+        };
+        db.packages
+            .keys()
+            .filter(|p| !installed.contains(*p))
+            .cloned()
+            .collect()
+    }
+
+    /// Drop stale entries from the database, returning their names.
+    pub fn prune_uninstalled(&self, db: &mut PackageDb) -> Result<Vec<String>> {
+        let gone = self.stale_packages(db);
+        for pkg in &gone {
+            db.remove(pkg)?;
+        }
+        Ok(gone)
+    }
+
+    /// Managed, non-held packages with a newer version in the repository.
+    pub fn pending_updates(&self, db: &PackageDb) -> Result<Vec<PendingUpdate>> {
+        let remote = self.repo.remote_versions()?;
+        Ok(pending_updates(self, db, &remote))
+    }
+
+    /// Compute pending updates and store them in the [`UpdateState`] file.
+    pub fn check_updates(&self) -> Result<UpdateState> {
+        let state = UpdateState {
+            checked_at: now(),
+            updates: self.pending_updates(&self.load_db())?,
+        };
+        state.save();
+        Ok(state)
+    }
+
+    /// Snapshot of the managed packages, see [`Export`].
+    pub fn export(&self) -> Export {
+        Export::from_db(&self.load_db(), self.installer.as_ref())
+    }
+
+    /// Sort the packages of `export` by what importing them here takes.
+    pub fn import_plan(&self, export: &Export) -> Result<ImportPlan> {
+        let installed = self.installer.installed_package_names().unwrap_or_default();
+        let remote = self.repo.remote_versions()?;
+        let db = self.load_db();
+        let mut plan = ImportPlan::default();
+        for package in &export.packages {
+            let name = package.name.clone();
+            if installed.contains(&name) {
+                if db.contains(&name) {
+                    plan.present.push(name);
+                } else {
+                    plan.adopt.push(name);
+                }
+            } else if remote.contains_key(&name) {
+                plan.install.push(name);
+            } else {
+                plan.unavailable.push(name);
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Diff `manifest` against the installed packages. With `prune`, tracked
+    /// packages the manifest doesn't list are scheduled for removal. With a
+    /// `lock`, packages are brought to exactly their locked versions instead of
+    /// the latest ones in the repository.
+    pub fn plan_manifest(
+        &self,
+        manifest: &Manifest,
+        prune: bool,
+        lock: Option<&LockFile>,
+    ) -> Result<ApplyPlan> {
+        let installed: HashMap<String, String> = self
+            .installer
+            .installed_package_versions(false)
+            .ok_or_else(|| {
+                Error::Pacman(String::from(
+                    "Failed to query installed packages from pacman",
+                ))
+            })?
+            .into_iter()
+            .collect();
+        let remote = match lock {
+            Some(_) => HashMap::new(),
+            None => self.repo.remote_versions()?,
+        };
+        let source = if lock.is_some() {
+            "lock file"
+        } else {
+            "repository"
+        };
+        let db = self.load_db();
+        let mut plan = ApplyPlan::default();
+
+        for package in &manifest.packages {
+            let name = &package.name;
+            let constraint = &package.constraint;
+            let ignored = self.config.ignore.iter().any(|p| glob_match(p, name));
+
+            if package.hold && !db.held.contains(name) {
+                plan.hold.push(name.clone());
+            } else if !package.hold && db.held.contains(name) {
+                plan.unhold.push(name.clone());
+            }
+
+            let target = match lock {
+                Some(lock) => lock.get(name).map(|p| &p.version),
+                None => remote.get(name),
+            };
+            match (installed.get(name), target) {
+                (None, Some(target)) if constraint.matches(target) => {
+                    plan.install.push(name.clone())
+                }
+                (None, Some(target)) => plan.unsatisfiable.push((
+                    name.clone(),
+                    format!("{} has {}, manifest wants {}", source, target, constraint),
+                )),
+                (None, None) => plan
+                    .unsatisfiable
+                    .push((name.clone(), format!("not in the {}", source))),
+                (Some(current), target) => {
+                    if !db.contains(name) {
+                        plan.adopt.push(name.clone());
+                    }
+                    // A lock pins the version, which may mean going back
+                    let outdated = |target: &str| match lock {
+                        Some(_) => target != current,
+                        None => pacman::is_newer(target, current),
+                    };
+                    match target {
+                        Some(target)
+                            if !package.hold
+                                && !ignored
+                                && outdated(target)
+                                && constraint.matches(target) =>
+                        {
+                            plan.upgrade.push(PendingUpdate {
+                                name: name.clone(),
+                                installed: current.clone(),
+                                available: target.clone(),
+                            })
+                        }
+                        _ if !constraint.matches(current) => plan.unsatisfiable.push((
+                            name.clone(),
+                            format!("installed {} doesn't satisfy {}", current, constraint),
+                        )),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        if prune {
+            let wanted: HashSet<&str> = manifest.packages.iter().map(|p| p.name.as_str()).collect();
+            plan.remove = db
+                .sorted()
+                .into_iter()
+                .filter(|name| !wanted.contains(name.as_str()) && installed.contains_key(*name))
+                .cloned()
+                .collect();
+        }
+        Ok(plan)
+    }
+
+    /// Resolve `roots` together with every ArchCraft package they depend on and
+    /// record the exact files and their checksums.
+    pub fn lock(&self, roots: &[String]) -> Result<LockFile> {
+        let staging = self.config.staging_dir();
+        let mut packages: Vec<LockedPackage> = Vec::new();
+        for root in roots {
+            if packages.iter().any(|p| &p.name == root) {
+                continue;
+            }
+            for staged in resolver::resolve_closure(self.repo.as_ref(), root, &staging)? {
+                if packages.iter().any(|p| p.name == staged.name) {
+                    continue;
+                }
+                let sha256 = archive::sha256(&staged.path)
+                    .ok_or_else(|| Error::InvalidArchive(staged.path.clone()))?;
+                packages.push(LockedPackage {
+                    depends: staged
+                        .info
+                        .depends
+                        .iter()
+                        .map(|d| strip_version_constraint(d).to_string())
+                        .collect(),
+                    name: staged.name,
+                    version: staged.version,
+                    file: staged.file,
+                    sha256,
+                });
+            }
+        }
+
+        // Dependencies from the sync repositories are pacman's business
+        let locked: HashSet<String> = packages.iter().map(|p| p.name.clone()).collect();
+        for package in &mut packages {
+            package.depends.retain(|d| locked.contains(d));
+        }
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(LockFile { packages })
+    }
+
+    /// Download the archive of `pkg` into `dir` without installing it, checked
+    /// against `lock` when given, otherwise against the repository's hash.
+    pub fn download(&self, pkg: &str, dir: &Path, lock: Option<&LockFile>) -> Result<Download> {
+        let file = match lock {
+            Some(lock) => {
+                let locked = lock
+                    .get(pkg)
+                    .ok_or_else(|| Error::NotLocked(pkg.to_string()))?;
+                locked.file.clone()
+            }
+            None => self
+                .repo
+                .find_package_file(pkg)?
+                .ok_or_else(|| Error::NotFound(pkg.to_string()))?,
+        };
+        std::fs::create_dir_all(dir)?;
+        let cached = lock
+            .and_then(|lock| lock.get(pkg))
+            .and_then(|locked| self.repo.cached_package(&file, dir, Some(&locked.sha256)));
+        let path = match cached {
+            Some(path) => path,
+            None => self.repo.download_package(&file, dir)?,
+        };
+        let sha256 = archive::sha256(&path).ok_or_else(|| Error::InvalidArchive(path.clone()))?;
+
+        let verified = match lock.and_then(|lock| lock.get(pkg)) {
+            Some(locked) if locked.sha256 != sha256 => {
+                return Err(Error::ChecksumMismatch {
+                    file,
+                    expected: locked.sha256.clone(),
+                    actual: sha256,
+                });
+            }
+            Some(_) => Verification::Lock,
+            None => match (self.repo.remote_files(), archive::git_blob_sha(&path)) {
+                (Err(e), _) => Verification::Unverified(e.to_string()),
+                (Ok(_), None) => Verification::Unverified(String::from("git is not available")),
+                (Ok(files), Some(actual)) => {
+                    let expected = files
+                        .get(&file)
+                        .map(|f| f.sha.clone())
+                        .ok_or_else(|| Error::NotFound(pkg.to_string()))?;
+                    if actual != expected {
+                        return Err(Error::ChecksumMismatch {
+                            file,
+                            expected,
+                            actual,
+                        });
+                    }
+                    Verification::Repository
+                }
+            },
+        };
+        Ok(Download {
+            file,
+            path,
+            sha256,
+            verified,
+        })
+    }
+
+    /// Download exactly the files `lock` records for `pkg` and its dependencies
+    /// and verify their checksums. Dependencies already installed at their
+    /// locked version are left out.
+    pub fn stage_locked(&self, lock: &LockFile, pkg: &str) -> Result<Vec<StagedPackage>> {
+        let staging = self.config.staging_dir();
+        let mut staged = Vec::new();
+        for (i, locked) in lock.closure(pkg)?.into_iter().enumerate() {
+            if i > 0
+                && self.installer.installed_version(&locked.name).as_deref()
+                    == Some(locked.version.as_str())
+            {
+                continue;
+            }
+            let path = match self
+                .repo
+                .cached_package(&locked.file, &staging, Some(&locked.sha256))
+            {
+                Some(path) => path,
+                None => self.repo.download_package(&locked.file, &staging)?,
+            };
+            let actual = archive::sha256(&path).unwrap_or_default();
+            if actual != locked.sha256 {
+                return Err(Error::ChecksumMismatch {
+                    file: locked.file.clone(),
+                    expected: locked.sha256.clone(),
+                    actual,
+                });
+            }
+            files::record(&locked.file, &path);
+            let info = archive::read_pkginfo(&path).unwrap_or_default();
+            descriptions::record(&locked.file, &info.desc);
+            staged.push(StagedPackage {
+                dependency: i > 0,
+                name: locked.name.clone(),
+                version: locked.version.clone(),
+                file: locked.file.clone(),
+                info,
+                path,
+            });
+        }
+        Ok(staged)
+    }
+
+    /// Repository commits between the installed version of `pkg` and the
+    /// latest one: commits naming the package since the installed file was
+    /// added, plus the commit that added the latest file.
+    pub fn changelog(&self, pkg: &str) -> Result<Changelog> {
+        let latest_file = self
+            .repo
+            .find_package_file(pkg)?
+            .ok_or_else(|| Error::NotFound(pkg.to_string()))?;
+        let (name, latest) =
+            parse_package_file(&latest_file).ok_or_else(|| Error::NotFound(pkg.to_string()))?;
+        let installed = self.installer.installed_version(&name);
+
+        let mut commits = Vec::new();
+        if let Some(version) = installed.as_deref().filter(|v| *v != latest) {
+            // e.g. "any.pkg.tar.zst" or "x86_64.pkg.tar.xz"
+            let suffix = latest_file.rsplit('-').next().unwrap_or_default();
+            let installed_file = format!("{}-{}-{}", name, version, suffix);
+            // The oldest commit touching the file is the one that added it
+            let added = self
+                .repo
+                .commits(&latest_file, Some(&installed_file), None)?
+                .pop();
+            if let Some(added) = added {
+                commits = self
+                    .repo
+                    .commits(&latest_file, None, Some(&added.date))?
+                    .into_iter()
+                    .filter(|c| c.sha != added.sha && changelog::mentions(c, &name))
+                    .collect();
+            }
+        }
+        for commit in self.repo.commits(&latest_file, Some(&latest_file), None)? {
+            if !commits.iter().any(|c| c.sha == commit.sha) {
+                commits.push(commit);
+            }
+        }
+        commits.sort_by(|a, b| b.date.cmp(&a.date));
+
+        Ok(Changelog {
+            name,
+            installed,
+            latest,
+            commits,
+        })
+    }
+
+    /// `.pacnew`/`.pacsave` files next to the files of crafty-managed packages.
+    pub fn config_conflicts(&self) -> Vec<ConfigConflict> {
+        self.load_db()
+            .sorted()
+            .into_iter()
+            .flat_map(|pkg| {
+                pacnew::scan(
+                    paths::install_root(),
+                    pkg,
+                    &self.installer.package_files(pkg).unwrap_or_default(),
+                )
+            })
+            .collect()
+    }
+
+    /// Config backups of `pkg`, oldest first.
+    pub fn backups(&self, pkg: &str) -> Vec<Backup> {
+        backup::list(&self.state_dir, paths::install_root(), pkg)
+    }
+
+    /// Copy the files of a backup back in place, the latest one unless
+    /// `timestamp` picks another. Files outside the home directory are
+    /// copied as root when the user can't write them.
+    pub fn restore(&self, pkg: &str, timestamp: Option<u64>) -> Result<Backup> {
+        let backup = backup::list(&self.state_dir, paths::install_root(), pkg)
+            .into_iter()
+            .rfind(|b| timestamp.is_none_or(|t| b.timestamp == t))
+            .ok_or_else(|| match timestamp {
+                Some(t) => Error::NoBackup(format!("{} at {}", pkg, t)),
+                None => Error::NoBackup(pkg.to_string()),
+            })?;
+        for path in backup.files() {
+            let saved = backup.saved_path(&path);
+            let copied = path
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::copy(&saved, &path));
+            match copied {
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                    let mode = std::fs::metadata(&saved)?.permissions().mode() & 0o7777;
+                    let status = self
+                        .installer
+                        .privileged_command("install")
+                        .arg("-D")
+                        .arg(format!("--mode={:o}", mode))
+                        .arg("--")
+                        .arg(&saved)
+                        .arg(&path)
+                        .status()?;
+                    if !status.success() {
+                        return Err(Error::Io(std::io::Error::other(format!(
+                            "Failed to restore {}",
+                            path.display()
+                        ))));
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(backup)
+    }
+
+    /// Package groups, see [`groups`].
+    pub fn groups(&self) -> Result<Vec<Group>> {
+        let remote = self.repo.remote_versions()?;
+        Ok(groups::build(
+            remote.keys().map(String::as_str),
+            &self.config.groups,
+        ))
+    }
+
+    pub fn group(&self, name: &str) -> Result<Group> {
+        self.groups()?
+            .into_iter()
+            .find(|g| g.name == name)
+            .ok_or_else(|| Error::UnknownGroup(name.to_string()))
+    }
+
+    /// Theme bundles available in the repository, see [`themes`].
+    pub fn themes(&self) -> Result<Vec<Theme>> {
+        let remote = self.repo.remote_versions()?;
+        Ok(themes::group(remote.keys().map(String::as_str)))
+    }
+
+    pub fn theme(&self, name: &str) -> Result<Theme> {
+        self.themes()?
+            .into_iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| Error::UnknownTheme(name.to_string()))
+    }
+
+    /// Installed ArchCraft packages crafty doesn't track yet, as (name, version).
+    /// Only foreign packages (`pacman -Qm`) are considered unless `all` is set.
+    pub fn adopt_candidates(&self, all: bool) -> Result<Vec<(String, String)>> {
+        let installed = self
+            .installer
+            .installed_package_versions(!all)
+            .ok_or_else(|| {
+                Error::Pacman(String::from(
+                    "Failed to query installed packages from pacman",
+                ))
+            })?;
+        let remote = self.repo.remote_versions()?;
+        let db = self.load_db();
+        Ok(installed
+            .into_iter()
+            .filter(|(name, _)| remote.contains_key(name) && !db.contains(name))
+            .collect())
+    }
+}
+
+// Removal hooks see the version being removed in both variables
+fn removal_target<'a>(package: &'a str, version: Option<&'a str>) -> HookTarget<'a> {
+    HookTarget {
+        package,
+        version,
+        old_version: version,
+    }
+}
+
+fn pending_updates(
+    manager: &Manager,
+    db: &PackageDb,
+    remote: &HashMap<String, String>,
+) -> Vec<PendingUpdate> {
+    let names: Vec<&str> = db
+        .packages
+        .keys()
+        .filter(|p| !manager.is_held(db, p))
+        .map(String::as_str)
+        .collect();
+    manager
+        .installer
+        .query_local_packages(&names)
+        .into_iter()
+        .filter_map(|local| {
+            let latest = remote.get(&local.name)?;
+            pacman::is_newer(latest, &local.version).then(|| PendingUpdate {
+                available: latest.clone(),
+                name: local.name,
+                installed: local.version,
+            })
+        })
+        .collect()
+}
+
+// Archive listing of every staged package, from the files index when possible
+fn listings(staged: &[StagedPackage]) -> Vec<Vec<String>> {
+    let index = FilesIndex::load();
+    staged
+        .iter()
+        .map(|p| match index.packages.get(&p.file) {
+            Some(entries) => entries.clone(),
+            None => archive::list_archive(&p.path).unwrap_or_default(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        LocalRepo, RecordingInstaller,
+        installer::{LocalPackages, Recorded},
+        testutil::{TempDir, package},
+    };
+    use std::fs;
+
+    const APP: &str = "crafty-fixture-app";
+    const LIB: &str = "crafty-fixture-lib";
+
+    struct Fixture {
+        repo: TempDir,
+        staging: TempDir,
+        state: TempDir,
+        installer: RecordingInstaller,
+    }
+
+    impl Fixture {
+        // The app depends on a library only the repository has and on a
+        // package from the sync repositories that is already installed
+        fn new() -> Self {
+            let repo = TempDir::new("repo");
+            package(
+                repo.path(),
+                APP,
+                "1.0-1",
+                &[LIB, "glibc>=2.38"],
+                &[("usr/bin/crafty-fixture-app", "#!/bin/sh\n")],
+            );
+            package(
+                repo.path(),
+                LIB,
+                "1.0-1",
+                &[],
+                &[("usr/lib/crafty-fixture-lib.so", "lib")],
+            );
+            Self {
+                repo,
+                staging: TempDir::new("staging"),
+                state: TempDir::new("state"),
+                installer: RecordingInstaller::new().with_installed("glibc", "2.40-1"),
+            }
+        }
+
+        // A fresh manager each time, so it sees the repository as it is now
+        fn manager(&self) -> Manager {
+            let config = Config {
+                download_dir: Some(self.staging.path().to_path_buf()),
+                backup: false,
+                parallel_downloads: 1,
+                ..Config::default()
+            };
+            Manager::with_backends(
+                config,
+                LocalRepo::new(self.repo.path()),
+                self.installer.clone(),
+            )
+            .with_state_dir(self.state.path())
+        }
+    }
+
+    #[test]
+    fn install_records_the_package_and_marks_dependencies() {
+        let fixture = Fixture::new();
+        let manager = fixture.manager();
+
+        let report = manager.install(APP).unwrap();
+
+        let changes: Vec<(&str, bool)> = report
+            .packages
+            .iter()
+            .map(|p| (p.name.as_str(), p.dependency))
+            .collect();
+        assert_eq!(changes, [(APP, false), (LIB, true)]);
+        assert!(report.unregistered.is_empty());
+        let transactions = fixture.installer.transactions();
+        assert_eq!(transactions.len(), 2);
+        assert!(matches!(&transactions[0], Recorded::Install(paths) if paths.len() == 2));
+        assert!(matches!(&transactions[1], Recorded::MarkAsDependencies(names) if names == &[LIB]));
+
+        let local = fixture.installer.query_local_packages(&[APP, LIB]);
+        assert!(local[0].explicit);
+        assert!(!local[1].explicit);
+        assert_eq!(
+            fixture.installer.package_files(APP).unwrap(),
+            ["/usr/bin/crafty-fixture-app"]
+        );
+        assert_eq!(
+            fixture.installer.owners("/usr/bin/crafty-fixture-app"),
+            [APP]
+        );
+
+        let db = manager.load_db();
+        let record = db.get(APP).unwrap();
+        assert_eq!(record.full_version().as_deref(), Some("1.0-1"));
+        assert_eq!(
+            record.file.as_deref(),
+            Some("crafty-fixture-app-1.0-1-any.pkg.tar.gz")
+        );
+        assert!(record.sha256.is_some());
+        assert!(db.contains(LIB));
+        assert_eq!(history::load(manager.state_dir()).len(), 2);
+    }
+
+    #[test]
+    fn upgrade_installs_the_newer_version() {
+        let fixture = Fixture::new();
+        fixture.manager().install(APP).unwrap();
+        fs::remove_file(fixture.repo.join("crafty-fixture-app-1.0-1-any.pkg.tar.gz")).unwrap();
+        package(
+            fixture.repo.path(),
+            APP,
+            "1.1-1",
+            &[LIB],
+            &[("usr/bin/crafty-fixture-app", "#!/bin/sh\nexit 0\n")],
+        );
+        let manager = fixture.manager();
+
+        let updates = manager.pending_updates(&manager.load_db()).unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].name, APP);
+        assert_eq!(updates[0].installed, "1.0-1");
+        assert_eq!(updates[0].available, "1.1-1");
+
+        // The library is installed by now, only the app is staged
+        let staged = manager.stage_many(&[APP.to_string()]).unwrap();
+        assert_eq!(staged.len(), 1);
+        let report = manager.commit(&staged).unwrap();
+        assert_eq!(report.packages.len(), 1);
+        assert_eq!(report.packages[0].old_version.as_deref(), Some("1.0-1"));
+        assert_eq!(report.packages[0].new_version.as_deref(), Some("1.1-1"));
+
+        assert_eq!(
+            fixture.installer.installed_version(APP).as_deref(),
+            Some("1.1-1")
+        );
+        let db = manager.load_db();
+        assert_eq!(
+            db.get(APP).unwrap().full_version().as_deref(),
+            Some("1.1-1")
+        );
+        assert!(manager.pending_updates(&db).unwrap().is_empty());
+        let history = history::load(manager.state_dir());
+        let last = history.last().unwrap();
+        assert_eq!(last.action, Action::Upgrade);
+        assert_eq!(last.old_version.as_deref(), Some("1.0-1"));
+    }
+
+    #[test]
+    fn remove_takes_unneeded_dependencies_along() {
+        let fixture = Fixture::new();
+        let manager = fixture.manager();
+        manager.install(APP).unwrap();
+
+        let report = manager.remove(APP).unwrap();
+
+        assert_eq!(report.removed.len(), 1);
+        assert_eq!(report.removed[0].name, APP);
+        assert_eq!(report.removed[0].old_version.as_deref(), Some("1.0-1"));
+        assert_eq!(report.pruned, [LIB]);
+        assert!(report.orphans.is_empty());
+        assert!(matches!(
+            fixture.installer.transactions().last(),
+            Some(Recorded::Remove(names)) if names == &[APP]
+        ));
+        assert_eq!(fixture.installer.installed_version(APP), None);
+        assert_eq!(fixture.installer.installed_version(LIB), None);
+        assert!(fixture.installer.installed_version("glibc").is_some());
+        let db = manager.load_db();
+        assert!(!db.contains(APP));
+        assert!(!db.contains(LIB));
+    }
+
+    #[test]
+    fn previews_repository_packages_and_files() {
+        let fixture = Fixture::new();
+        let manager = fixture.manager();
+
+        let previews = manager.preview(&[APP.to_string()], None).unwrap();
+        assert_eq!(previews.len(), 1);
+        assert_eq!(previews[0].name, APP);
+        assert_eq!(previews[0].version, "1.0-1");
+        assert_eq!(previews[0].installed, None);
+        assert!(!previews[0].cached);
+
+        manager.install(APP).unwrap();
+        let file = fixture.repo.join("crafty-fixture-app-1.0-1-any.pkg.tar.gz");
+        let preview = manager.preview_file(&file).unwrap();
+        assert_eq!(preview.name, APP);
+        assert_eq!(preview.version, "1.0-1");
+        assert_eq!(preview.installed.as_deref(), Some("1.0-1"));
+        assert_eq!(preview.size, Some(fs::metadata(&file).unwrap().len()));
+        assert!(preview.cached);
+        assert!(matches!(
+            manager.preview_file(&fixture.repo.join("missing.pkg.tar.gz")),
+            Err(Error::InvalidArchive(_))
+        ));
+    }
+
+    #[test]
+    fn removing_an_unknown_package_fails() {
+        let fixture = Fixture::new();
+        let manager = fixture.manager();
+
+        let err = manager.remove(APP).unwrap_err();
+
+        assert!(matches!(err, Error::NotInstalled(name) if name == APP));
+        assert!(fixture.installer.transactions().is_empty());
+    }
+}
@@ -0,0 +1,395 @@
+//! Declarative description of the packages a machine should have.
+//!
+//! Manifests use a small subset of TOML:
+//!
+//! ```toml
+//! [packages]
+//! archcraft-openbox = "*"
+//! archcraft-polybar = ">=3.6"
+//! archcraft-gtk-theme-arc = { version = "<2", hold = true }
+//! ```
+//!
+//! Values are a version constraint (`*` or empty for any version, otherwise one
+//! of `=`, `<`, `<=`, `>`, `>=` followed by a version, a bare version meaning
+//! `=`) or an inline table with `version` and `hold` keys. Since the repository
+//! only carries the latest build of a package, a constraint can hold back an
+//! upgrade but never install an older version.
+
+use crate::{Error, Result, pacman, updates::PendingUpdate};
+use std::{cmp::Ordering, fs, path::Path};
+
+#[derive(Debug, Clone, Default)]
+pub struct Manifest {
+    /// Packages in the order they appear in the file
+    pub packages: Vec<ManifestPackage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ManifestPackage {
+    pub name: String,
+    pub constraint: Constraint,
+    /// Keep the package at its installed version, like `crafty hold`
+    pub hold: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Constraint {
+    #[default]
+    Any,
+    Eq(String),
+    Lt(String),
+    Le(String),
+    Gt(String),
+    Ge(String),
+}
+
+impl Constraint {
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        if text.is_empty() || text == "*" {
+            return Ok(Constraint::Any);
+        }
+        let (op, version) = match text.find(|c: char| !matches!(c, '<' | '>' | '=')) {
+            Some(i) => text.split_at(i),
+            None => (text, ""),
+        };
+        let version = version.trim().to_string();
+        if version.is_empty() {
+            return Err(Error::InvalidFile(format!(
+                "Missing version in constraint '{}'",
+                text
+            )));
+        }
+        Ok(match op {
+            "" | "=" | "==" => Constraint::Eq(version),
+            "<" => Constraint::Lt(version),
+            "<=" => Constraint::Le(version),
+            ">" => Constraint::Gt(version),
+            ">=" => Constraint::Ge(version),
+            _ => {
+                return Err(Error::InvalidFile(format!(
+                    "Unknown operator '{}' in constraint '{}'",
+                    op, text
+                )));
+            }
+        })
+    }
+
+    /// Whether `version` satisfies the constraint, compared with pacman's rules.
+    pub fn matches(&self, version: &str) -> bool {
+        let compare = |wanted: &str| pacman::vercmp(version, wanted);
+        match self {
+            Constraint::Any => true,
+            Constraint::Eq(v) => compare(v) == Ordering::Equal,
+            Constraint::Lt(v) => compare(v) == Ordering::Less,
+            Constraint::Le(v) => compare(v) != Ordering::Greater,
+            Constraint::Gt(v) => compare(v) == Ordering::Greater,
+            Constraint::Ge(v) => compare(v) != Ordering::Less,
+        }
+    }
+}
+
+impl std::fmt::Display for Constraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constraint::Any => write!(f, "*"),
+            Constraint::Eq(v) => write!(f, "={}", v),
+            Constraint::Lt(v) => write!(f, "<{}", v),
+            Constraint::Le(v) => write!(f, "<={}", v),
+            Constraint::Gt(v) => write!(f, ">{}", v),
+            Constraint::Ge(v) => write!(f, ">={}", v),
+        }
+    }
+}
+
+// A parsed right-hand side of `key = value`
+enum Value {
+    String(String),
+    Bool(bool),
+    Table(Vec<(String, Value)>),
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| Error::InvalidFile(format!("Failed to read {}: {}", path.display(), e)))?;
+        Self::parse(&data).map_err(|e| Error::InvalidFile(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut manifest = Manifest::default();
+        let mut section = String::new();
+        for (n, raw) in text.lines().enumerate() {
+            let at = |msg: String| Error::InvalidFile(format!("line {}: {}", n + 1, msg));
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let header = header
+                    .strip_suffix(']')
+                    .ok_or_else(|| at(String::from("unterminated table header")))?;
+                section = header.trim().to_string();
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| at(format!("expected `key = value`, found '{}'", line)))?;
+            let key = unquote_key(key.trim());
+            let (value, rest) = parse_value(value.trim()).map_err(at)?;
+            if !rest.trim().is_empty() {
+                return Err(at(format!("unexpected '{}' after value", rest.trim())));
+            }
+            if section != "packages" {
+                return Err(at(format!("unknown key '{}' outside of [packages]", key)));
+            }
+            let package = package_from_value(key, value).map_err(|e| at(e.to_string()))?;
+            manifest.packages.push(package);
+        }
+        Ok(manifest)
+    }
+}
+
+fn package_from_value(name: String, value: Value) -> Result<ManifestPackage> {
+    let mut package = ManifestPackage {
+        name,
+        constraint: Constraint::Any,
+        hold: false,
+    };
+    match value {
+        Value::String(constraint) => package.constraint = Constraint::parse(&constraint)?,
+        Value::Table(fields) => {
+            for (key, value) in fields {
+                match (key.as_str(), value) {
+                    ("version", Value::String(v)) => package.constraint = Constraint::parse(&v)?,
+                    ("hold", Value::Bool(b)) => package.hold = b,
+                    (key, _) => {
+                        return Err(Error::InvalidFile(format!(
+                            "unexpected '{}' for package {}",
+                            key, package.name
+                        )));
+                    }
+                }
+            }
+        }
+        Value::Bool(_) => {
+            return Err(Error::InvalidFile(format!(
+                "expected a version or a table for package {}",
+                package.name
+            )));
+        }
+    }
+    Ok(package)
+}
+
+// `#` starts a comment unless it is inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' if in_string => escaped = !escaped,
+            '"' if !escaped => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => escaped = false,
+        }
+    }
+    line
+}
+
+fn unquote_key(key: &str) -> String {
+    key.strip_prefix('"')
+        .and_then(|k| k.strip_suffix('"'))
+        .unwrap_or(key)
+        .to_string()
+}
+
+// Parse one value from the start of `text`, returning it with the unparsed rest
+fn parse_value(text: &str) -> std::result::Result<(Value, &str), String> {
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((Value::String(value), &rest[i + 1..])),
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, c)) => value.push(c),
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+        return Err(String::from("unterminated string"));
+    }
+    if let Some(rest) = text.strip_prefix("true") {
+        return Ok((Value::Bool(true), rest));
+    }
+    if let Some(rest) = text.strip_prefix("false") {
+        return Ok((Value::Bool(false), rest));
+    }
+    if let Some(mut rest) = text.strip_prefix('{') {
+        let mut fields = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix('}') {
+                return Ok((Value::Table(fields), after));
+            }
+            let (key, after) = rest
+                .split_once('=')
+                .ok_or_else(|| String::from("expected `key = value` in inline table"))?;
+            let (value, after) = parse_value(after.trim_start())?;
+            fields.push((unquote_key(key.trim()), value));
+            rest = after.trim_start();
+            rest = rest.strip_prefix(',').unwrap_or(rest);
+        }
+    }
+    Err(format!("unsupported value '{}'", text))
+}
+
+/// Changes needed to bring this machine in line with a [`Manifest`].
+#[derive(Debug, Default)]
+pub struct ApplyPlan {
+    pub install: Vec<String>,
+    pub upgrade: Vec<PendingUpdate>,
+    /// Installed already, only needs to be tracked by crafty
+    pub adopt: Vec<String>,
+    pub hold: Vec<String>,
+    pub unhold: Vec<String>,
+    /// Tracked packages missing from the manifest, only filled when pruning
+    pub remove: Vec<String>,
+    /// (package, reason) for packages the repository can't satisfy
+    pub unsatisfiable: Vec<(String, String)>,
+}
+
+impl ApplyPlan {
+    pub fn is_empty(&self) -> bool {
+        self.install.is_empty()
+            && self.upgrade.is_empty()
+            && self.adopt.is_empty()
+            && self.hold.is_empty()
+            && self.unhold.is_empty()
+            && self.remove.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_constraints() {
+        let parse = |text| Constraint::parse(text).unwrap();
+        assert_eq!(parse(""), Constraint::Any);
+        assert_eq!(parse(" * "), Constraint::Any);
+        assert_eq!(parse("1.2-3"), Constraint::Eq(String::from("1.2-3")));
+        assert_eq!(parse("=1.2"), Constraint::Eq(String::from("1.2")));
+        assert_eq!(parse("==1.2"), Constraint::Eq(String::from("1.2")));
+        assert_eq!(parse("<2"), Constraint::Lt(String::from("2")));
+        assert_eq!(parse("<= 2"), Constraint::Le(String::from("2")));
+        assert_eq!(parse(">1:3.6"), Constraint::Gt(String::from("1:3.6")));
+        assert_eq!(parse(">=3.6"), Constraint::Ge(String::from("3.6")));
+        assert_eq!(parse(">=3.6").to_string(), ">=3.6");
+        assert_eq!(parse("3.6").to_string(), "=3.6");
+
+        assert!(matches!(
+            Constraint::parse(">="),
+            Err(Error::InvalidFile(_))
+        ));
+        assert!(matches!(
+            Constraint::parse("=>1"),
+            Err(Error::InvalidFile(_))
+        ));
+        assert!(matches!(
+            Constraint::parse("<>1"),
+            Err(Error::InvalidFile(_))
+        ));
+    }
+
+    #[test]
+    fn matches_versions() {
+        assert!(Constraint::Any.matches("0.1-1"));
+        assert!(Constraint::Eq(String::from("3.6-1")).matches("3.6-1"));
+        assert!(!Constraint::Eq(String::from("3.6-1")).matches("3.7-1"));
+        assert!(Constraint::Ge(String::from("3.6")).matches("3.6"));
+        assert!(Constraint::Ge(String::from("3.6")).matches("4.0"));
+        assert!(!Constraint::Lt(String::from("2")).matches("2"));
+        assert!(Constraint::Le(String::from("2")).matches("2"));
+        assert!(!Constraint::Gt(String::from("2")).matches("1"));
+    }
+
+    #[test]
+    fn parses_manifests() {
+        let manifest = Manifest::parse(
+            r#"
+# Machines in the lab
+[packages]
+archcraft-openbox = "*"
+archcraft-polybar = ">=3.6"   # needs the new modules
+"archcraft-gtk-theme-arc" = { version = "<2", hold = true }
+archcraft-wallpapers = {hold=false,version="1.0-2"}
+archcraft-hash = "=1#2"
+"#,
+        )
+        .unwrap();
+        let packages: Vec<(&str, String, bool)> = manifest
+            .packages
+            .iter()
+            .map(|p| (p.name.as_str(), p.constraint.to_string(), p.hold))
+            .collect();
+        assert_eq!(
+            packages,
+            [
+                ("archcraft-openbox", String::from("*"), false),
+                ("archcraft-polybar", String::from(">=3.6"), false),
+                ("archcraft-gtk-theme-arc", String::from("<2"), true),
+                ("archcraft-wallpapers", String::from("=1.0-2"), false),
+                ("archcraft-hash", String::from("=1#2"), false),
+            ]
+        );
+        assert!(Manifest::parse("").unwrap().packages.is_empty());
+    }
+
+    #[test]
+    fn reports_the_line_of_an_error() {
+        let error = |text: &str| match Manifest::parse(text) {
+            Err(Error::InvalidFile(message)) => message,
+            other => panic!("expected an error, got {:?}", other),
+        };
+        assert_eq!(
+            error("[packages]\nfoo = \"1\"\nbar"),
+            "line 3: expected `key = value`, found 'bar'"
+        );
+        assert_eq!(
+            error("foo = \"1\""),
+            "line 1: unknown key 'foo' outside of [packages]"
+        );
+        assert_eq!(error("[packages"), "line 1: unterminated table header");
+        assert_eq!(
+            error("[packages]\nfoo = \"1"),
+            "line 2: unterminated string"
+        );
+        assert_eq!(
+            error("[packages]\nfoo = \"1\" \"2\""),
+            "line 2: unexpected '\"2\"' after value"
+        );
+        assert_eq!(
+            error("[packages]\nfoo = 1"),
+            "line 2: unsupported value '1'"
+        );
+        assert_eq!(
+            error("[packages]\nfoo = true"),
+            "line 2: expected a version or a table for package foo"
+        );
+        assert_eq!(
+            error("[packages]\nfoo = { pin = true }"),
+            "line 2: unexpected 'pin' for package foo"
+        );
+        assert_eq!(
+            error("[packages]\nfoo = \"=<1\""),
+            "line 2: Unknown operator '=<' in constraint '=<1'"
+        );
+    }
+}
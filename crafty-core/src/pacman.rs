@@ -0,0 +1,464 @@
+//! Read-only queries against the local pacman database.
+
+use crate::paths;
+use regex::Regex;
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    path::PathBuf,
+    process::{Command, Output},
+    sync::OnceLock,
+};
+
+/// Package details as reported by `pacman -Qi`.
+#[derive(Debug, Clone)]
+pub struct LocalPackage {
+    pub name: String,
+    pub version: String,
+    pub arch: String,
+    pub install_date: String,
+    /// Install reason, as opposed to installed as a dependency
+    pub explicit: bool,
+    pub depends: Vec<String>,
+    pub required_by: Vec<String>,
+    pub optional_for: Vec<String>,
+}
+
+fn lines(output: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(output)
+        .lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+/// `--root` and `--dbpath` for the root set with [`paths::set_root`], empty
+/// for the live system.
+pub fn root_args() -> Vec<OsString> {
+    match paths::root() {
+        Some(root) => vec![
+            OsString::from("--root"),
+            root.as_os_str().to_owned(),
+            OsString::from("--dbpath"),
+            paths::root_dbpath(root).into_os_string(),
+        ],
+        None => Vec::new(),
+    }
+}
+
+// `pacman` querying the database of the current root. Its output is parsed,
+// so it must not be translated.
+fn pacman() -> Command {
+    let mut cmd = Command::new("pacman");
+    cmd.env("LC_ALL", "C").args(root_args());
+    cmd
+}
+
+// Run a query, logged at trace level since these happen all the time
+fn query(cmd: &mut Command) -> std::io::Result<Output> {
+    log::trace!("Running {:?}", cmd);
+    cmd.output()
+}
+
+/// Installed version of `pkg`, `None` when it isn't installed.
+pub fn installed_version(pkg: &str) -> Option<String> {
+    let output = query(pacman().arg("-Q").arg(pkg)).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (_, version) = stdout.trim().split_once(' ')?;
+    Some(version.to_string())
+}
+
+/// Names of all installed packages, `None` if pacman can't be queried.
+pub fn installed_package_names() -> Option<HashSet<String>> {
+    let output = query(pacman().arg("-Qq")).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(lines(&output.stdout).into_iter().collect())
+}
+
+/// Installed (name, version) pairs, optionally limited to foreign packages.
+pub fn installed_package_versions(foreign_only: bool) -> Option<Vec<(String, String)>> {
+    let flag = if foreign_only { "-Qm" } else { "-Q" };
+    let output = query(pacman().arg(flag)).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let (name, version) = line.split_once(' ')?;
+                Some((name.to_string(), version.trim().to_string()))
+            })
+            .collect(),
+    )
+}
+
+/// `pacman -Qi` details for the given packages; unknown names are skipped.
+pub fn query_local_packages<S: AsRef<str>>(names: &[S]) -> Vec<LocalPackage> {
+    if names.is_empty() {
+        return Vec::new();
+    }
+    let Ok(output) = query(pacman().arg("-Qi").args(names.iter().map(|n| n.as_ref()))) else {
+        return Vec::new();
+    };
+    parse_local_packages(&String::from_utf8_lossy(&output.stdout))
+}
+
+// Each package is a block of "Key : Value" lines separated by a blank line
+fn parse_local_packages(output: &str) -> Vec<LocalPackage> {
+    let mut packages = Vec::new();
+    for block in output.split("\n\n") {
+        let field = |key: &str| {
+            block.lines().find_map(|line| {
+                let (k, v) = line.split_once(':')?;
+                (k.trim() == key).then(|| v.trim().to_string())
+            })
+        };
+        // Space separated lists, "None" when empty
+        let list = |key: &str| -> Vec<String> {
+            field(key)
+                .unwrap_or_default()
+                .split_whitespace()
+                .filter(|v| *v != "None")
+                .map(str::to_string)
+                .collect()
+        };
+        if let Some(name) = field("Name") {
+            packages.push(LocalPackage {
+                name,
+                version: field("Version").unwrap_or_default(),
+                arch: field("Architecture").unwrap_or_default(),
+                install_date: field("Install Date").unwrap_or_default(),
+                explicit: field("Install Reason").is_some_and(|r| r.starts_with("Explicitly")),
+                depends: list("Depends On"),
+                required_by: list("Required By"),
+                optional_for: list("Optional For"),
+            });
+        }
+    }
+    packages
+}
+
+/// Packages installed as dependencies that nothing requires anymore.
+pub fn orphaned_packages() -> Vec<String> {
+    match query(pacman().arg("-Qdtq")) {
+        Ok(output) => lines(&output.stdout),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// The dependencies not satisfied by the local database, as printed by `pacman -T`.
+pub fn unsatisfied_dependencies(depends: &[String]) -> Vec<String> {
+    if depends.is_empty() {
+        return Vec::new();
+    }
+    match query(pacman().arg("-T").args(depends)) {
+        Ok(output) => lines(&output.stdout),
+        // Without pacman there is nothing to check against, assume everything is missing
+        Err(_) => depends.to_vec(),
+    }
+}
+
+/// Installed packages owning `path`.
+pub fn owners(path: &str) -> Vec<String> {
+    match query(pacman().arg("-Qqo").arg(path)) {
+        Ok(output) if output.status.success() => lines(&output.stdout),
+        _ => Vec::new(),
+    }
+}
+
+/// Owning package of each of `paths`, queried in batches; unowned paths are left out.
+pub fn owners_of<P: AsRef<std::path::Path>>(paths: &[P]) -> HashMap<PathBuf, String> {
+    let mut owners = HashMap::new();
+    for chunk in paths.chunks(200) {
+        let args = chunk.iter().map(|p| p.as_ref());
+        // Exits non-zero as soon as one path is unowned, the rest is still
+        // printed, in English like every query
+        if let Ok(output) = query(pacman().arg("-Qo").args(args)) {
+            owners.extend(parse_owners(&String::from_utf8_lossy(&output.stdout)));
+        }
+    }
+    owners
+}
+
+// "/usr/bin/foo is owned by foo 1.0-1"
+fn parse_owners(output: &str) -> impl Iterator<Item = (PathBuf, String)> + '_ {
+    output.lines().filter_map(|line| {
+        let (path, owner) = line.split_once(" is owned by ")?;
+        let name = owner.split_whitespace().next()?;
+        Some((PathBuf::from(path), name.to_string()))
+    })
+}
+
+/// Whether pacman reads zstd packages itself, which it does since 5.2.
+pub fn supports_zstd() -> bool {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"Pacman v(\d+)\.(\d+)").unwrap());
+    let Ok(output) = query(pacman().arg("--version")) else {
+        return false;
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    re.captures(&stdout).is_some_and(|caps| {
+        let major: u32 = caps[1].parse().unwrap_or(0);
+        let minor: u32 = caps[2].parse().unwrap_or(0);
+        (major, minor) >= (5, 2)
+    })
+}
+
+/// Files installed by `pkg` as seen from inside the root, `None` when it
+/// isn't installed.
+pub fn package_files(pkg: &str) -> Option<Vec<String>> {
+    let output = query(pacman().arg("-Qlq").arg(pkg)).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // pacman prefixes every path with the root
+    let root = paths::install_root().to_string_lossy().into_owned();
+    Some(
+        lines(&output.stdout)
+            .into_iter()
+            .map(|line| match line.strip_prefix(root.trim_end_matches('/')) {
+                Some(path) if path.starts_with('/') => path.to_string(),
+                _ => line,
+            })
+            .collect(),
+    )
+}
+
+/// A file `pacman -Qkk` found differing from what its package installed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileProblem {
+    pub package: String,
+    pub path: PathBuf,
+    /// pacman's reason, e.g. "Size mismatch" or "No such file or directory"
+    pub reason: String,
+}
+
+impl FileProblem {
+    pub fn is_missing(&self) -> bool {
+        self.reason == "No such file or directory"
+    }
+}
+
+/// Check the files of `pkgs` against the metadata pacman recorded when it
+/// installed them, `None` when pacman can't be run. Files that can't be read
+/// without root are left out.
+pub fn check_files<S: AsRef<str>>(pkgs: &[S]) -> Option<Vec<FileProblem>> {
+    if pkgs.is_empty() {
+        return Some(Vec::new());
+    }
+    // pacman exits non-zero whenever it finds something
+    let output = query(pacman().arg("-Qkk").args(pkgs.iter().map(|p| p.as_ref()))).ok()?;
+    Some(parse_file_problems(&String::from_utf8_lossy(
+        &output.stderr,
+    )))
+}
+
+// "warning: foo: /usr/bin/foo (Size mismatch)"
+fn parse_file_problems(stderr: &str) -> Vec<FileProblem> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"^warning: (\S+): (/.*) \(([^()]+)\)$").unwrap());
+    stderr
+        .lines()
+        .filter_map(|line| re.captures(line.trim()))
+        .filter(|caps| &caps[3] != "Permission denied")
+        .map(|caps| FileProblem {
+            package: caps[1].to_string(),
+            path: PathBuf::from(&caps[2]),
+            reason: caps[3].to_string(),
+        })
+        .collect()
+}
+
+// `vercmp a b` prints -1, 0 or 1
+fn run_vercmp(a: &str, b: &str) -> Option<i32> {
+    let output = query(Command::new("vercmp").arg(a).arg(b)).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Compare versions the way pacman does, falling back to plain inequality without vercmp.
+pub fn is_newer(candidate: &str, installed: &str) -> bool {
+    match run_vercmp(candidate, installed) {
+        Some(ord) => ord > 0,
+        None => candidate != installed,
+    }
+}
+
+/// Order two versions the way pacman does, falling back to string order without vercmp.
+pub fn vercmp(a: &str, b: &str) -> Ordering {
+    match run_vercmp(a, b) {
+        Some(ord) => ord.cmp(&0),
+        None => a.cmp(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `LC_ALL=C pacman -Qi bash archcraft-openbox`
+    const QI: &str = "\
+Name            : bash
+Version         : 5.2.026-2
+Description     : The GNU Bourne Again shell
+Architecture    : x86_64
+URL             : https://www.gnu.org/software/bash/bash.html
+Licenses        : GPL-3.0-or-later
+Groups          : None
+Provides        : sh
+Depends On      : readline  libreadline.so=8-64  glibc  ncurses
+Optional Deps   : bash-completion: for tab completion
+Required By     : archcraft-openbox  bzip2  gzip
+Optional For    : None
+Conflicts With  : None
+Replaces        : None
+Installed Size  : 8.21 MiB
+Packager        : Tobias Powalowski <tpowa@archlinux.org>
+Build Date      : Sat 13 Jan 2024 10:21:55 AM UTC
+Install Date    : Mon 05 Feb 2024 08:14:02 PM UTC
+Install Reason  : Installed as a dependency for another package
+Install Script  : No
+Validated By    : Signature
+
+Name            : archcraft-openbox
+Version         : 2:3.6.1-14
+Description     : Openbox configurations for Archcraft
+Architecture    : any
+URL             : https://github.com/archcraft-os/archcraft-packages
+Licenses        : GPL3
+Groups          : None
+Provides        : None
+Depends On      : openbox  bash  polybar>=3.6
+Optional Deps   : None
+Required By     : None
+Optional For    : None
+Conflicts With  : None
+Replaces        : None
+Installed Size  : 21.30 MiB
+Packager        : Aditya Shakya <adi1090x@gmail.com>
+Build Date      : Sun 04 Feb 2024 01:02:03 PM UTC
+Install Date    : Mon 05 Feb 2024 08:14:05 PM UTC
+Install Reason  : Explicitly installed
+Install Script  : Yes
+Validated By    : None
+
+";
+
+    #[test]
+    fn parses_local_packages() {
+        let packages = parse_local_packages(QI);
+        assert_eq!(packages.len(), 2);
+
+        let bash = &packages[0];
+        assert_eq!(bash.name, "bash");
+        assert_eq!(bash.version, "5.2.026-2");
+        assert_eq!(bash.arch, "x86_64");
+        assert_eq!(bash.install_date, "Mon 05 Feb 2024 08:14:02 PM UTC");
+        assert!(!bash.explicit);
+        assert_eq!(
+            bash.depends,
+            ["readline", "libreadline.so=8-64", "glibc", "ncurses"]
+        );
+        assert_eq!(bash.required_by, ["archcraft-openbox", "bzip2", "gzip"]);
+        assert!(bash.optional_for.is_empty());
+
+        let openbox = &packages[1];
+        assert_eq!(openbox.name, "archcraft-openbox");
+        // The epoch's colon isn't taken for the separator
+        assert_eq!(openbox.version, "2:3.6.1-14");
+        assert!(openbox.explicit);
+        assert_eq!(openbox.depends, ["openbox", "bash", "polybar>=3.6"]);
+        assert!(openbox.required_by.is_empty());
+    }
+
+    #[test]
+    fn parses_owners() {
+        // `LC_ALL=C pacman -Qo /usr/bin/bash /etc/xdg/openbox/rc.xml /tmp/unowned`;
+        // the error for the unowned path goes to stderr
+        let stdout = "\
+/usr/bin/bash is owned by bash 5.2.026-2
+/etc/xdg/openbox/rc.xml is owned by archcraft-openbox 2:3.6.1-14
+";
+        let owners: HashMap<PathBuf, String> = parse_owners(stdout).collect();
+        assert_eq!(owners.len(), 2);
+        assert_eq!(owners[&PathBuf::from("/usr/bin/bash")], "bash");
+        assert_eq!(
+            owners[&PathBuf::from("/etc/xdg/openbox/rc.xml")],
+            "archcraft-openbox"
+        );
+        // A path with spaces keeps them
+        let owners: Vec<_> =
+            parse_owners("/usr/share/My Theme/index.theme is owned by theme 1-1").collect();
+        assert_eq!(
+            owners,
+            [(
+                PathBuf::from("/usr/share/My Theme/index.theme"),
+                String::from("theme")
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_file_problems() {
+        // stderr of `LC_ALL=C pacman -Qkk bash archcraft-openbox`, the per
+        // package totals go to stdout
+        let stderr = "\
+warning: archcraft-openbox: /etc/xdg/openbox/rc.xml (Modification time mismatch)
+warning: archcraft-openbox: /etc/xdg/openbox/rc.xml (Size mismatch)
+warning: archcraft-openbox: /usr/share/archcraft/My Theme/gtkrc (No such file or directory)
+warning: bash: /etc/bash.bash_logout (Permission denied)
+warning: could not get file information for /usr/share/doc/bash
+";
+        let problems = parse_file_problems(stderr);
+        let found: Vec<(&str, &str, &str, bool)> = problems
+            .iter()
+            .map(|p| {
+                (
+                    p.package.as_str(),
+                    p.path.to_str().unwrap(),
+                    p.reason.as_str(),
+                    p.is_missing(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    "archcraft-openbox",
+                    "/etc/xdg/openbox/rc.xml",
+                    "Modification time mismatch",
+                    false
+                ),
+                (
+                    "archcraft-openbox",
+                    "/etc/xdg/openbox/rc.xml",
+                    "Size mismatch",
+                    false
+                ),
+                (
+                    "archcraft-openbox",
+                    "/usr/share/archcraft/My Theme/gtkrc",
+                    "No such file or directory",
+                    true
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_nothing_from_empty_output() {
+        assert!(parse_local_packages("").is_empty());
+        assert!(parse_local_packages("error: package 'foo' was not found\n").is_empty());
+        assert!(parse_file_problems("").is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+//! `.pacnew` and `.pacsave` files pacman leaves next to modified config files.
+
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictKind {
+    /// The package's version of a file the user changed, not installed
+    Pacnew,
+    /// A changed file saved when its package was removed
+    Pacsave,
+}
+
+impl ConflictKind {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConflictKind::Pacnew => "pacnew",
+            ConflictKind::Pacsave => "pacsave",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigConflict {
+    pub package: String,
+    /// The live config file
+    pub path: PathBuf,
+    pub kind: ConflictKind,
+}
+
+impl ConfigConflict {
+    /// The `.pacnew`/`.pacsave` file itself.
+    pub fn conflict_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".");
+        path.push(self.kind.extension());
+        PathBuf::from(path)
+    }
+}
+
+/// Conflicts next to the files of `package` in the system under `root`. Paths
+/// may be relative to `/`, as in archive listings; directories are skipped.
+pub fn scan<S: AsRef<str>>(root: &Path, package: &str, files: &[S]) -> Vec<ConfigConflict> {
+    let mut conflicts = Vec::new();
+    for file in files {
+        let file = file.as_ref();
+        if file.ends_with('/') {
+            continue;
+        }
+        let path = root.join(file.trim_start_matches('/'));
+        for kind in [ConflictKind::Pacnew, ConflictKind::Pacsave] {
+            let conflict = ConfigConflict {
+                package: package.to_string(),
+                path: path.clone(),
+                kind,
+            };
+            if conflict.conflict_path().exists() {
+                conflicts.push(conflict);
+            }
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+    use std::fs;
+
+    #[test]
+    fn finds_conflicts_inside_the_root() {
+        let root = TempDir::new("root");
+        fs::create_dir_all(root.join("etc/xdg")).unwrap();
+        fs::write(root.join("etc/xdg/panel.conf"), "").unwrap();
+        fs::write(root.join("etc/xdg/panel.conf.pacnew"), "").unwrap();
+        fs::write(root.join("etc/xdg/old.conf.pacsave"), "").unwrap();
+
+        let conflicts = scan(
+            root.path(),
+            "panel",
+            &[
+                "etc/xdg/",
+                "/etc/xdg/panel.conf",
+                "etc/xdg/old.conf",
+                "etc/xdg/new.conf",
+            ],
+        );
+
+        assert_eq!(
+            conflicts,
+            [
+                ConfigConflict {
+                    package: String::from("panel"),
+                    path: root.join("etc/xdg/panel.conf"),
+                    kind: ConflictKind::Pacnew,
+                },
+                ConfigConflict {
+                    package: String::from("panel"),
+                    path: root.join("etc/xdg/old.conf"),
+                    kind: ConflictKind::Pacsave,
+                },
+            ]
+        );
+        assert_eq!(
+            conflicts[0].conflict_path(),
+            root.join("etc/xdg/panel.conf.pacnew")
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Locations of crafty's files on disk, following the XDG Base Directory spec.
+
+use std::{fs, io, os::unix::fs::PermissionsExt, path::Path, path::PathBuf, sync::OnceLock};
+
+// Files that belong in the config directory, everything else is state
+const CONFIG_FILES: [&str; 2] = ["config.json", "hooks"];
+
+// Set at most once, before anything is read from the state directory
+static ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+fn home() -> PathBuf {
+    dirs::home_dir().unwrap()
+}
+
+/// `$XDG_CONFIG_HOME/crafty`, holding the config and user hooks.
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| home().join(".config"))
+        .join("crafty")
+}
+
+/// `$XDG_CACHE_HOME/crafty`, for data that can be rebuilt, such as the files index.
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| home().join(".cache"))
+        .join("crafty")
+}
+
+/// `$XDG_STATE_HOME/crafty`, holding the package database, transaction
+/// history, config backups and the log file. With a [`root`] it is
+/// `<root>/var/lib/crafty`, so each root keeps its own database.
+pub fn state_dir() -> PathBuf {
+    if let Some(root) = root() {
+        return root.join("var/lib/crafty");
+    }
+    dirs::state_dir()
+        .unwrap_or_else(|| home().join(".local").join("state"))
+        .join("crafty")
+}
+
+/// Install into the system under `root` instead of `/`, e.g. a chroot or an
+/// ISO build tree; pacman is pointed at it with `--root` and `--dbpath`.
+/// Creates the directories pacman expects there, like pacstrap does. Only the
+/// first call has an effect.
+pub fn set_root(root: &Path) -> io::Result<()> {
+    for dir in ["var/lib/pacman", "var/cache/pacman/pkg", "var/log", "etc"] {
+        fs::create_dir_all(root.join(dir))?;
+    }
+    let tmp = root.join("tmp");
+    fs::create_dir_all(&tmp)?;
+    fs::set_permissions(&tmp, fs::Permissions::from_mode(0o1777))?;
+    // pacman resolves a relative root against its own working directory
+    let _ = ROOT.set(root.canonicalize()?);
+    Ok(())
+}
+
+/// The root set with [`set_root`], `None` for the live system.
+pub fn root() -> Option<&'static Path> {
+    ROOT.get().map(PathBuf::as_path)
+}
+
+/// The system packages are installed into: [`root`], or `/` for the live system.
+pub fn install_root() -> &'static Path {
+    root().unwrap_or(Path::new("/"))
+}
+
+/// pacman's database under [`root`].
+pub fn root_dbpath(root: &Path) -> PathBuf {
+    root.join("var/lib/pacman")
+}
+
+/// `$XDG_RUNTIME_DIR`, for sockets that live as long as the session; the
+/// state directory when it isn't set.
+pub fn runtime_dir() -> PathBuf {
+    dirs::runtime_dir().unwrap_or_else(state_dir)
+}
+
+/// Where package archives are downloaded before installation, unless the
+/// `download_dir` config key says otherwise.
+pub fn staging_dir() -> PathBuf {
+    cache_dir().join("pkg")
+}
+
+/// The single directory older versions kept everything in.
+pub fn legacy_dir() -> PathBuf {
+    home().join(".config").join(".crafty")
+}
+
+/// Move everything from [`legacy_dir`] to the XDG directories, keeping files
+/// that already exist there. Returns the (old, new) path of every moved entry;
+/// front-ends should call this before loading the config.
+pub fn migrate_legacy() -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let legacy = legacy_dir();
+    if !legacy.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut moved = Vec::new();
+    for entry in fs::read_dir(&legacy)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let dir = if CONFIG_FILES.iter().any(|f| name == *f) {
+            config_dir()
+        } else {
+            state_dir()
+        };
+        let target = dir.join(&name);
+        if target.exists() {
+            continue;
+        }
+        fs::create_dir_all(&dir)?;
+        move_path(&entry.path(), &target)?;
+        moved.push((entry.path(), target));
+    }
+
+    // Leave the directory alone if something was kept back
+    let _ = fs::remove_dir(&legacy);
+    Ok(moved)
+}
+
+// rename(2), or copy and delete when the XDG directories are on another filesystem
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    copy_recursive(from, to)?;
+    if from.is_dir() {
+        fs::remove_dir_all(from)
+    } else {
+        fs::remove_file(from)
+    }
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    if !from.is_dir() {
+        return fs::copy(from, to).map(|_| ());
+    }
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
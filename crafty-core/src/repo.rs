@@ -0,0 +1,372 @@
+//! Where packages come from: the [`RepoClient`] trait and a repository in a
+//! local directory. The ArchCraft repositories on GitHub are in [`crate::github`].
+
+use crate::{
+    Error, EventHook, Result,
+    archive::{self, Compression, EXTENSION_PATTERN, is_valid_archive, parse_package_file},
+    changelog::Commit,
+    search::{SearchMatch, SearchPattern},
+};
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// A package file in the repository, with the details its name carries.
+#[derive(Debug, Clone)]
+pub struct RepoPackage {
+    pub file: String,
+    pub name: String,
+    pub version: String,
+    pub arch: String,
+    /// Size of the archive in bytes, when known
+    pub size: Option<u64>,
+    /// Name of the repository it comes from, see [`crate::Config::repos`]
+    pub source: String,
+}
+
+impl RepoPackage {
+    /// Parse "archcraft-foo-1.2-3-any.pkg.tar.zst".
+    pub fn from_file(file: &str) -> Option<Self> {
+        static RE: OnceLock<Regex> = OnceLock::new();
+        let re = RE.get_or_init(|| {
+            Regex::new(&format!(
+                r"^(?P<name>.+)-(?P<version>[^-]+-\d+)-(?P<arch>any|x86_64){}$",
+                EXTENSION_PATTERN
+            ))
+            .unwrap()
+        });
+        let caps = re.captures(file)?;
+        Some(Self {
+            file: file.to_string(),
+            name: caps["name"].to_string(),
+            version: caps["version"].to_string(),
+            arch: caps["arch"].to_string(),
+            size: None,
+            source: String::new(),
+        })
+    }
+}
+
+/// Metadata the repository keeps about a file in its package directory.
+#[derive(Debug, Clone)]
+pub struct RemoteFile {
+    pub size: u64,
+    /// Git blob hash of the contents, see [`crate::archive::git_blob_sha`]
+    pub sha: String,
+}
+
+/// A package repository: lists package files and fetches them. Everything
+/// besides the index, file metadata and downloads is derived from those, so a
+/// backend only needs to provide them.
+pub trait RepoClient: Send + Sync {
+    /// Names of all entries in the package directories of the repositories,
+    /// highest priority first.
+    fn index(&self) -> Result<&[String]>;
+
+    /// Size and hash of every file in the package directories.
+    fn remote_files(&self) -> Result<&HashMap<String, RemoteFile>>;
+
+    /// Fetch a package archive into `dir` and validate it, reusing a verified
+    /// earlier download.
+    fn download_package(&self, package_file: &str, dir: &Path) -> Result<PathBuf>;
+
+    /// Name of the repository `package_file` comes from, see [`crate::Config::repos`].
+    fn source_name(&self, package_file: &str) -> &str;
+
+    /// GET `url` as text, for lookups outside the repository such as the AUR.
+    fn fetch_text(&self, url: &str) -> Result<String>;
+
+    /// GET `url` as bytes, counted in [`downloaded_bytes`](Self::downloaded_bytes).
+    fn download(&self, url: &str) -> Result<Vec<u8>>;
+
+    /// Report retries and downloads to `hook`.
+    fn set_events(&mut self, _hook: EventHook) {}
+
+    /// Forget the index and file metadata fetched so far, so the next lookup
+    /// fetches them again.
+    fn refresh(&mut self) {}
+
+    /// The index as far as it is known without going over the network, e.g.
+    /// for shell completion; backends with a remote index override this.
+    fn offline_index(&self) -> Vec<String> {
+        self.index().map(<[String]>::to_vec).unwrap_or_default()
+    }
+
+    /// Total size of everything downloaded through this client.
+    fn downloaded_bytes(&self) -> u64 {
+        0
+    }
+
+    /// Commits touching `file` in the package directory of the repository
+    /// `package_file` comes from, or the whole directory when `None`, newest
+    /// first. Empty when the repository keeps no history.
+    fn commits(
+        &self,
+        _package_file: &str,
+        _file: Option<&str>,
+        _since: Option<&str>,
+    ) -> Result<Vec<Commit>> {
+        Ok(Vec::new())
+    }
+
+    /// An earlier download of `package_file` in `dir`, if its size and hash
+    /// match the repository, or `sha256` when given, e.g. from a lock file.
+    fn cached_package(
+        &self,
+        package_file: &str,
+        dir: &Path,
+        sha256: Option<&str>,
+    ) -> Option<PathBuf> {
+        self.is_cached(package_file, dir, sha256)
+            .then(|| dir.join(package_file))
+    }
+
+    /// All package files in the repository.
+    fn packages(&self) -> Result<Vec<String>> {
+        // Regex to match package files
+        let pkg_re = Regex::new(&format!(
+            r"^(.+)-[\d\.]+-\d+-(any|x86_64){}$",
+            EXTENSION_PATTERN
+        ))
+        .unwrap();
+        Ok(self
+            .index()?
+            .iter()
+            .filter(|name| pkg_re.is_match(name))
+            .cloned()
+            .collect())
+    }
+
+    /// All packages with their parsed details. Sizes come from
+    /// [`remote_files`](Self::remote_files) and are left out when it fails,
+    /// e.g. when the GitHub API is rate limited.
+    fn package_details(&self) -> Result<Vec<RepoPackage>> {
+        let files = self
+            .remote_files()
+            .inspect_err(|e| log::debug!("Package sizes unavailable: {}", e))
+            .ok();
+        Ok(self
+            .packages()?
+            .iter()
+            .filter_map(|file| RepoPackage::from_file(file))
+            .map(|mut package| {
+                package.size = files
+                    .and_then(|files| files.get(&package.file))
+                    .map(|f| f.size);
+                package.source = self.source_name(&package.file).to_string();
+                package
+            })
+            .collect())
+    }
+
+    /// The file providing `pkg`, also accepting names without the `archcraft-`
+    /// prefix, for names the user typed.
+    fn find_package_file(&self, pkg: &str) -> Result<Option<String>> {
+        let re = package_file_regex(&format!("(?:archcraft-)?{}", regex::escape(pkg)));
+        Ok(self.index()?.iter().find(|name| re.is_match(name)).cloned())
+    }
+
+    /// The file providing exactly `pkg`, for dependencies, which name real
+    /// packages: `polybar` must not pull in `archcraft-polybar`.
+    fn find_dependency_file(&self, pkg: &str) -> Result<Option<String>> {
+        let re = package_file_regex(&regex::escape(pkg));
+        Ok(self.index()?.iter().find(|name| re.is_match(name)).cloned())
+    }
+
+    /// Version of every package in the highest priority repository that has
+    /// it, keyed by package name.
+    fn remote_versions(&self) -> Result<HashMap<String, String>> {
+        let mut versions = HashMap::new();
+        for (name, version) in self
+            .packages()?
+            .iter()
+            .filter_map(|file| parse_package_file(file))
+        {
+            versions.entry(name).or_insert(version);
+        }
+        Ok(versions)
+    }
+
+    /// Packages matching any of the patterns, best matches first.
+    fn search(&self, patterns: &[SearchPattern]) -> Result<Vec<SearchMatch>> {
+        // Regex to match package files and extract package name
+        let pkg_re = Regex::new(&format!(
+            r"^(?P<pkg_name>.+)-[\d\.]+-\d+-(any|x86_64){}$",
+            EXTENSION_PATTERN
+        ))
+        .unwrap();
+
+        let mut matching_packages = Vec::new();
+        for name in self.index()? {
+            let Some(pkg_name) = pkg_re.captures(name).and_then(|c| c.name("pkg_name")) else {
+                continue;
+            };
+            // Search only in the package name part (without version and extension)
+            let best = patterns
+                .iter()
+                .filter_map(|pattern| pattern.score(pkg_name.as_str()))
+                .max_by_key(|(kind, score, _)| (*kind, *score));
+            if let Some((kind, score, positions)) = best {
+                matching_packages.push(SearchMatch {
+                    file: name.to_string(),
+                    kind,
+                    score,
+                    positions,
+                });
+            }
+        }
+
+        matching_packages.sort_by(|a, b| a.rank(b));
+        Ok(matching_packages)
+    }
+
+    /// Like [`cached_package`](Self::cached_package), without reporting the file as used.
+    fn is_cached(&self, package_file: &str, dir: &Path, sha256: Option<&str>) -> bool {
+        let path = dir.join(package_file);
+        let Ok(metadata) = fs::metadata(&path) else {
+            return false;
+        };
+        match sha256 {
+            Some(expected) => archive::sha256(&path).is_some_and(|actual| actual == expected),
+            None => {
+                // Without the repository's hash there's nothing to trust the file by
+                let Some(remote) = self.remote_files().ok().and_then(|f| f.get(package_file))
+                else {
+                    return false;
+                };
+                remote.size == metadata.len()
+                    && archive::git_blob_sha(&path).is_some_and(|actual| actual == remote.sha)
+            }
+        }
+    }
+}
+
+// Package files whose name matches the regex `name`
+fn package_file_regex(name: &str) -> Regex {
+    Regex::new(&format!(
+        r"^{}-[\d\.]+-\d+-(any|x86_64){}$",
+        name, EXTENSION_PATTERN
+    ))
+    .unwrap()
+}
+
+/// A directory of package archives serving as the repository, e.g. a local
+/// mirror or test fixtures. Packages are copied out of it, nothing goes over
+/// the network except [`fetch_text`](RepoClient::fetch_text) and
+/// [`download`](RepoClient::download).
+pub struct LocalRepo {
+    name: String,
+    dir: PathBuf,
+    index: OnceLock<Vec<String>>,
+    files: OnceLock<HashMap<String, RemoteFile>>,
+    downloaded: AtomicU64,
+}
+
+impl LocalRepo {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            name: String::from("local"),
+            dir: dir.into(),
+            index: OnceLock::new(),
+            files: OnceLock::new(),
+            downloaded: AtomicU64::new(0),
+        }
+    }
+
+    /// Name reported as the source of its packages, "local" by default.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl RepoClient for LocalRepo {
+    fn index(&self) -> Result<&[String]> {
+        if let Some(index) = self.index.get() {
+            return Ok(index);
+        }
+        let entries = fs::read_dir(&self.dir)
+            .map_err(|e| Error::Index(format!("cannot read {}: {}", self.dir.display(), e)))?;
+        let mut files: Vec<String> = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_file())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        Ok(self.index.get_or_init(|| files))
+    }
+
+    fn remote_files(&self) -> Result<&HashMap<String, RemoteFile>> {
+        if let Some(files) = self.files.get() {
+            return Ok(files);
+        }
+        // Hashing every file is slow, only archives get verified against it
+        let files = self
+            .index()?
+            .iter()
+            .filter(|name| Compression::of(name).is_some())
+            .filter_map(|name| {
+                let path = self.dir.join(name);
+                let file = RemoteFile {
+                    size: fs::metadata(&path).ok()?.len(),
+                    sha: archive::git_blob_sha(&path)?,
+                };
+                Some((name.clone(), file))
+            })
+            .collect();
+        Ok(self.files.get_or_init(|| files))
+    }
+
+    fn download_package(&self, package_file: &str, dir: &Path) -> Result<PathBuf> {
+        let source = self.dir.join(package_file);
+        if !source.is_file() {
+            return Err(Error::NotFound(package_file.to_string()));
+        }
+        if let Some(path) = self.cached_package(package_file, dir, None) {
+            return Ok(path);
+        }
+        fs::create_dir_all(dir)?;
+        let path = dir.join(package_file);
+        let bytes = fs::copy(&source, &path)?;
+        self.downloaded.fetch_add(bytes, Ordering::Relaxed);
+        if !is_valid_archive(&path, package_file) {
+            return Err(Error::InvalidArchive(path));
+        }
+        Ok(path)
+    }
+
+    fn source_name(&self, _package_file: &str) -> &str {
+        &self.name
+    }
+
+    fn fetch_text(&self, url: &str) -> Result<String> {
+        Ok(reqwest::blocking::get(url)?.error_for_status()?.text()?)
+    }
+
+    fn download(&self, url: &str) -> Result<Vec<u8>> {
+        let bytes = reqwest::blocking::get(url)?.error_for_status()?.bytes()?;
+        self.downloaded
+            .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        Ok(bytes.to_vec())
+    }
+
+    fn refresh(&mut self) {
+        self.index = OnceLock::new();
+        self.files = OnceLock::new();
+    }
+
+    fn downloaded_bytes(&self) -> u64 {
+        self.downloaded.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,306 @@
+//! Dependency resolution across the ArchCraft repository.
+
+use crate::{
+    Error, LocalPackages, RepoClient, Result,
+    archive::{
+        PkgInfo, package_name_from_file, parse_package_file, read_pkginfo, strip_version_constraint,
+    },
+    descriptions, files,
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// A package file fetched from the repository, staged for a pacman transaction.
+#[derive(Debug, Clone)]
+pub struct StagedPackage {
+    pub name: String,
+    pub version: String,
+    pub file: String,
+    pub path: PathBuf,
+    pub info: PkgInfo,
+    /// Pulled in by another staged package rather than requested
+    pub dependency: bool,
+}
+
+/// Download `pkg` plus every unsatisfied dependency that only the ArchCraft
+/// repository provides. The requested package comes first; dependencies that
+/// aren't in the repository are left for pacman to pull from the sync repos.
+/// What counts as satisfied is asked of `local`.
+pub fn resolve(
+    repo: &dyn RepoClient,
+    local: &dyn LocalPackages,
+    pkg: &str,
+    staging: &Path,
+) -> Result<Vec<StagedPackage>> {
+    resolve_with(repo, Some(local), pkg, staging, false)
+}
+
+/// Like [`resolve`], but also follows dependencies that are already installed,
+/// so the result doesn't depend on the state of this machine.
+pub fn resolve_closure(
+    repo: &dyn RepoClient,
+    pkg: &str,
+    staging: &Path,
+) -> Result<Vec<StagedPackage>> {
+    resolve_with(repo, None, pkg, staging, false)
+}
+
+/// Stage a local package file, e.g. one built with makepkg, plus the unsatisfied
+/// dependencies the ArchCraft repository provides. Name and version come from
+/// the archive's .PKGINFO, or its file name if that lacks them.
+pub fn resolve_file(
+    repo: &dyn RepoClient,
+    local: &dyn LocalPackages,
+    path: &Path,
+    staging: &Path,
+) -> Result<Vec<StagedPackage>> {
+    let path = path.canonicalize()?;
+    let info = read_pkginfo(&path).ok_or_else(|| Error::InvalidArchive(path.clone()))?;
+    let file = path
+        .file_name()
+        .map(|f| f.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (name, version) = if info.name.is_empty() {
+        parse_package_file(&file).ok_or_else(|| Error::InvalidArchive(path.clone()))?
+    } else {
+        (info.name.clone(), info.version.clone())
+    };
+
+    let missing = local.unsatisfied_dependencies(&info.depends);
+    let mut staged = vec![StagedPackage {
+        name,
+        version,
+        file,
+        path,
+        info,
+        dependency: false,
+    }];
+    for dep in missing {
+        let dep_name = strip_version_constraint(&dep);
+        if staged.iter().any(|p| p.name == dep_name) {
+            continue;
+        }
+        let deps = match resolve_with(repo, Some(local), dep_name, staging, true) {
+            Ok(deps) => deps,
+            // Not an ArchCraft package, pacman resolves it from the sync repositories
+            Err(Error::NotFound(_)) => continue,
+            Err(e) => return Err(e),
+        };
+        for package in deps {
+            if !staged.iter().any(|p| p.name == package.name) {
+                staged.push(package);
+            }
+        }
+    }
+    Ok(staged)
+}
+
+// Without `local` every dependency is followed, installed or not. Only a
+// package the user named may omit the `archcraft-` prefix, dependencies and a
+// `pkg` that is one itself are looked up by their exact name.
+fn resolve_with(
+    repo: &dyn RepoClient,
+    local: Option<&dyn LocalPackages>,
+    pkg: &str,
+    staging: &Path,
+    dependency: bool,
+) -> Result<Vec<StagedPackage>> {
+    let mut staged = Vec::new();
+    let mut seen = HashSet::from([pkg.to_string()]);
+    let mut queue = vec![pkg.to_string()];
+
+    while let Some(name) = queue.pop() {
+        // Attempt to find the correct package file by listing available files
+        let lookup = if dependency || !staged.is_empty() {
+            repo.find_dependency_file(&name)?
+        } else {
+            repo.find_package_file(&name)?
+        };
+        let package_file = match lookup {
+            Some(file) => file,
+            None if staged.is_empty() => return Err(Error::NotFound(name)),
+            // Not an ArchCraft package, pacman resolves it from the sync repositories
+            None => continue,
+        };
+
+        let path = repo.download_package(&package_file, staging)?;
+        files::record(&package_file, &path);
+        let info = read_pkginfo(&path).unwrap_or_default();
+        descriptions::record(&package_file, &info.desc);
+        let (real_name, version) = if info.name.is_empty() {
+            parse_package_file(&package_file)
+                .unwrap_or_else(|| (package_name_from_file(&package_file), String::new()))
+        } else {
+            (info.name.clone(), info.version.clone())
+        };
+
+        let missing = match local {
+            Some(local) => local.unsatisfied_dependencies(&info.depends),
+            None => info.depends.clone(),
+        };
+        for dep in missing {
+            let dep_name = strip_version_constraint(&dep).to_string();
+            if seen.insert(dep_name.clone()) {
+                queue.push(dep_name);
+            }
+        }
+
+        staged.push(StagedPackage {
+            dependency: dependency || !staged.is_empty(),
+            name: real_name,
+            version,
+            file: package_file,
+            path,
+            info,
+        });
+    }
+
+    Ok(staged)
+}
+
+/// Order `staged` so every package comes after the staged packages it depends
+/// on, keeping the given order otherwise. Dependency cycles are broken where
+/// they are first found.
+pub fn dependency_order(staged: Vec<StagedPackage>) -> Vec<StagedPackage> {
+    fn visit(i: usize, staged: &[StagedPackage], visited: &mut [bool], order: &mut Vec<usize>) {
+        if visited[i] {
+            return;
+        }
+        visited[i] = true;
+        for dep in &staged[i].info.depends {
+            let dep = strip_version_constraint(dep);
+            if let Some(j) = staged.iter().position(|p| p.name == dep) {
+                visit(j, staged, visited, order);
+            }
+        }
+        order.push(i);
+    }
+
+    let mut visited = vec![false; staged.len()];
+    let mut order = Vec::with_capacity(staged.len());
+    for i in 0..staged.len() {
+        visit(i, &staged, &mut visited, &mut order);
+    }
+    let mut slots: Vec<Option<StagedPackage>> = staged.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        LocalRepo, RecordingInstaller,
+        testutil::{TempDir, package},
+    };
+
+    // An ArchCraft wrapper whose name ends in the name of a real package
+    fn repo() -> TempDir {
+        let repo = TempDir::new("repo");
+        package(
+            repo.path(),
+            "crafty-fixture-panel",
+            "1.0-1",
+            &["crafty-fixture-bar"],
+            &[],
+        );
+        package(
+            repo.path(),
+            "archcraft-crafty-fixture-bar",
+            "1.0-1",
+            &[],
+            &[],
+        );
+        repo
+    }
+
+    #[test]
+    fn dependencies_are_looked_up_by_their_exact_name() {
+        let repo = repo();
+        let staging = TempDir::new("staging");
+        let local = RecordingInstaller::new();
+
+        let staged = resolve(
+            &LocalRepo::new(repo.path()),
+            &local,
+            "crafty-fixture-panel",
+            staging.path(),
+        )
+        .unwrap();
+
+        let names: Vec<&str> = staged.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["crafty-fixture-panel"]);
+    }
+
+    #[test]
+    fn requested_packages_may_omit_the_prefix() {
+        let repo = repo();
+        let staging = TempDir::new("staging");
+        let local = RecordingInstaller::new();
+
+        let staged = resolve(
+            &LocalRepo::new(repo.path()),
+            &local,
+            "crafty-fixture-bar",
+            staging.path(),
+        )
+        .unwrap();
+
+        assert_eq!(staged.len(), 1);
+        assert_eq!(staged[0].name, "archcraft-crafty-fixture-bar");
+        assert!(!staged[0].dependency);
+    }
+
+    fn staged(name: &str, depends: &[&str]) -> StagedPackage {
+        StagedPackage {
+            name: name.to_string(),
+            version: String::from("1.0-1"),
+            file: format!("{}-1.0-1-any.pkg.tar.zst", name),
+            path: PathBuf::from(name),
+            info: PkgInfo {
+                name: name.to_string(),
+                depends: depends.iter().map(|d| d.to_string()).collect(),
+                ..PkgInfo::default()
+            },
+            dependency: false,
+        }
+    }
+
+    fn names(staged: &[StagedPackage]) -> Vec<&str> {
+        staged.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn dependencies_come_first() {
+        let ordered = dependency_order(vec![
+            staged("app", &["lib>=2", "glibc"]),
+            staged("lib", &["base"]),
+            staged("base", &[]),
+        ]);
+        assert_eq!(names(&ordered), ["base", "lib", "app"]);
+    }
+
+    #[test]
+    fn unrelated_packages_keep_their_order() {
+        let ordered = dependency_order(vec![
+            staged("theme", &[]),
+            staged("app", &["lib"]),
+            staged("icons", &[]),
+            staged("lib", &[]),
+        ]);
+        assert_eq!(names(&ordered), ["theme", "lib", "app", "icons"]);
+    }
+
+    #[test]
+    fn cycles_keep_every_package() {
+        let ordered = dependency_order(vec![
+            staged("a", &["b"]),
+            staged("b", &["c"]),
+            staged("c", &["a"]),
+            staged("d", &["a"]),
+        ]);
+        assert_eq!(names(&ordered), ["c", "b", "a", "d"]);
+        assert!(dependency_order(Vec::new()).is_empty());
+    }
+}
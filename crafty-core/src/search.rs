@@ -0,0 +1,200 @@
+//! Matching package names against fuzzy or regex patterns.
+
+use crate::fuzzy;
+use regex::Regex;
+
+pub enum SearchPattern {
+    Fuzzy(String),
+    Regex(Regex),
+}
+
+impl std::fmt::Display for SearchPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchPattern::Fuzzy(keyword) => write!(f, "'{}'", keyword),
+            SearchPattern::Regex(re) => write!(f, "/{}/", re),
+        }
+    }
+}
+
+/// How a pattern matched a name; better kinds rank first regardless of score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchKind {
+    /// The characters appear in order, with gaps
+    Fuzzy,
+    Substring,
+    Prefix,
+    Exact,
+}
+
+/// A search hit with its score and the matched character positions.
+#[derive(Debug, Clone)]
+pub struct SearchMatch {
+    pub file: String,
+    pub kind: MatchKind,
+    /// Orders hits of the same kind, higher is better
+    pub score: i64,
+    pub positions: Vec<usize>,
+}
+
+impl SearchMatch {
+    /// Best matches first, then by file name.
+    pub fn rank(&self, other: &Self) -> std::cmp::Ordering {
+        other
+            .kind
+            .cmp(&self.kind)
+            .then_with(|| other.score.cmp(&self.score))
+            .then_with(|| self.file.cmp(&other.file))
+    }
+}
+
+impl SearchPattern {
+    /// Score a package name, `None` when it doesn't match.
+    pub fn score(&self, name: &str) -> Option<(MatchKind, i64, Vec<usize>)> {
+        let scored = self.score_name(name);
+        if let Some((kind, score, _)) = &scored {
+            log::trace!("{} matches {} ({:?}, score {})", name, self, kind, score);
+        }
+        scored
+    }
+
+    /// Character positions of the pattern in `text`, `None` when it doesn't
+    /// occur. Fuzzy patterns are matched literally, ignoring case.
+    pub fn find(&self, text: &str) -> Option<Vec<usize>> {
+        let (start, len) = match self {
+            SearchPattern::Fuzzy(keyword) => {
+                (find_literal(keyword, text)?, keyword.chars().count())
+            }
+            SearchPattern::Regex(re) => {
+                let m = re.find(text)?;
+                (
+                    text[..m.start()].chars().count(),
+                    m.as_str().chars().count(),
+                )
+            }
+        };
+        Some((start..start + len).collect())
+    }
+
+    fn score_name(&self, name: &str) -> Option<(MatchKind, i64, Vec<usize>)> {
+        match self {
+            SearchPattern::Fuzzy(keyword) => {
+                let (score, positions) = fuzzy::score(keyword, name)?;
+                // Highlight the literal occurrence rather than the fuzzy alignment
+                match find_literal(keyword, name) {
+                    Some(start) => {
+                        let len = keyword.chars().count();
+                        let kind = span_kind(start, len, name);
+                        Some((kind, score, (start..start + len).collect()))
+                    }
+                    None => Some((MatchKind::Fuzzy, score, positions)),
+                }
+            }
+            // The matched span is what gets highlighted
+            SearchPattern::Regex(re) => {
+                let m = re.find(name)?;
+                let start = name[..m.start()].chars().count();
+                let len = m.as_str().chars().count();
+                // Shorter names first among equally good regex hits
+                let score = -(name.chars().count() as i64);
+                Some((
+                    span_kind(start, len, name),
+                    score,
+                    (start..start + len).collect(),
+                ))
+            }
+        }
+    }
+}
+
+// Character offset of `keyword` in `name`, ignoring case
+fn find_literal(keyword: &str, name: &str) -> Option<usize> {
+    let keyword: Vec<char> = keyword.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    if keyword.is_empty() {
+        return None;
+    }
+    name.windows(keyword.len()).position(|w| w == keyword)
+}
+
+fn span_kind(start: usize, len: usize, name: &str) -> MatchKind {
+    match (start, len == name.chars().count()) {
+        (0, true) => MatchKind::Exact,
+        (0, false) => MatchKind::Prefix,
+        _ => MatchKind::Substring,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(pattern: &SearchPattern, file: &str) -> Option<SearchMatch> {
+        let (kind, score, positions) = pattern.score(file)?;
+        Some(SearchMatch {
+            file: file.to_string(),
+            kind,
+            score,
+            positions,
+        })
+    }
+
+    #[test]
+    fn fuzzy_patterns_rank_literal_hits_first() {
+        let pattern = SearchPattern::Fuzzy(String::from("openbox"));
+        let mut hits: Vec<SearchMatch> = [
+            "archcraft-openbox-themes",
+            "openbox-menu",
+            "archcraft-open-box",
+            "openbox",
+            "archcraft-openbox",
+            "archcraft-polybar",
+        ]
+        .into_iter()
+        .filter_map(|file| hit(&pattern, file))
+        .collect();
+        hits.sort_by(|a, b| a.rank(b));
+
+        let ranked: Vec<(&str, MatchKind)> =
+            hits.iter().map(|h| (h.file.as_str(), h.kind)).collect();
+        assert_eq!(
+            ranked,
+            [
+                ("openbox", MatchKind::Exact),
+                ("openbox-menu", MatchKind::Prefix),
+                ("archcraft-openbox", MatchKind::Substring),
+                ("archcraft-openbox-themes", MatchKind::Substring),
+                ("archcraft-open-box", MatchKind::Fuzzy),
+            ]
+        );
+        // The literal occurrence is highlighted
+        assert_eq!(hits[2].positions, (10..17).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn regex_patterns_prefer_shorter_names() {
+        let pattern = SearchPattern::Regex(Regex::new("gtk-theme").unwrap());
+        let short = hit(&pattern, "archcraft-gtk-theme-arc").unwrap();
+        let long = hit(&pattern, "archcraft-gtk-theme-arc-darker").unwrap();
+        assert_eq!(short.kind, MatchKind::Substring);
+        assert_eq!(short.positions, (10..19).collect::<Vec<_>>());
+        assert_eq!(short.rank(&long), std::cmp::Ordering::Less);
+        assert!(hit(&pattern, "archcraft-openbox").is_none());
+
+        let anchored = SearchPattern::Regex(Regex::new("^archcraft-.*box$").unwrap());
+        assert_eq!(
+            hit(&anchored, "archcraft-openbox").unwrap().kind,
+            MatchKind::Exact
+        );
+        assert_eq!(anchored.to_string(), "/^archcraft-.*box$/");
+    }
+
+    #[test]
+    fn finds_patterns_in_descriptions() {
+        let pattern = SearchPattern::Fuzzy(String::from("Openbox"));
+        assert_eq!(pattern.find("Themes for openbox"), Some((11..18).collect()));
+        assert_eq!(pattern.find("Themes for obx"), None);
+        let pattern = SearchPattern::Regex(Regex::new("[0-9]+").unwrap());
+        assert_eq!(pattern.find("Polybär 3.6"), Some(vec![8]));
+    }
+}
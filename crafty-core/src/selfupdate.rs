@@ -0,0 +1,142 @@
+//! Updating a standalone crafty binary from the project's GitHub releases.
+
+use crate::{Error, RepoClient, Result, archive, util::natural_cmp};
+use serde_json::Value;
+use std::{
+    cmp::Ordering,
+    env::consts::ARCH,
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/philopaterwaheed/crafty/releases/latest";
+
+/// A file attached to a release.
+#[derive(Debug, Clone)]
+pub struct Asset {
+    pub name: String,
+    pub url: String,
+}
+
+/// The latest release with the binary for this architecture.
+#[derive(Debug, Clone)]
+pub struct Release {
+    /// Tag without the leading `v`
+    pub version: String,
+    pub binary: Asset,
+    /// `<binary>.sha256` or a `sha256sums` file covering it
+    pub checksum: Asset,
+}
+
+impl Release {
+    pub fn is_newer_than(&self, current: &str) -> bool {
+        natural_cmp(&self.version, current) == Ordering::Greater
+    }
+}
+
+/// Look up the latest release and its assets for this machine.
+pub fn latest_release(repo: &dyn RepoClient) -> Result<Release> {
+    let text = repo.fetch_text(LATEST_RELEASE_URL)?;
+    let json: Value = serde_json::from_str(&text).map_err(|e| Error::SelfUpdate(e.to_string()))?;
+    let tag = json
+        .get("tag_name")
+        .and_then(|t| t.as_str())
+        .ok_or_else(|| Error::SelfUpdate(String::from("unexpected release response")))?;
+    let assets: Vec<Asset> = json
+        .get("assets")
+        .and_then(|a| a.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|asset| {
+            Some(Asset {
+                name: asset.get("name")?.as_str()?.to_string(),
+                url: asset.get("browser_download_url")?.as_str()?.to_string(),
+            })
+        })
+        .collect();
+
+    // e.g. "crafty-x86_64-linux", anything but checksums and archives
+    let binary = assets
+        .iter()
+        .find(|a| {
+            let name = a.name.to_lowercase();
+            name.starts_with("crafty")
+                && name.contains(ARCH)
+                && !name.contains("sha256")
+                && !name.ends_with(".tar.gz")
+        })
+        .cloned()
+        .ok_or_else(|| Error::SelfUpdate(format!("release {} has no {} binary", tag, ARCH)))?;
+    let checksum = assets
+        .iter()
+        .find(|a| a.name == format!("{}.sha256", binary.name))
+        .or_else(|| {
+            assets
+                .iter()
+                .find(|a| a.name.to_lowercase().starts_with("sha256sums"))
+        })
+        .cloned()
+        .ok_or_else(|| Error::SelfUpdate(format!("release {} has no checksums", tag)))?;
+
+    Ok(Release {
+        version: tag.trim_start_matches('v').to_string(),
+        binary,
+        checksum,
+    })
+}
+
+/// Download the release binary, verify it and atomically replace `exe` with it.
+pub fn install(repo: &dyn RepoClient, release: &Release, exe: &Path) -> Result<()> {
+    let checksums = repo.fetch_text(&release.checksum.url)?;
+    let expected = expected_sha256(&checksums, &release.binary.name).ok_or_else(|| {
+        Error::SelfUpdate(format!(
+            "{} doesn't list {}",
+            release.checksum.name, release.binary.name
+        ))
+    })?;
+
+    let bytes = repo.download(&release.binary.url)?;
+    // Next to the executable so the final rename stays on one filesystem
+    let dir = exe.parent().unwrap_or(Path::new("/"));
+    let mut new = exe.as_os_str().to_owned();
+    new.push(".new");
+    let new = PathBuf::from(new);
+    fs::write(&new, &bytes).map_err(|e| {
+        Error::SelfUpdate(format!(
+            "cannot write to {}: {} (was crafty installed by a package manager?)",
+            dir.display(),
+            e
+        ))
+    })?;
+
+    let actual = archive::sha256(&new).unwrap_or_default();
+    if actual != expected {
+        let _ = fs::remove_file(&new);
+        return Err(Error::ChecksumMismatch {
+            file: release.binary.name.clone(),
+            expected,
+            actual,
+        });
+    }
+    fs::set_permissions(&new, fs::Permissions::from_mode(0o755))?;
+    // The running process keeps its copy, the next start picks up the new one
+    fs::rename(&new, exe)?;
+    Ok(())
+}
+
+// Either a bare hash or `sha256sum` output listing several files
+fn expected_sha256(checksums: &str, file: &str) -> Option<String> {
+    let mut lines = checksums.lines().filter(|l| !l.trim().is_empty());
+    let matching = checksums.lines().find_map(|line| {
+        let (hash, name) = line.split_once(char::is_whitespace)?;
+        (name.trim().trim_start_matches('*') == file).then_some(hash)
+    });
+    let hash = match (matching, lines.next(), lines.next()) {
+        (Some(hash), _, _) => hash,
+        (None, Some(only), None) => only.split_whitespace().next()?,
+        _ => return None,
+    };
+    Some(hash.to_lowercase())
+}
@@ -0,0 +1,161 @@
+//! Figures about the managed packages and crafty's files, behind `crafty stats`.
+
+use crate::{
+    Manager, aur, changelog, github,
+    history::{self, Action},
+    paths,
+    repo::RepoPackage,
+    updates::UpdateState,
+    util::dir_size,
+};
+use serde::Serialize;
+use std::collections::HashSet;
+
+// Repository packages listed as recently changed
+const RECENT: usize = 10;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Stats {
+    /// Packages in crafty's database
+    pub installed: usize,
+    pub held: usize,
+    /// Of those, built from the AUR
+    pub aur: usize,
+    /// Sum of the sizes recorded when the packages were installed
+    pub installed_size: u64,
+    /// Packages without a recorded size, e.g. adopted ones
+    pub unknown_size: usize,
+    /// Bytes in the cache and download directories
+    pub cache_size: u64,
+    /// When the repository index was last fetched or found unchanged
+    pub last_refresh: Option<u64>,
+    /// The latest successful upgrade in the history
+    pub last_upgrade: Option<u64>,
+    /// Updates available for the managed packages, `None` when the repository
+    /// can't be reached and no earlier check was recorded
+    pub pending_updates: Option<usize>,
+    /// When the repository couldn't be reached, the time of the last
+    /// `upgrade --check` that `pending_updates` comes from
+    pub checked_at: Option<u64>,
+    /// Repository packages whose changes are most recent, newest first
+    pub recent: Vec<RecentChange>,
+}
+
+/// A repository package and when a commit last touched it.
+#[derive(Serialize, Debug, Clone)]
+pub struct RecentChange {
+    pub name: String,
+    /// ISO 8601 commit date
+    pub date: String,
+}
+
+impl Stats {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+/// Gather everything; the network is only used for pending updates and the
+/// recent changes, both left empty or taken from the last check without it.
+pub fn collect(manager: &Manager) -> Stats {
+    let db = manager.load_db();
+    let sizes: Vec<Option<u64>> = db.packages.values().map(|p| p.installed_size).collect();
+
+    let staging = manager.config.staging_dir();
+    let cache = paths::cache_dir();
+    let mut cache_size = dir_size(&cache);
+    if !staging.starts_with(&cache) {
+        cache_size += dir_size(&staging);
+    }
+
+    let (pending_updates, checked_at) = match manager.pending_updates(&db) {
+        Ok(updates) => (Some(updates.len()), None),
+        Err(e) => {
+            log::debug!("Counting pending updates failed: {}", e);
+            match UpdateState::load() {
+                Some(state) => (Some(state.updates.len()), Some(state.checked_at)),
+                None => (None, None),
+            }
+        }
+    };
+
+    Stats {
+        installed: db.packages.len(),
+        held: db.held.iter().filter(|name| db.contains(name)).count(),
+        aur: db
+            .packages
+            .values()
+            .filter(|p| p.origin.as_deref() == Some(aur::ORIGIN))
+            .count(),
+        installed_size: sizes.iter().flatten().sum(),
+        unknown_size: sizes.iter().filter(|size| size.is_none()).count(),
+        cache_size,
+        last_refresh: github::last_refresh(),
+        last_upgrade: history::load(manager.state_dir())
+            .iter()
+            .filter(|entry| entry.action == Action::Upgrade && entry.success)
+            .map(|entry| entry.timestamp)
+            .max(),
+        pending_updates,
+        checked_at,
+        recent: recent_changes(manager),
+    }
+}
+
+// Packages named in the latest commits to the package directory of the
+// highest priority repository. Commit messages name what they change, when a
+// message names both "foo" and "foo-themes" only the longer one counts.
+fn recent_changes(manager: &Manager) -> Vec<RecentChange> {
+    let Ok(files) = manager.repo.packages() else {
+        return Vec::new();
+    };
+    let names: Vec<String> = files
+        .iter()
+        .filter_map(|file| RepoPackage::from_file(file))
+        .map(|package| package.name)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let Some(first) = files.first() else {
+        return Vec::new();
+    };
+    let commits = match manager.repo.commits(first, None, None) {
+        Ok(commits) => commits,
+        Err(e) => {
+            log::debug!("Fetching recent commits failed: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let mut seen = HashSet::new();
+    let mut recent = Vec::new();
+    for commit in &commits {
+        let mentioned: Vec<&String> = names
+            .iter()
+            .filter(|name| changelog::mentions(commit, name))
+            .collect();
+        let mut changed: Vec<&String> = mentioned
+            .iter()
+            .filter(|name| {
+                !mentioned
+                    .iter()
+                    .any(|other| other.len() > name.len() && other.contains(name.as_str()))
+            })
+            .copied()
+            .collect();
+        changed.sort();
+        for name in changed {
+            if seen.insert(name.clone()) {
+                recent.push(RecentChange {
+                    name: name.clone(),
+                    date: commit.date.clone(),
+                });
+            }
+        }
+        if recent.len() >= RECENT {
+            break;
+        }
+    }
+    recent.truncate(RECENT);
+    recent
+}
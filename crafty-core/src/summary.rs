@@ -0,0 +1,73 @@
+//! Totals across the transactions of one crafty run, reported at the end.
+
+use crate::manager::{InstallReport, PackageChange, RemoveReport};
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Default)]
+pub struct Summary {
+    pub changes: Vec<PackageChange>,
+    /// Packages left alone on purpose, e.g. held ones
+    pub skipped: Vec<Skipped>,
+    pub failed: Vec<Failure>,
+    pub warnings: Vec<String>,
+    pub downloaded_bytes: u64,
+    pub elapsed_secs: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Skipped {
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Failure {
+    pub name: String,
+    pub error: String,
+}
+
+impl Summary {
+    pub const fn new() -> Self {
+        Self {
+            changes: Vec::new(),
+            skipped: Vec::new(),
+            failed: Vec::new(),
+            warnings: Vec::new(),
+            downloaded_bytes: 0,
+            elapsed_secs: 0.0,
+        }
+    }
+
+    pub fn add_install(&mut self, report: &InstallReport) {
+        self.changes.extend(report.packages.iter().cloned());
+        self.warnings.extend(report.warnings.iter().cloned());
+    }
+
+    pub fn add_remove(&mut self, report: &RemoveReport) {
+        self.changes.extend(report.removed.iter().cloned());
+        self.warnings.extend(report.warnings.iter().cloned());
+    }
+
+    pub fn skip(&mut self, name: &str, reason: &str) {
+        self.skipped.push(Skipped {
+            name: name.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    pub fn fail(&mut self, name: &str, error: &impl ToString) {
+        self.failed.push(Failure {
+            name: name.to_string(),
+            error: error.to_string(),
+        });
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Nothing happened worth summarizing.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.skipped.is_empty() && self.failed.is_empty()
+    }
+}
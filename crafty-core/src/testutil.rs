@@ -0,0 +1,83 @@
+//! Fixtures shared by the unit tests: scratch directories and package archives.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A directory under the system temp dir, removed again on drop.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "crafty-test-{}-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed),
+            name
+        ));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Build `<name>-<version>-any.pkg.tar.gz` in `dir` with a .PKGINFO listing
+/// `depends` and the given (path, contents) files, returning its path.
+pub fn package(
+    dir: &Path,
+    name: &str,
+    version: &str,
+    depends: &[&str],
+    files: &[(&str, &str)],
+) -> PathBuf {
+    let root = TempDir::new(name);
+    let mut pkginfo = format!(
+        "pkgname = {}\npkgver = {}\npkgdesc = {} for the tests\narch = any\nsize = 1024\n",
+        name, version, name
+    );
+    for dep in depends {
+        pkginfo.push_str(&format!("depend = {}\n", dep));
+    }
+    fs::write(root.join(".PKGINFO"), pkginfo).unwrap();
+    let mut entries = vec![String::from(".PKGINFO")];
+    for (path, contents) in files {
+        let file = root.join(path);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, contents).unwrap();
+        entries.push(path.to_string());
+    }
+
+    fs::create_dir_all(dir).unwrap();
+    let archive = dir.join(format!("{}-{}-any.pkg.tar.gz", name, version));
+    let status = Command::new("bsdtar")
+        .arg("-czf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(root.path())
+        .args(&entries)
+        .status()
+        .unwrap();
+    assert!(
+        status.success(),
+        "bsdtar failed to build {}",
+        archive.display()
+    );
+    archive
+}
@@ -0,0 +1,45 @@
+//! ArchCraft theme bundles.
+//!
+//! A theme is spread over several packages, one per component, that share the
+//! theme name after a `-theme-` (or `-themes-`) segment, e.g.
+//! `archcraft-gtk-theme-nordic` and `archcraft-openbox-theme-nordic` both
+//! belong to the `nordic` theme.
+
+use regex::Regex;
+use std::{collections::BTreeMap, sync::OnceLock};
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    /// Package names, sorted
+    pub packages: Vec<String>,
+}
+
+/// The theme `package` belongs to, if any.
+pub fn theme_of(package: &str) -> Option<&str> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"^(?:archcraft-)?.+?-themes?-(?P<theme>.+)$").unwrap());
+    re.captures(package)
+        .and_then(|caps| caps.name("theme"))
+        .map(|m| m.as_str())
+}
+
+/// Group package names into themes, sorted by theme name.
+pub fn group<'a>(packages: impl IntoIterator<Item = &'a str>) -> Vec<Theme> {
+    let mut themes: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for package in packages {
+        if let Some(theme) = theme_of(package) {
+            themes.entry(theme).or_default().push(package.to_string());
+        }
+    }
+    themes
+        .into_iter()
+        .map(|(name, mut packages)| {
+            packages.sort();
+            Theme {
+                name: name.to_string(),
+                packages,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,245 @@
+//! Why a pacman transaction failed, recovered from its diagnostics.
+//!
+//! pacman only reports failure through its exit code, the reasons are in the
+//! messages it prints to stderr. Those are parsed into a [`TransactionError`]
+//! so callers can tell a missing dependency from a file conflict.
+
+use regex::Regex;
+use std::{fmt, path::PathBuf, sync::OnceLock};
+
+#[derive(Debug, Clone, Default)]
+pub struct TransactionError {
+    /// What pacman was asked to do, e.g. "install"
+    pub operation: String,
+    /// Dependencies that can't be satisfied, with the package requiring them when known
+    pub missing: Vec<(String, Option<String>)>,
+    /// (package, path) for files that already exist on the filesystem
+    pub file_conflicts: Vec<(String, PathBuf)>,
+    /// Pairs of packages that can't be installed together
+    pub package_conflicts: Vec<(String, String)>,
+    /// Other `error:` lines, verbatim
+    pub errors: Vec<String>,
+}
+
+struct Patterns {
+    unsatisfied: Regex,
+    breaks: Regex,
+    target: Regex,
+    exists: Regex,
+    conflict: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        unsatisfied: Regex::new(r"unable to satisfy dependency '([^']+)' required by (\S+)")
+            .unwrap(),
+        breaks: Regex::new(r"removing \S+ breaks dependency '([^']+)' required by (\S+)").unwrap(),
+        target: Regex::new(r"^error: target not found: (\S+)").unwrap(),
+        exists: Regex::new(r"^(\S+): (/.*?) exists in filesystem").unwrap(),
+        conflict: Regex::new(r"^:: (\S+) and (\S+) are in conflict").unwrap(),
+    })
+}
+
+impl TransactionError {
+    /// Collect the reasons from pacman's stderr.
+    pub fn parse(operation: &str, stderr: &str) -> Self {
+        let p = patterns();
+        let mut error = Self {
+            operation: operation.to_string(),
+            ..Self::default()
+        };
+        for line in stderr.lines().map(str::trim) {
+            if let Some(caps) = p
+                .unsatisfied
+                .captures(line)
+                .or_else(|| p.breaks.captures(line))
+            {
+                error
+                    .missing
+                    .push((caps[1].to_string(), Some(caps[2].to_string())));
+            } else if let Some(caps) = p.target.captures(line) {
+                error.missing.push((caps[1].to_string(), None));
+            } else if let Some(caps) = p.exists.captures(line) {
+                error
+                    .file_conflicts
+                    .push((caps[1].to_string(), PathBuf::from(&caps[2])));
+            } else if let Some(caps) = p.conflict.captures(line) {
+                // Reported once in the prompt and again in the final error
+                let pair = (caps[1].to_string(), caps[2].to_string());
+                if !error.package_conflicts.contains(&pair) {
+                    error.package_conflicts.push(pair);
+                }
+            } else if let Some(message) = line.strip_prefix("error: ") {
+                error.errors.push(message.to_string());
+            }
+        }
+        error
+    }
+
+    /// Whether any specific reason could be recovered.
+    pub fn is_diagnosed(&self) -> bool {
+        !self.missing.is_empty()
+            || !self.file_conflicts.is_empty()
+            || !self.package_conflicts.is_empty()
+    }
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut reasons = Vec::new();
+        for (dependency, required_by) in &self.missing {
+            reasons.push(match required_by {
+                Some(package) => format!("{} requires {}", package, dependency),
+                None => format!("{} not found", dependency),
+            });
+        }
+        for (package, path) in &self.file_conflicts {
+            reasons.push(format!("{} conflicts with {}", package, path.display()));
+        }
+        for (a, b) in &self.package_conflicts {
+            reasons.push(format!("{} conflicts with {}", a, b));
+        }
+        if !self.is_diagnosed() {
+            reasons.extend(self.errors.iter().cloned());
+        }
+        if reasons.is_empty() {
+            write!(f, "Pacman failed to {} package", self.operation)
+        } else {
+            write!(
+                f,
+                "Pacman failed to {}: {}",
+                self.operation,
+                reasons.join("; ")
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_unsatisfied_dependencies() {
+        let stderr = "\
+error: failed to prepare transaction (could not satisfy dependencies)
+:: unable to satisfy dependency 'archcraft-gtk-theme>=2.0' required by archcraft-openbox
+:: unable to satisfy dependency 'libfoo.so=1-64' required by archcraft-bar
+";
+        let error = TransactionError::parse("install", stderr);
+        assert_eq!(
+            error.missing,
+            vec![
+                (
+                    String::from("archcraft-gtk-theme>=2.0"),
+                    Some(String::from("archcraft-openbox"))
+                ),
+                (
+                    String::from("libfoo.so=1-64"),
+                    Some(String::from("archcraft-bar"))
+                ),
+            ]
+        );
+        assert!(error.is_diagnosed());
+        assert_eq!(
+            error.to_string(),
+            "Pacman failed to install: archcraft-openbox requires archcraft-gtk-theme>=2.0; \
+             archcraft-bar requires libfoo.so=1-64"
+        );
+    }
+
+    #[test]
+    fn parses_broken_dependencies_on_removal() {
+        let stderr = "\
+checking dependencies...
+error: failed to prepare transaction (could not satisfy dependencies)
+:: removing archcraft-gtk-theme breaks dependency 'archcraft-gtk-theme' required by archcraft-openbox
+";
+        let error = TransactionError::parse("remove", stderr);
+        assert_eq!(
+            error.missing,
+            vec![(
+                String::from("archcraft-gtk-theme"),
+                Some(String::from("archcraft-openbox"))
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_missing_targets() {
+        let error = TransactionError::parse("remove", "error: target not found: archcraft-nope\n");
+        assert_eq!(error.missing, vec![(String::from("archcraft-nope"), None)]);
+        assert_eq!(
+            error.to_string(),
+            "Pacman failed to remove: archcraft-nope not found"
+        );
+    }
+
+    #[test]
+    fn parses_file_conflicts() {
+        let stderr = "\
+(1/1) checking for file conflicts                  [######################] 100%
+error: failed to commit transaction (conflicting files)
+archcraft-bar: /usr/bin/bar exists in filesystem
+archcraft-bar: /usr/share/my files/bar.conf exists in filesystem (owned by bar)
+Errors occurred, no packages were upgraded.
+";
+        let error = TransactionError::parse("install", stderr);
+        assert_eq!(
+            error.file_conflicts,
+            vec![
+                (String::from("archcraft-bar"), PathBuf::from("/usr/bin/bar")),
+                (
+                    String::from("archcraft-bar"),
+                    PathBuf::from("/usr/share/my files/bar.conf")
+                ),
+            ]
+        );
+        assert!(error.missing.is_empty());
+    }
+
+    #[test]
+    fn parses_package_conflicts_once() {
+        let stderr = "\
+:: archcraft-bar-git and archcraft-bar are in conflict. Remove archcraft-bar? [y/N] 
+error: unresolvable package conflicts detected
+error: failed to prepare transaction (conflicting dependencies)
+:: archcraft-bar-git and archcraft-bar are in conflict
+";
+        let error = TransactionError::parse("install", stderr);
+        assert_eq!(
+            error.package_conflicts,
+            vec![(
+                String::from("archcraft-bar-git"),
+                String::from("archcraft-bar")
+            )]
+        );
+        assert_eq!(
+            error.to_string(),
+            "Pacman failed to install: archcraft-bar-git conflicts with archcraft-bar"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_error_lines() {
+        let stderr = "\
+error: could not open file /var/lib/pacman/local/ALPM_DB_VERSION: No such file or directory
+error: failed to init transaction (unable to lock database)
+error: could not lock database: File exists
+";
+        let error = TransactionError::parse("install", stderr);
+        assert!(!error.is_diagnosed());
+        assert_eq!(error.errors.len(), 3);
+        assert_eq!(
+            error.to_string(),
+            "Pacman failed to install: could not open file /var/lib/pacman/local/ALPM_DB_VERSION: \
+             No such file or directory; failed to init transaction (unable to lock database); \
+             could not lock database: File exists"
+        );
+        assert_eq!(
+            TransactionError::parse("install", "").to_string(),
+            "Pacman failed to install package"
+        );
+    }
+}
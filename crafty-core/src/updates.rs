@@ -0,0 +1,38 @@
+//! Pending updates for crafty-managed packages.
+
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+/// Result of the last `upgrade --check`, read by status bars and notifiers.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct UpdateState {
+    pub checked_at: u64,
+    pub updates: Vec<PendingUpdate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PendingUpdate {
+    pub name: String,
+    pub installed: String,
+    pub available: String,
+}
+
+impl UpdateState {
+    pub fn path() -> PathBuf {
+        paths::state_dir().join("updates.json")
+    }
+
+    pub fn load() -> Option<Self> {
+        let data = fs::read_to_string(Self::path()).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    pub fn save(&self) {
+        let path = Self::path();
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir).unwrap();
+        let data = serde_json::to_string_pretty(self).unwrap();
+        fs::write(path, data).unwrap();
+    }
+}
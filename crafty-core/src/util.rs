@@ -0,0 +1,270 @@
+//! Small helpers shared across modules.
+
+use regex::Regex;
+use std::{
+    cmp::Ordering,
+    ffi::CString,
+    fs::{self, File},
+    io::{self, Write},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Shell-style wildcard match supporting `*` and `?`.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut re = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).is_ok_and(|re| re.is_match(name))
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// UTC "YYYY-MM-DD HH:MM:SS", see Howard Hinnant's civil_from_days.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let secs = timestamp % 86_400;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// A systemd-style time span such as "6h", "1d 12h" or "90min"; a bare
+/// number counts seconds.
+pub fn parse_duration(span: &str) -> Option<Duration> {
+    let mut total: u64 = 0;
+    let mut rest = span.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let value: u64 = rest[..digits].parse().ok()?;
+        rest = rest[digits..].trim_start();
+        let unit_len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let seconds = match &rest[..unit_len] {
+            "" | "s" | "sec" | "second" | "seconds" => 1,
+            "m" | "min" | "minute" | "minutes" => 60,
+            "h" | "hr" | "hour" | "hours" => 3600,
+            "d" | "day" | "days" => 86_400,
+            "w" | "week" | "weeks" => 604_800,
+            _ => return None,
+        };
+        total = value
+            .checked_mul(seconds)
+            .and_then(|seconds| total.checked_add(seconds))?;
+        rest = rest[unit_len..].trim_start();
+    }
+    Some(Duration::from_secs(total))
+}
+
+/// Replace `path` with `data` through a temporary file in the same directory,
+/// so a crash mid-write leaves either the old or the new contents.
+pub fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut file = File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+/// Full path of `program` if it is in `$PATH`.
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Bytes available to unprivileged users on the filesystem holding `path`.
+pub fn available_space(path: &Path) -> Option<u64> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Total size of the files under `path`, without following symlinks; 0 when
+/// it doesn't exist.
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| dir_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Human-readable size with binary units, e.g. "1.5 MiB".
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// A size like curl's --limit-rate takes it: bytes with an optional K, M or
+/// G suffix in binary units, e.g. "500K" or "1.5M".
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let (number, multiplier) = match size.char_indices().last()? {
+        (i, 'k' | 'K') => (&size[..i], 1024.0),
+        (i, 'm' | 'M') => (&size[..i], 1024.0 * 1024.0),
+        (i, 'g' | 'G') => (&size[..i], 1024.0 * 1024.0 * 1024.0),
+        _ => (size, 1.0),
+    };
+    let value: f64 = number.trim().parse().ok()?;
+    (value.is_finite() && value >= 0.0).then_some((value * multiplier) as u64)
+}
+
+/// Compare strings with runs of digits ordered by value, so "1.10" sorts after
+/// "1.9". Close to pacman's vercmp without spawning a process per comparison.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(ca), Some(cb)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        let ordering = if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            let split = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            let (na, ra) = a.split_at(split(a));
+            let (nb, rb) = b.split_at(split(b));
+            let (na, nb) = (na.trim_start_matches('0'), nb.trim_start_matches('0'));
+            a = ra;
+            b = rb;
+            na.len().cmp(&nb.len()).then_with(|| na.cmp(nb))
+        } else {
+            a = &a[ca.len_utf8()..];
+            b = &b[cb.len_utf8()..];
+            ca.cmp(&cb)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match_whole_names() {
+        assert!(glob_match("archcraft-*", "archcraft-openbox"));
+        assert!(glob_match("archcraft-?ox", "archcraft-box"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*-gtk-*", "archcraft-gtk-theme-arc"));
+        assert!(!glob_match("archcraft-*", "xarchcraft-openbox"));
+        assert!(!glob_match("archcraft-?ox", "archcraft-fox-2"));
+        // Regex syntax in the pattern is taken literally
+        assert!(glob_match("lib.so+", "lib.so+"));
+        assert!(!glob_match("lib.so+", "libxsoo"));
+    }
+
+    #[test]
+    fn parses_durations() {
+        let secs = |span| parse_duration(span).map(|d| d.as_secs());
+        assert_eq!(secs("90"), Some(90));
+        assert_eq!(secs("6h"), Some(21_600));
+        assert_eq!(secs("1d 12h"), Some(129_600));
+        assert_eq!(secs("1d12h30min"), Some(131_400));
+        assert_eq!(secs(" 2 weeks "), Some(1_209_600));
+        assert_eq!(secs("90min"), Some(5_400));
+        assert_eq!(secs(""), None);
+        assert_eq!(secs("h"), None);
+        assert_eq!(secs("5 fortnights"), None);
+        assert_eq!(secs("-5m"), None);
+    }
+
+    #[test]
+    fn rejects_durations_that_overflow() {
+        assert_eq!(parse_duration("18446744073709551615w"), None);
+        assert_eq!(parse_duration("18446744073709551615 1"), None);
+        assert_eq!(parse_duration("99999999999999999999"), None);
+        assert_eq!(
+            parse_duration("18446744073709551615").map(|d| d.as_secs()),
+            Some(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("500K"), Some(512_000));
+        assert_eq!(parse_size("500k"), Some(512_000));
+        assert_eq!(parse_size("1.5M"), Some(1_572_864));
+        assert_eq!(parse_size("2G"), Some(2_147_483_648));
+        assert_eq!(parse_size(" 1 M "), Some(1_048_576));
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size("-1K"), None);
+        assert_eq!(parse_size("fast"), None);
+        assert_eq!(parse_size("infK"), None);
+    }
+
+    #[test]
+    fn compares_digit_runs_by_value() {
+        assert_eq!(natural_cmp("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(natural_cmp("1.9", "1.10"), Ordering::Less);
+        assert_eq!(natural_cmp("2.0-1", "2.0-1"), Ordering::Equal);
+        assert_eq!(natural_cmp("1.01", "1.1"), Ordering::Equal);
+        assert_eq!(natural_cmp("1.0", "1.0.1"), Ordering::Less);
+        assert_eq!(natural_cmp("alpha", "beta"), Ordering::Less);
+        assert_eq!(
+            natural_cmp("archcraft-polybar-12", "archcraft-polybar-2"),
+            Ordering::Greater
+        );
+        assert_eq!(natural_cmp("", "1"), Ordering::Less);
+    }
+}
@@ -0,0 +1,137 @@
+//! Integrity of the managed packages, behind `crafty verify`: crafty's
+//! database against pacman's, the installed files against `pacman -Qkk` and
+//! the cached archives against the checksums recorded at install time.
+
+use crate::{
+    Error, LocalPackages, PackageDb, Result, archive, pacman::FileProblem, resolver::StagedPackage,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// What verifying one managed package found.
+#[derive(Debug, Clone)]
+pub struct PackageCheck {
+    pub name: String,
+    /// Version in crafty's database, unknown for some adopted packages
+    pub recorded: Option<String>,
+    /// Version pacman has installed, `None` when it was removed behind crafty's back
+    pub installed: Option<String>,
+    /// Files pacman found missing or altered
+    pub files: Vec<FileProblem>,
+    pub archive: ArchiveState,
+}
+
+/// The archive a package was installed from, looked up in the download directory.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArchiveState {
+    /// No file or checksum was recorded, e.g. for adopted packages
+    Unrecorded,
+    /// Not in the download directory
+    Absent,
+    /// Matches the recorded checksum, so it can be reinstalled offline
+    Intact(PathBuf),
+    /// Differs from the recorded checksum
+    Corrupt(PathBuf),
+}
+
+/// Broken packages staged for reinstalling, see [`crate::Manager::stage_reinstall`].
+#[derive(Debug, Default)]
+pub struct Reinstall {
+    pub staged: Vec<StagedPackage>,
+    /// Packages that couldn't be fetched, with the reason
+    pub failed: Vec<(String, Error)>,
+}
+
+impl PackageCheck {
+    pub fn missing(&self) -> impl Iterator<Item = &FileProblem> {
+        self.files.iter().filter(|f| f.is_missing())
+    }
+
+    pub fn modified(&self) -> impl Iterator<Item = &FileProblem> {
+        self.files.iter().filter(|f| !f.is_missing())
+    }
+
+    /// pacman has another version installed than crafty recorded.
+    pub fn version_mismatch(&self) -> bool {
+        match (&self.recorded, &self.installed) {
+            (Some(recorded), Some(installed)) => recorded != installed,
+            _ => false,
+        }
+    }
+
+    /// In the database but no longer installed; `crafty db sync` drops it.
+    pub fn is_stale(&self) -> bool {
+        self.installed.is_none()
+    }
+
+    /// Installed, but not as crafty installed it; reinstalling repairs it.
+    pub fn is_broken(&self) -> bool {
+        !self.is_stale() && (self.version_mismatch() || !self.files.is_empty())
+    }
+
+    pub fn is_ok(&self) -> bool {
+        !self.is_stale() && !self.is_broken() && !matches!(self.archive, ArchiveState::Corrupt(_))
+    }
+}
+
+/// Check the managed packages `names` against what `local` has installed,
+/// looking for their archives in `staging`.
+pub fn check(
+    local: &dyn LocalPackages,
+    db: &PackageDb,
+    names: &[&String],
+    staging: &Path,
+) -> Result<Vec<PackageCheck>> {
+    let installed: HashMap<String, String> = local
+        .installed_package_versions(false)
+        .ok_or_else(|| {
+            Error::Pacman(String::from(
+                "Failed to query installed packages from pacman",
+            ))
+        })?
+        .into_iter()
+        .collect();
+    // pacman stops at names it doesn't know, so only installed ones are checked
+    let present: Vec<&str> = names
+        .iter()
+        .filter(|name| installed.contains_key(**name))
+        .map(|name| name.as_str())
+        .collect();
+    let mut problems: HashMap<String, Vec<FileProblem>> = HashMap::new();
+    for problem in local
+        .check_files(&present)
+        .ok_or_else(|| Error::Pacman(String::from("Failed to run pacman -Qkk")))?
+    {
+        problems
+            .entry(problem.package.clone())
+            .or_default()
+            .push(problem);
+    }
+
+    Ok(names
+        .iter()
+        .filter_map(|name| {
+            let record = db.get(name)?;
+            let archive = match (&record.file, &record.sha256) {
+                (Some(file), Some(sha256)) => {
+                    let path = staging.join(file);
+                    match archive::sha256(&path) {
+                        None => ArchiveState::Absent,
+                        Some(actual) if actual == *sha256 => ArchiveState::Intact(path),
+                        Some(_) => ArchiveState::Corrupt(path),
+                    }
+                }
+                _ => ArchiveState::Unrecorded,
+            };
+            Some(PackageCheck {
+                name: name.to_string(),
+                recorded: record.full_version(),
+                installed: installed.get(*name).cloned(),
+                files: problems.remove(*name).unwrap_or_default(),
+                archive,
+            })
+        })
+        .collect())
+}
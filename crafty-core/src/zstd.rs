@@ -0,0 +1,1141 @@
+//! A Zstandard decoder (RFC 8878), for handing pacman older than 5.2 plain
+//! tar archives without depending on an external decompressor.
+//!
+//! Only what package archives use is supported: frames without a dictionary,
+//! optionally followed by more frames or skippable frames. The content
+//! checksum is verified when a frame carries one. Decoding streams: only the
+//! window a frame's matches can reach back into is kept in memory.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+};
+
+const MAGIC: u32 = 0xFD2F_B528;
+const MAX_BLOCK_SIZE: usize = 128 * 1024;
+// Decoded output past the window is written out in batches of at least this
+const FLUSH_SIZE: usize = 1024 * 1024;
+
+// Predefined distributions of the sequence codes, with their accuracy logs
+const LITERAL_LENGTH_DEFAULT: (&[i16], u8) = (
+    &[
+        4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 2, 1, 1, 1,
+        1, 1, -1, -1, -1, -1,
+    ],
+    6,
+);
+const MATCH_LENGTH_DEFAULT: (&[i16], u8) = (
+    &[
+        1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1, -1, -1,
+    ],
+    6,
+);
+const OFFSET_DEFAULT: (&[i16], u8) = (
+    &[
+        1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1,
+    ],
+    5,
+);
+
+// (baseline, extra bits) of the literal length codes from 16 on; lower codes are the length
+const LITERAL_LENGTH_CODES: [(u32, u8); 20] = [
+    (16, 1),
+    (18, 1),
+    (20, 1),
+    (22, 1),
+    (24, 2),
+    (28, 2),
+    (32, 3),
+    (40, 3),
+    (48, 4),
+    (64, 6),
+    (128, 7),
+    (256, 8),
+    (512, 9),
+    (1024, 10),
+    (2048, 11),
+    (4096, 12),
+    (8192, 13),
+    (16384, 14),
+    (32768, 15),
+    (65536, 16),
+];
+
+// (baseline, extra bits) of the match length codes from 32 on; lower codes are the length - 3
+const MATCH_LENGTH_CODES: [(u32, u8); 21] = [
+    (35, 1),
+    (37, 1),
+    (39, 1),
+    (41, 1),
+    (43, 2),
+    (47, 2),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 5),
+    (131, 7),
+    (259, 8),
+    (515, 9),
+    (1027, 10),
+    (2051, 11),
+    (4099, 12),
+    (8195, 13),
+    (16387, 14),
+    (32771, 15),
+    (65539, 16),
+];
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("zstd: {}", msg))
+}
+
+/// Decompress the zstd file `from` into `to`, which is removed again when
+/// `from` turns out to be damaged.
+pub fn decompress_file(from: &Path, to: &Path) -> io::Result<()> {
+    let input = BufReader::new(File::open(from)?);
+    let mut output = BufWriter::new(File::create(to)?);
+    let result = decompress_to(input, &mut output).and_then(|()| output.flush());
+    if result.is_err() {
+        let _ = fs::remove_file(to);
+    }
+    result
+}
+
+/// Decompress every frame in `data`.
+pub fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    decompress_to(data, &mut out)?;
+    Ok(out)
+}
+
+/// Decompress every frame read from `input`, writing the result to `output`
+/// as it is decoded.
+pub fn decompress_to<R: BufRead, W: Write>(input: R, output: &mut W) -> io::Result<()> {
+    let mut input = Stream { inner: input };
+    while !input.is_empty()? {
+        let magic = input.u32()?;
+        if magic & 0xFFFF_FFF0 == 0x184D_2A50 {
+            let size = input.u32()?;
+            input.skip(u64::from(size))?;
+            continue;
+        }
+        if magic != MAGIC {
+            return Err(invalid("bad magic number"));
+        }
+        Frame::default().decode(&mut input, output)?;
+    }
+    Ok(())
+}
+
+// The compressed stream, read a frame header or block at a time
+struct Stream<R> {
+    inner: R,
+}
+
+impl<R: BufRead> Stream<R> {
+    fn is_empty(&mut self) -> io::Result<bool> {
+        Ok(self.inner.fill_buf()?.is_empty())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        self.inner.read_exact(buf).map_err(|e| match e.kind() {
+            ErrorKind::UnexpectedEof => invalid("unexpected end of input"),
+            _ => e,
+        })
+    }
+
+    fn take(&mut self, n: usize) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; n];
+        self.read(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn skip(&mut self, n: u64) -> io::Result<()> {
+        let skipped = io::copy(&mut (&mut self.inner).take(n), &mut io::sink())?;
+        if skipped < n {
+            return Err(invalid("unexpected end of input"));
+        }
+        Ok(())
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        let mut byte = [0];
+        self.read(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    // Little-endian unsigned integer of `n` bytes
+    fn uint(&mut self, n: usize) -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        self.read(&mut bytes[..n])?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(self.uint(4)? as u32)
+    }
+}
+
+// A block's compressed data, read from the front
+struct Input<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if n > self.data.len() {
+            return Err(invalid("unexpected end of input"));
+        }
+        let (head, rest) = self.data.split_at(n);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    // Little-endian unsigned integer of `n` bytes
+    fn uint(&mut self, n: usize) -> io::Result<u64> {
+        Ok(self
+            .take(n)?
+            .iter()
+            .rev()
+            .fold(0, |value, byte| value << 8 | u64::from(*byte)))
+    }
+}
+
+// State carried from block to block within a frame
+struct Frame {
+    huffman: Option<HuffmanTable>,
+    literal_lengths: Option<FseTable>,
+    offsets: Option<FseTable>,
+    match_lengths: Option<FseTable>,
+    repeat_offsets: [usize; 3],
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self {
+            huffman: None,
+            literal_lengths: None,
+            offsets: None,
+            match_lengths: None,
+            repeat_offsets: [1, 4, 8],
+        }
+    }
+}
+
+impl Frame {
+    // Decode one frame after its magic number, writing it to `output`
+    fn decode<R: BufRead, W: Write>(
+        &mut self,
+        input: &mut Stream<R>,
+        output: &mut W,
+    ) -> io::Result<()> {
+        let descriptor = input.u8()?;
+        let content_size_flag = descriptor >> 6;
+        let single_segment = descriptor & 0x20 != 0;
+        let has_checksum = descriptor & 0x04 != 0;
+        if descriptor & 0x08 != 0 {
+            return Err(invalid("reserved bit set in the frame header"));
+        }
+        let window_descriptor = if single_segment {
+            None
+        } else {
+            Some(input.u8()?)
+        };
+        let dictionary_id = match descriptor & 0x03 {
+            0 => 0,
+            1 => input.uint(1)?,
+            2 => input.uint(2)?,
+            _ => input.uint(4)?,
+        };
+        if dictionary_id != 0 {
+            return Err(invalid("dictionaries are not supported"));
+        }
+        let content_size = match content_size_flag {
+            0 if single_segment => input.uint(1)?,
+            0 => 0,
+            1 => input.uint(2)? + 256,
+            2 => input.uint(4)?,
+            _ => input.uint(8)?,
+        };
+        // A single segment frame is its own window
+        let window = match window_descriptor {
+            Some(byte) => {
+                let base = 1u64 << (10 + (byte >> 3));
+                base + base / 8 * u64::from(byte & 0x07)
+            }
+            None => content_size,
+        };
+        let window = usize::try_from(window).unwrap_or(usize::MAX);
+
+        // What was decoded and not written yet, at least the window once
+        // there is that much
+        let mut out = Vec::new();
+        let mut hash = Xxh64::new(0);
+        loop {
+            let header = input.uint(3)? as u32;
+            let last = header & 1 != 0;
+            let size = (header >> 3) as usize;
+            if size > MAX_BLOCK_SIZE {
+                return Err(invalid("block too large"));
+            }
+            match (header >> 1) & 0x03 {
+                0 => out.extend_from_slice(&input.take(size)?),
+                1 => {
+                    let byte = input.u8()?;
+                    out.resize(out.len() + size, byte);
+                }
+                2 => self.block(&input.take(size)?, &mut out)?,
+                _ => return Err(invalid("reserved block type")),
+            }
+            if out.len() >= window.saturating_add(window.max(FLUSH_SIZE)) {
+                let n = out.len() - window;
+                hash.update(&out[..n]);
+                output.write_all(&out[..n])?;
+                out.drain(..n);
+            }
+            if last {
+                break;
+            }
+        }
+        hash.update(&out);
+        output.write_all(&out)?;
+
+        if has_checksum {
+            let expected = input.u32()?;
+            if hash.finish() as u32 != expected {
+                return Err(invalid("checksum mismatch"));
+            }
+        }
+        Ok(())
+    }
+
+    // A compressed block: literals, then the sequences putting them together
+    // with matches. Matches may reach back as far as the window, which `out`
+    // holds the end of.
+    fn block(&mut self, data: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        let mut input = Input::new(data);
+        let literals = self.literals(&mut input)?;
+        let sequences = self.sequences(&mut input)?;
+
+        let mut literals = literals.as_slice();
+        for sequence in sequences {
+            let (now, rest) = literals
+                .split_at_checked(sequence.literal_length)
+                .ok_or_else(|| invalid("literal length past the literals"))?;
+            out.extend_from_slice(now);
+            literals = rest;
+
+            let offset = self.offset(sequence.offset_value, sequence.literal_length)?;
+            if offset > out.len() {
+                return Err(invalid("match offset before the start of the window"));
+            }
+            let from = out.len() - offset;
+            if offset >= sequence.match_length {
+                out.extend_from_within(from..from + sequence.match_length);
+            } else {
+                // Overlapping: the match repeats bytes it is writing itself
+                for i in 0..sequence.match_length {
+                    out.push(out[from + i]);
+                }
+            }
+        }
+        out.extend_from_slice(literals);
+        Ok(())
+    }
+
+    // Resolve an offset value into a distance, keeping the repeat offsets up to date
+    fn offset(&mut self, value: u32, literal_length: usize) -> io::Result<usize> {
+        let reps = &mut self.repeat_offsets;
+        if value > 3 {
+            let offset = value as usize - 3;
+            *reps = [offset, reps[0], reps[1]];
+            return Ok(offset);
+        }
+        // Without literals the repeat offsets are shifted by one
+        let index = if literal_length == 0 {
+            value
+        } else {
+            value - 1
+        };
+        let offset = match index {
+            0 => reps[0],
+            1 => {
+                reps.swap(0, 1);
+                reps[0]
+            }
+            2 => {
+                let offset = reps[2];
+                *reps = [offset, reps[0], reps[1]];
+                offset
+            }
+            _ => {
+                let offset = reps[0]
+                    .checked_sub(1)
+                    .filter(|o| *o > 0)
+                    .ok_or_else(|| invalid("zero match offset"))?;
+                *reps = [offset, reps[0], reps[1]];
+                offset
+            }
+        };
+        Ok(offset)
+    }
+
+    fn literals(&mut self, input: &mut Input) -> io::Result<Vec<u8>> {
+        let first = input.u8()?;
+        let kind = first & 0x03;
+        let size_format = (first >> 2) & 0x03;
+        if kind < 2 {
+            let size = match size_format {
+                0 | 2 => usize::from(first >> 3),
+                1 => usize::from(first >> 4) | usize::from(input.u8()?) << 4,
+                _ => usize::from(first >> 4) | (input.uint(2)? as usize) << 4,
+            };
+            return Ok(if kind == 0 {
+                input.take(size)?.to_vec()
+            } else {
+                vec![input.u8()?; size]
+            });
+        }
+
+        let (streams, rest_bytes, bits) = match size_format {
+            0 => (1, 2, 10),
+            1 => (4, 2, 10),
+            2 => (4, 3, 14),
+            _ => (4, 4, 18),
+        };
+        let header = u64::from(first) | input.uint(rest_bytes)? << 8;
+        let mask = (1 << bits) - 1;
+        let regenerated = ((header >> 4) & mask) as usize;
+        let compressed = ((header >> (4 + bits)) & mask) as usize;
+        let mut data = Input::new(input.take(compressed)?);
+        if kind == 2 {
+            self.huffman = Some(HuffmanTable::read(&mut data)?);
+        }
+        let table = self
+            .huffman
+            .as_ref()
+            .ok_or_else(|| invalid("repeated Huffman table without a previous one"))?;
+
+        let mut literals = Vec::with_capacity(regenerated);
+        if streams == 1 {
+            table.decode(data.data, regenerated, &mut literals)?;
+        } else {
+            let sizes = [data.uint(2)?, data.uint(2)?, data.uint(2)?].map(|s| s as usize);
+            let per_stream = regenerated.div_ceil(4);
+            for size in sizes {
+                table.decode(data.take(size)?, per_stream, &mut literals)?;
+            }
+            let remaining = regenerated
+                .checked_sub(3 * per_stream)
+                .ok_or_else(|| invalid("too few literals for four streams"))?;
+            table.decode(data.data, remaining, &mut literals)?;
+        }
+        Ok(literals)
+    }
+
+    fn sequences(&mut self, input: &mut Input) -> io::Result<Vec<Sequence>> {
+        let first = input.u8()?;
+        let count = match first {
+            0 => return Ok(Vec::new()),
+            1..128 => usize::from(first),
+            128..255 => usize::from(first - 128) << 8 | usize::from(input.u8()?),
+            255 => input.uint(2)? as usize + 0x7F00,
+        };
+        let modes = input.u8()?;
+        if modes & 0x03 != 0 {
+            return Err(invalid("reserved bits set in the compression modes"));
+        }
+        read_table(
+            &mut self.literal_lengths,
+            modes >> 6,
+            LITERAL_LENGTH_DEFAULT,
+            35,
+            9,
+            input,
+        )?;
+        read_table(
+            &mut self.offsets,
+            (modes >> 4) & 0x03,
+            OFFSET_DEFAULT,
+            31,
+            8,
+            input,
+        )?;
+        read_table(
+            &mut self.match_lengths,
+            (modes >> 2) & 0x03,
+            MATCH_LENGTH_DEFAULT,
+            52,
+            9,
+            input,
+        )?;
+        let (Some(literal_lengths), Some(offsets), Some(match_lengths)) =
+            (&self.literal_lengths, &self.offsets, &self.match_lengths)
+        else {
+            unreachable!("read_table leaves a table behind");
+        };
+
+        let mut bits = BackwardBits::new(input.data)?;
+        let mut ll_state = literal_lengths.init(&mut bits);
+        let mut of_state = offsets.init(&mut bits);
+        let mut ml_state = match_lengths.init(&mut bits);
+        let mut sequences = Vec::with_capacity(count);
+        for i in 0..count {
+            let of_code = offsets.symbol(of_state);
+            let ml_code = match_lengths.symbol(ml_state);
+            let ll_code = literal_lengths.symbol(ll_state);
+            // Codes an RLE table repeats aren't checked when it is read
+            if ll_code > 35 || ml_code > 52 || of_code > 31 {
+                return Err(invalid("sequence code out of range"));
+            }
+            let offset_value = (1u64 << of_code) + bits.read(u32::from(of_code));
+            let (ml_base, ml_bits) = match ml_code {
+                0..32 => (u32::from(ml_code) + 3, 0),
+                _ => MATCH_LENGTH_CODES[usize::from(ml_code) - 32],
+            };
+            let match_length = ml_base as usize + bits.read(u32::from(ml_bits)) as usize;
+            let (ll_base, ll_bits) = match ll_code {
+                0..16 => (u32::from(ll_code), 0),
+                _ => LITERAL_LENGTH_CODES[usize::from(ll_code) - 16],
+            };
+            let literal_length = ll_base as usize + bits.read(u32::from(ll_bits)) as usize;
+            sequences.push(Sequence {
+                literal_length,
+                match_length,
+                offset_value: u32::try_from(offset_value)
+                    .map_err(|_| invalid("offset out of range"))?,
+            });
+            if i + 1 < count {
+                ll_state = literal_lengths.update(ll_state, &mut bits);
+                ml_state = match_lengths.update(ml_state, &mut bits);
+                of_state = offsets.update(of_state, &mut bits);
+            }
+        }
+        if bits.pos != 0 {
+            return Err(invalid("sequence bitstream not fully consumed"));
+        }
+        Ok(sequences)
+    }
+}
+
+struct Sequence {
+    literal_length: usize,
+    match_length: usize,
+    offset_value: u32,
+}
+
+// Set `table` for the compression `mode` of one sequence code
+fn read_table(
+    table: &mut Option<FseTable>,
+    mode: u8,
+    default: (&[i16], u8),
+    max_symbol: u8,
+    max_log: u8,
+    input: &mut Input,
+) -> io::Result<()> {
+    match mode {
+        0 => *table = Some(FseTable::build(default.0, default.1)?),
+        1 => *table = Some(FseTable::rle(input.u8()?)),
+        2 => *table = Some(FseTable::read(input, max_symbol, max_log)?),
+        _ if table.is_none() => return Err(invalid("repeated table without a previous one")),
+        _ => {}
+    }
+    Ok(())
+}
+
+// Bits read from the end of a buffer towards its start, starting below the
+// highest set bit of the last byte. Reads past the start yield zeros.
+struct BackwardBits<'a> {
+    data: &'a [u8],
+    // Bits left before the start, negative once reads went past it
+    pos: isize,
+}
+
+impl<'a> BackwardBits<'a> {
+    fn new(data: &'a [u8]) -> io::Result<Self> {
+        let last = *data.last().ok_or_else(|| invalid("empty bitstream"))?;
+        if last == 0 {
+            return Err(invalid("bitstream without an end marker"));
+        }
+        let pos = data.len() as isize * 8 - last.leading_zeros() as isize - 1;
+        Ok(Self { data, pos })
+    }
+
+    // `n` bits, at most 56, from `start` on in the little-endian bit order
+    fn bits_at(&self, start: usize, n: u32) -> u64 {
+        let byte = start / 8;
+        let mut word = [0; 8];
+        let end = self.data.len().min(byte + 8);
+        if byte < end {
+            word[..end - byte].copy_from_slice(&self.data[byte..end]);
+        }
+        (u64::from_le_bytes(word) >> (start % 8)) & ((1 << n) - 1)
+    }
+
+    fn peek(&self, n: u32) -> u64 {
+        if n == 0 {
+            return 0;
+        }
+        let start = self.pos - n as isize;
+        if start >= 0 {
+            self.bits_at(start as usize, n)
+        } else if self.pos > 0 {
+            self.bits_at(0, self.pos as u32) << -start
+        } else {
+            0
+        }
+    }
+
+    fn read(&mut self, n: u32) -> u64 {
+        let value = self.peek(n);
+        self.pos -= n as isize;
+        value
+    }
+
+    fn overflowed(&self) -> bool {
+        self.pos < 0
+    }
+}
+
+#[derive(Clone, Copy)]
+struct FseEntry {
+    symbol: u8,
+    bits: u8,
+    baseline: u16,
+}
+
+// A finite state entropy decoding table
+struct FseTable {
+    log: u8,
+    entries: Vec<FseEntry>,
+}
+
+impl FseTable {
+    // A table always decoding `symbol` without reading any bits
+    fn rle(symbol: u8) -> Self {
+        Self {
+            log: 0,
+            entries: vec![FseEntry {
+                symbol,
+                bits: 0,
+                baseline: 0,
+            }],
+        }
+    }
+
+    // The table description at the start of `input`
+    fn read(input: &mut Input, max_symbol: u8, max_log: u8) -> io::Result<Self> {
+        let (counts, log, used) = read_counts(input.data, max_symbol, max_log)?;
+        input.take(used)?;
+        Self::build(&counts, log)
+    }
+
+    // Spread the symbols of the normalized `counts` over the table
+    fn build(counts: &[i16], log: u8) -> io::Result<Self> {
+        let size = 1usize << log;
+        let mut entries = vec![
+            FseEntry {
+                symbol: 0,
+                bits: 0,
+                baseline: 0
+            };
+            size
+        ];
+        let mut next = vec![0u32; counts.len()];
+        let mut high = size - 1;
+        for (symbol, &count) in counts.iter().enumerate() {
+            if count == -1 {
+                entries[high].symbol = symbol as u8;
+                high = high.wrapping_sub(1);
+                next[symbol] = 1;
+            } else {
+                next[symbol] = count.max(0) as u32;
+            }
+        }
+        let step = (size >> 1) + (size >> 3) + 3;
+        let mut position = 0;
+        for (symbol, &count) in counts.iter().enumerate() {
+            for _ in 0..count.max(0) {
+                entries[position].symbol = symbol as u8;
+                loop {
+                    position = (position + step) & (size - 1);
+                    if position <= high {
+                        break;
+                    }
+                }
+            }
+        }
+        if position != 0 {
+            return Err(invalid("malformed FSE distribution"));
+        }
+        for entry in &mut entries {
+            let state = next[usize::from(entry.symbol)];
+            next[usize::from(entry.symbol)] += 1;
+            let bits = log as u32 - (31 - state.leading_zeros());
+            entry.bits = bits as u8;
+            entry.baseline = ((state << bits) as usize - size) as u16;
+        }
+        Ok(Self { log, entries })
+    }
+
+    fn init(&self, bits: &mut BackwardBits) -> usize {
+        bits.read(u32::from(self.log)) as usize
+    }
+
+    fn symbol(&self, state: usize) -> u8 {
+        self.entries[state].symbol
+    }
+
+    fn update(&self, state: usize, bits: &mut BackwardBits) -> usize {
+        let entry = self.entries[state];
+        usize::from(entry.baseline) + bits.read(u32::from(entry.bits)) as usize
+    }
+}
+
+// Normalized counts of an FSE table description at the start of `data`, with
+// the accuracy log and the number of bytes it took
+fn read_counts(data: &[u8], max_symbol: u8, max_log: u8) -> io::Result<(Vec<i16>, u8, usize)> {
+    // Forward little-endian bits; reads past the end yield zeros
+    let bit = |pos: usize, n: u32| -> u32 {
+        let mut value = 0;
+        for i in 0..n as usize {
+            let p = pos + i;
+            let set = data.get(p / 8).is_some_and(|b| b >> (p % 8) & 1 != 0);
+            value |= u32::from(set) << i;
+        }
+        value
+    };
+    let mut pos = 0;
+    let log = bit(pos, 4) as u8 + 5;
+    pos += 4;
+    if log > max_log {
+        return Err(invalid("FSE accuracy log too large"));
+    }
+
+    let mut counts = Vec::new();
+    let mut remaining = (1i32 << log) + 1;
+    let mut threshold = 1i32 << log;
+    let mut nb_bits = u32::from(log) + 1;
+    let mut previous_zero = false;
+    while remaining > 1 {
+        if previous_zero {
+            loop {
+                let repeat = bit(pos, 2);
+                pos += 2;
+                counts.extend(std::iter::repeat_n(0, repeat as usize));
+                if repeat != 3 {
+                    break;
+                }
+            }
+        }
+        if counts.len() > usize::from(max_symbol) {
+            return Err(invalid("too many symbols in an FSE distribution"));
+        }
+        let max = 2 * threshold - 1 - remaining;
+        let low = bit(pos, nb_bits - 1) as i32;
+        let value = if low < max {
+            pos += nb_bits as usize - 1;
+            low
+        } else {
+            let value = bit(pos, nb_bits) as i32;
+            pos += nb_bits as usize;
+            if value >= threshold {
+                value - max
+            } else {
+                value
+            }
+        };
+        let count = value - 1;
+        remaining -= count.abs();
+        counts.push(count as i16);
+        previous_zero = count == 0;
+        while remaining < threshold {
+            nb_bits -= 1;
+            threshold >>= 1;
+        }
+    }
+    let used = pos.div_ceil(8);
+    if remaining != 1 || used > data.len() {
+        return Err(invalid("malformed FSE distribution"));
+    }
+    Ok((counts, log, used))
+}
+
+// A Huffman decoding table indexed by the next `max_bits` bits
+struct HuffmanTable {
+    max_bits: u32,
+    // (symbol, code length)
+    entries: Vec<(u8, u8)>,
+}
+
+impl HuffmanTable {
+    // The tree description at the start of `input`
+    fn read(input: &mut Input) -> io::Result<Self> {
+        let header = input.u8()?;
+        let mut weights = if header < 128 {
+            Self::fse_weights(input.take(usize::from(header))?)?
+        } else {
+            let count = usize::from(header - 127);
+            let bytes = input.take(count.div_ceil(2))?;
+            (0..count)
+                .map(|i| {
+                    let byte = bytes[i / 2];
+                    if i % 2 == 0 { byte >> 4 } else { byte & 0x0F }
+                })
+                .collect()
+        };
+        if weights.len() > 255 {
+            return Err(invalid("too many Huffman weights"));
+        }
+
+        // The last weight is implied: it completes the sum to a power of two
+        let sum: u32 = weights
+            .iter()
+            .filter(|w| **w > 0)
+            .map(|w| 1 << (w - 1))
+            .sum();
+        if sum == 0 {
+            return Err(invalid("empty Huffman tree"));
+        }
+        let max_bits = 32 - sum.leading_zeros();
+        let rest = (1 << max_bits) - sum;
+        if !rest.is_power_of_two() || max_bits > 11 {
+            return Err(invalid("malformed Huffman weights"));
+        }
+        weights.push(rest.trailing_zeros() as u8 + 1);
+
+        // Symbols fill the table by weight, then symbol order
+        let mut entries = Vec::with_capacity(1 << max_bits);
+        for weight in 1..=max_bits as u8 {
+            for (symbol, _) in weights.iter().enumerate().filter(|(_, w)| **w == weight) {
+                let length = (max_bits + 1 - u32::from(weight)) as u8;
+                let span = 1 << (weight - 1);
+                entries.extend(std::iter::repeat_n((symbol as u8, length), span));
+            }
+        }
+        if entries.len() != 1 << max_bits {
+            return Err(invalid("malformed Huffman weights"));
+        }
+        Ok(Self { max_bits, entries })
+    }
+
+    // Weights compressed with FSE: two interleaved states over one bitstream
+    fn fse_weights(data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut input = Input::new(data);
+        let table = FseTable::read(&mut input, 255, 6)?;
+        let mut bits = BackwardBits::new(input.data)?;
+        let mut states = [table.init(&mut bits), table.init(&mut bits)];
+        let mut weights = Vec::new();
+        'decode: loop {
+            for i in 0..2 {
+                weights.push(table.symbol(states[i]));
+                states[i] = table.update(states[i], &mut bits);
+                if bits.overflowed() {
+                    weights.push(table.symbol(states[1 - i]));
+                    break 'decode;
+                }
+            }
+            if weights.len() > 255 {
+                return Err(invalid("too many Huffman weights"));
+            }
+        }
+        Ok(weights)
+    }
+
+    // Decode `count` literals from one stream
+    fn decode(&self, data: &[u8], count: usize, out: &mut Vec<u8>) -> io::Result<()> {
+        let mut bits = BackwardBits::new(data)?;
+        for _ in 0..count {
+            let (symbol, length) = self.entries[bits.peek(self.max_bits) as usize];
+            bits.read(u32::from(length));
+            out.push(symbol);
+        }
+        if bits.pos != 0 {
+            return Err(invalid("Huffman stream not fully consumed"));
+        }
+        Ok(())
+    }
+}
+
+// XXH64, whose low 32 bits make the frame checksum, over data fed to it in pieces
+struct Xxh64 {
+    seed: u64,
+    lanes: [u64; 4],
+    // The start of a 32 byte stripe not complete yet
+    pending: Vec<u8>,
+    len: u64,
+}
+
+impl Xxh64 {
+    const P1: u64 = 0x9E37_79B1_85EB_CA87;
+    const P2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+    const P3: u64 = 0x1656_67B1_9E37_79F9;
+    const P4: u64 = 0x85EB_CA77_C2B2_AE63;
+    const P5: u64 = 0x27D4_EB2F_1656_67C5;
+
+    fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            lanes: [
+                seed.wrapping_add(Self::P1).wrapping_add(Self::P2),
+                seed.wrapping_add(Self::P2),
+                seed,
+                seed.wrapping_sub(Self::P1),
+            ],
+            pending: Vec::with_capacity(32),
+            len: 0,
+        }
+    }
+
+    fn round(acc: u64, input: u64) -> u64 {
+        acc.wrapping_add(input.wrapping_mul(Self::P2))
+            .rotate_left(31)
+            .wrapping_mul(Self::P1)
+    }
+
+    fn stripe(&mut self, stripe: &[u8]) {
+        for (lane, word) in self.lanes.iter_mut().zip(stripe.chunks_exact(8)) {
+            *lane = Self::round(*lane, u64::from_le_bytes(word.try_into().unwrap()));
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+        if !self.pending.is_empty() {
+            let n = data.len().min(32 - self.pending.len());
+            self.pending.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.pending.len() < 32 {
+                return;
+            }
+            let stripe: [u8; 32] = self.pending[..].try_into().unwrap();
+            self.stripe(&stripe);
+            self.pending.clear();
+        }
+        let mut stripes = data.chunks_exact(32);
+        for stripe in &mut stripes {
+            self.stripe(stripe);
+        }
+        self.pending.extend_from_slice(stripes.remainder());
+    }
+
+    fn finish(&self) -> u64 {
+        let merge = |acc: u64, value: u64| {
+            (acc ^ Self::round(0, value))
+                .wrapping_mul(Self::P1)
+                .wrapping_add(Self::P4)
+        };
+        let mut hash = if self.len >= 32 {
+            let v = self.lanes;
+            let mut hash = v[0]
+                .rotate_left(1)
+                .wrapping_add(v[1].rotate_left(7))
+                .wrapping_add(v[2].rotate_left(12))
+                .wrapping_add(v[3].rotate_left(18));
+            for v in v {
+                hash = merge(hash, v);
+            }
+            hash
+        } else {
+            self.seed.wrapping_add(Self::P5)
+        };
+        hash = hash.wrapping_add(self.len);
+
+        let mut rest = self.pending.as_slice();
+        while let Some((word, tail)) = rest.split_first_chunk::<8>() {
+            hash = (hash ^ Self::round(0, u64::from_le_bytes(*word)))
+                .rotate_left(27)
+                .wrapping_mul(Self::P1)
+                .wrapping_add(Self::P4);
+            rest = tail;
+        }
+        if let Some((word, tail)) = rest.split_first_chunk::<4>() {
+            hash = (hash ^ u64::from(u32::from_le_bytes(*word)).wrapping_mul(Self::P1))
+                .rotate_left(23)
+                .wrapping_mul(Self::P2)
+                .wrapping_add(Self::P3);
+            rest = tail;
+        }
+        for &byte in rest {
+            hash = (hash ^ u64::from(byte).wrapping_mul(Self::P5))
+                .rotate_left(11)
+                .wrapping_mul(Self::P1);
+        }
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(Self::P2);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(Self::P3);
+        hash ^ (hash >> 32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+    use std::process::{Command, Stdio};
+
+    // Text with repeats near and far and now and then any byte value, or
+    // noise when `noise` is set
+    fn sample(len: usize, noise: bool) -> Vec<u8> {
+        const WORDS: [&str; 9] = [
+            "pacman",
+            "archcraft",
+            "openbox",
+            "polybar",
+            "crafty",
+            "zstd",
+            "\n",
+            "\t",
+            "==",
+        ];
+        let mut state: u32 = 0x2545_F491;
+        let mut out = Vec::with_capacity(len);
+        while out.len() < len {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            if noise || state.is_multiple_of(8) {
+                out.extend_from_slice(&state.to_le_bytes());
+            } else {
+                out.extend_from_slice(WORDS[(state >> 8) as usize % WORDS.len()].as_bytes());
+            }
+        }
+        out.truncate(len);
+        out
+    }
+
+    // `data` compressed by the zstd command with `args`
+    fn compress(data: &[u8], args: &[&str]) -> Vec<u8> {
+        let mut child = Command::new("zstd")
+            .args(["-q", "-c"])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("zstd is needed to build the test data");
+        let mut stdin = child.stdin.take().unwrap();
+        let input = data.to_vec();
+        let writer = std::thread::spawn(move || stdin.write_all(&input));
+        let output = child.wait_with_output().unwrap();
+        writer.join().unwrap().unwrap();
+        assert!(output.status.success());
+        output.stdout
+    }
+
+    fn xxh64(data: &[u8], seed: u64) -> u64 {
+        let mut hash = Xxh64::new(seed);
+        hash.update(data);
+        hash.finish()
+    }
+
+    fn round_trip(data: &[u8], args: &[&str]) {
+        let compressed = compress(data, args);
+        let decompressed = decompress(&compressed).unwrap();
+        assert!(decompressed == data, "zstd {:?} didn't round-trip", args);
+    }
+
+    #[test]
+    fn decompresses_every_level() {
+        // Over 128 KiB, so it takes several blocks
+        let data = sample(300_000, false);
+        for level in ["-1", "-3", "-9", "-19"] {
+            round_trip(&data, &[level]);
+        }
+        round_trip(&data, &["--ultra", "-22", "--long=24"]);
+        round_trip(&data, &["-19", "--no-check"]);
+        round_trip(&data, &["--fast=5"]);
+    }
+
+    #[test]
+    fn decompresses_past_the_window_into_a_file() {
+        // Streamed without a content size, level 1 keeps a 512 KiB window
+        let data = sample(4_000_000, false);
+        let dir = TempDir::new("zstd");
+        let from = dir.join("data.zst");
+        let to = dir.join("data");
+        fs::write(&from, compress(&data, &["-1"])).unwrap();
+        decompress_file(&from, &to).unwrap();
+        assert!(fs::read(&to).unwrap() == data);
+
+        fs::write(&from, b"not zstd at all").unwrap();
+        assert!(decompress_file(&from, &to).is_err());
+        assert!(!to.exists());
+    }
+
+    #[test]
+    fn decompresses_raw_and_rle_blocks() {
+        round_trip(&sample(200_000, true), &["-3"]);
+        round_trip(&[b'a'; 300_000], &["-3"]);
+        round_trip(b"", &["-3"]);
+        round_trip(b"tiny", &["-3"]);
+    }
+
+    #[test]
+    fn decompresses_short_inputs() {
+        // Small blocks are where raw and RLE literals and single streams show up
+        for len in [1, 17, 100, 1000, 5000] {
+            round_trip(&sample(len, false), &["-19"]);
+            round_trip(&sample(len, true), &["-19"]);
+        }
+    }
+
+    #[test]
+    fn decompresses_concatenated_and_skippable_frames() {
+        let first = sample(10_000, false);
+        let second = sample(3_000, true);
+        let mut data = compress(&first, &["-5"]);
+        // A skippable frame with four bytes of user data
+        data.extend_from_slice(&[0x50, 0x2A, 0x4D, 0x18, 4, 0, 0, 0, 1, 2, 3, 4]);
+        data.extend_from_slice(&compress(&second, &["-5"]));
+
+        let mut expected = first;
+        expected.extend_from_slice(&second);
+        assert!(decompress(&data).unwrap() == expected);
+    }
+
+    #[test]
+    fn rejects_damaged_input() {
+        let data = sample(50_000, false);
+        let compressed = compress(&data, &["-3"]);
+
+        assert!(decompress(b"not zstd at all").is_err());
+        assert!(decompress(&compressed[..compressed.len() / 2]).is_err());
+        // Flipping a bit in the payload breaks decoding or the checksum
+        let mut flipped = compressed.clone();
+        let middle = flipped.len() / 2;
+        flipped[middle] ^= 0x10;
+        assert!(decompress(&flipped).is_err());
+    }
+
+    #[test]
+    fn hashes_like_xxh64() {
+        // Reference values from the xxHash test suite
+        assert_eq!(xxh64(b"", 0), 0xEF46_DB37_51D8_E999);
+        assert_eq!(xxh64(b"a", 0), 0xD24E_C4F1_A98C_6E5B);
+        assert_eq!(xxh64(b"abc", 0), 0x44BC_2CF5_AD77_0999);
+        assert_eq!(
+            xxh64(b"Nobody inspects the spammish repetition", 0),
+            0xFBCE_A83C_8A37_8BF1
+        );
+
+        // Fed in pieces that don't line up with its 32 byte stripes
+        let data = sample(1000, true);
+        let mut hash = Xxh64::new(0);
+        for piece in data.chunks(13) {
+            hash.update(piece);
+        }
+        assert_eq!(hash.finish(), xxh64(&data, 0));
+    }
+}
@@ -0,0 +1,123 @@
+// Declarative mode: bring the machine in line with a manifest
+
+use super::install;
+use crate::{report, style};
+use crafty_core::{Manager, Result, lock::LockFile, manifest::Manifest};
+use std::path::Path;
+
+pub fn apply(
+    manager: &Manager,
+    file: &Path,
+    prune: bool,
+    dry_run: bool,
+    lock: Option<&LockFile>,
+    yes: bool,
+) -> Result<()> {
+    let manifest = Manifest::load(file)?;
+    if lock.is_none() {
+        log::info!("Fetching package list from ArchCraft GitHub...");
+    }
+    let plan = manager.plan_manifest(&manifest, prune, lock)?;
+
+    if !plan.unsatisfiable.is_empty() {
+        println!("Cannot satisfy the manifest for:");
+        for (pkg, reason) in &plan.unsatisfiable {
+            println!("- {} ({})", pkg, reason);
+        }
+    }
+    if plan.is_empty() {
+        println!("Nothing to do, the system matches {}.", file.display());
+        return Ok(());
+    }
+
+    let prefix = if dry_run { "would " } else { "" };
+    for pkg in &plan.install {
+        println!("- {}install {}", prefix, pkg);
+    }
+    for update in &plan.upgrade {
+        println!(
+            "- {}upgrade {} {} -> {}",
+            prefix, update.name, update.installed, update.available
+        );
+    }
+    for pkg in &plan.adopt {
+        println!("- {}adopt {}", prefix, pkg);
+    }
+    for pkg in &plan.hold {
+        println!("- {}hold {}", prefix, pkg);
+    }
+    for pkg in &plan.unhold {
+        println!("- {}unhold {}", prefix, pkg);
+    }
+    for pkg in &plan.remove {
+        println!("- {}remove {}", prefix, pkg);
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    // Confirmed like install and remove do, before the lock is taken;
+    // declining either leaves the system as it is
+    let wanted: Vec<String> = plan
+        .install
+        .iter()
+        .chain(plan.upgrade.iter().map(|u| &u.name))
+        .cloned()
+        .collect();
+    let installs = if wanted.is_empty() {
+        Vec::new()
+    } else {
+        install::proceed(manager, &wanted, lock, yes)?
+    };
+    if !wanted.is_empty() && installs.is_empty() {
+        return Ok(());
+    }
+    if !plan.remove.is_empty() && !install::proceed_removal(manager, &plan.remove, yes) {
+        return Ok(());
+    }
+
+    let _lock = manager.lock_db()?;
+    let mut db = manager.load_db();
+    for pkg in &plan.adopt {
+        db.add(manager.installer.as_ref(), pkg)?;
+    }
+
+    let mut failed = Vec::new();
+    for pkg in &installs {
+        // Keep going, one broken package shouldn't stop the rest of the manifest
+        let result = match lock {
+            Some(lock) => install::install_locked(manager, lock, pkg),
+            None => install::install(manager, pkg),
+        };
+        if let Err(e) = result {
+            log::error!("{}", e);
+            report::record(|s| s.fail(pkg, &e));
+            failed.push(pkg.as_str());
+        }
+    }
+    // Removals share one transaction, confirmed above; failures are recorded
+    // by install::remove
+    if !plan.remove.is_empty()
+        && let Err(e) = install::remove(manager, &plan.remove, false, true)
+    {
+        log::error!("{}", e);
+        failed.extend(plan.remove.iter().map(String::as_str));
+    }
+
+    // Transactions saved their own copies of the database, start from the latest
+    let mut db = manager.load_db();
+    for pkg in &plan.hold {
+        db.held.insert(pkg.clone());
+    }
+    for pkg in &plan.unhold {
+        db.held.remove(pkg);
+    }
+    db.save()?;
+
+    if failed.is_empty() {
+        log::info!("{} Applied {}", style::check(), file.display());
+    } else {
+        log::error!("Failed to apply: {}", failed.join(", "));
+    }
+    Ok(())
+}
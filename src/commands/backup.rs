@@ -0,0 +1,36 @@
+// Rolling configs back to the snapshots taken before installs and upgrades
+
+use crate::style;
+use crafty_core::{Manager, Result, util::format_timestamp};
+
+pub fn restore(manager: &Manager, pkg: &str, timestamp: Option<u64>, list: bool) -> Result<()> {
+    if list {
+        let backups = manager.backups(pkg);
+        if backups.is_empty() {
+            println!("No backups of {}.", pkg);
+            return Ok(());
+        }
+        println!("Backups of {}:", pkg);
+        for backup in backups {
+            println!(
+                "- {} [{}] {} file(s)",
+                backup.timestamp,
+                format_timestamp(backup.timestamp),
+                backup.files().len()
+            );
+        }
+        return Ok(());
+    }
+
+    let backup = manager.restore(pkg, timestamp)?;
+    for file in backup.files() {
+        println!("- restored {}", file.display());
+    }
+    log::info!(
+        "{} Restored the configs of {} from {}",
+        style::check(),
+        pkg,
+        format_timestamp(backup.timestamp)
+    );
+    Ok(())
+}
@@ -0,0 +1,134 @@
+// Candidates for shell completion, see `crafty hook completion`. Subcommands
+// and flags come from the clap definition, package names from the cached
+// index and the database; nothing here goes over the network.
+
+use clap::{Arg, Command};
+use crafty_core::{Manager, Result, repo::RepoPackage};
+use std::collections::BTreeSet;
+
+// `words` are the arguments after `crafty` up to the cursor, the last one
+// being the (possibly empty) word under it
+pub fn complete(manager: &Manager, mut command: Command, words: &[String]) -> Result<()> {
+    let Some((current, before)) = words.split_last() else {
+        return Ok(());
+    };
+    // Propagates the global flags to every subcommand
+    command.build();
+
+    let mut command = &command;
+    let mut path = Vec::new();
+    let mut value_of: Option<&Arg> = None;
+    let mut positionals = 0;
+    let mut options_done = false;
+    for word in before {
+        if value_of.take().is_some() {
+            continue;
+        }
+        if word == "--" {
+            options_done = true;
+        } else if !options_done && word.starts_with('-') {
+            // `--limit-rate 1M` takes the next word, `--limit-rate=1M` doesn't
+            value_of = find_option(command, word)
+                .filter(|arg| !word.contains('=') && arg.get_action().takes_values());
+        } else if let Some(sub) = command.find_subcommand(word).filter(|_| positionals == 0) {
+            path.push(sub.get_name());
+            command = sub;
+        } else {
+            positionals += 1;
+        }
+    }
+
+    let candidates: Vec<String> = if let Some(arg) = value_of {
+        option_values(manager, arg)
+    } else if !options_done && current.starts_with('-') {
+        command
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .filter_map(|arg| arg.get_long())
+            .map(|long| format!("--{}", long))
+            .collect()
+    } else if command.has_subcommands() && positionals == 0 {
+        command
+            .get_subcommands()
+            .filter(|sub| !sub.is_hide_set())
+            .map(|sub| sub.get_name().to_string())
+            .collect()
+    } else {
+        arguments(manager, &path, positionals)
+    };
+
+    for candidate in candidates
+        .iter()
+        .filter(|c| c.starts_with(current.as_str()))
+    {
+        println!("{}", candidate);
+    }
+    Ok(())
+}
+
+// The flag `word` names, by its long or short form
+fn find_option<'a>(command: &'a Command, word: &str) -> Option<&'a Arg> {
+    let name = word.split('=').next().unwrap_or(word);
+    command
+        .get_arguments()
+        .find(|arg| match name.strip_prefix("--") {
+            Some(long) => arg.get_long() == Some(long),
+            None => {
+                let mut short = name.chars().skip(1);
+                short.next().is_some_and(|c| arg.get_short() == Some(c)) && short.next().is_none()
+            }
+        })
+}
+
+// Values for a flag; paths and free text are left to the shell
+fn option_values(manager: &Manager, arg: &Arg) -> Vec<String> {
+    if arg.get_id() == "tag" {
+        return tags(manager);
+    }
+    arg.get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect()
+}
+
+// The `n`th positional argument of the subcommand at `path`
+fn arguments(manager: &Manager, path: &[&str], n: usize) -> Vec<String> {
+    match (path, n) {
+        (["install" | "search" | "download" | "files"], _) => available(manager),
+        (["remove" | "upgrade" | "hold" | "unhold" | "why" | "changelog" | "history"], _) => {
+            installed(manager)
+        }
+        (["tag" | "note" | "restore" | "verify"], 0) => installed(manager),
+        (["tag"], _) => tags(manager),
+        _ => Vec::new(),
+    }
+}
+
+// Package names in the cached repository index
+fn available(manager: &Manager) -> Vec<String> {
+    let names: BTreeSet<String> = manager
+        .repo
+        .offline_index()
+        .iter()
+        .filter_map(|file| RepoPackage::from_file(file))
+        .map(|package| package.name)
+        .collect();
+    names.into_iter().collect()
+}
+
+// Packages installed through crafty
+fn installed(manager: &Manager) -> Vec<String> {
+    manager.load_db().packages.into_keys().collect()
+}
+
+// Tags in use on any package
+fn tags(manager: &Manager) -> Vec<String> {
+    let tags: BTreeSet<String> = manager
+        .load_db()
+        .packages
+        .into_values()
+        .flat_map(|package| package.tags)
+        .collect();
+    tags.into_iter().collect()
+}
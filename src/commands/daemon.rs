@@ -0,0 +1,96 @@
+// `crafty daemon`: periodic update checks with the result served to status bars
+
+use clap::ValueEnum;
+use crafty_core::{
+    Error, Manager, Result,
+    daemon::{self, Server, Status},
+    updates::PendingUpdate,
+    util::parse_duration,
+};
+use std::{io, thread};
+
+/// How `daemon status` prints the status.
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum Format {
+    /// The number of pending updates, e.g. for polybar
+    #[default]
+    Text,
+    /// A custom module object with text, tooltip and class for waybar
+    Waybar,
+    /// Everything the daemon knows, as it is sent over the socket
+    Json,
+}
+
+pub fn run(manager: &mut Manager, interval: Option<&str>, download: bool) -> Result<()> {
+    let span = interval.unwrap_or(&manager.config.check_interval);
+    let interval = parse_duration(span)
+        .filter(|d| !d.is_zero())
+        .ok_or_else(|| Error::Config(format!("invalid check interval '{}'", span)))?;
+    let path = daemon::socket_path();
+    let server = Server::bind(&path)?;
+    let status = server.status();
+    server.serve();
+    log::info!(
+        "Serving the update status on {}, checking every {}",
+        path.display(),
+        span
+    );
+
+    loop {
+        // The index of the previous round would never show anything new
+        manager.repo.refresh();
+        match manager.check_updates() {
+            Ok(state) => {
+                log::info!("{} update(s) available", state.updates.len());
+                let downloaded = if download {
+                    prefetch(manager, &state.updates)
+                } else {
+                    Vec::new()
+                };
+                *status.lock().unwrap() = Status {
+                    state,
+                    error: None,
+                    downloaded,
+                };
+            }
+            Err(e) => {
+                log::warn!("Checking for updates failed: {}", e);
+                status.lock().unwrap().error = Some(e.to_string());
+            }
+        }
+        thread::sleep(interval);
+    }
+}
+
+// Download pending updates so `crafty upgrade` finds them cached; installing
+// is left to the user
+fn prefetch(manager: &Manager, updates: &[PendingUpdate]) -> Vec<String> {
+    updates
+        .iter()
+        .filter(|update| match manager.prefetch(&update.name) {
+            Ok(_) => true,
+            Err(e) => {
+                log::warn!("Failed to download {}: {}", update.name, e);
+                false
+            }
+        })
+        .map(|update| update.name.clone())
+        .collect()
+}
+
+pub fn status(format: Format) -> Result<()> {
+    let path = daemon::socket_path();
+    let status = daemon::query(&path).map_err(|e| {
+        Error::Io(io::Error::other(format!(
+            "no crafty daemon answering on {} ({})",
+            path.display(),
+            e
+        )))
+    })?;
+    match format {
+        Format::Text => println!("{}", status.state.updates.len()),
+        Format::Waybar => println!("{}", status.to_waybar()),
+        Format::Json => println!("{}", status.to_json()),
+    }
+    Ok(())
+}
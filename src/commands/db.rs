@@ -0,0 +1,70 @@
+// Maintenance of crafty's package database, and the tags and notes kept in it
+
+use crafty_core::{Error, Manager, Result};
+
+pub fn sync(manager: &Manager, dry_run: bool) -> Result<()> {
+    let _lock = manager.lock_db()?;
+    let mut db = manager.load_db();
+    let stale = if dry_run {
+        manager.stale_packages(&db)
+    } else {
+        manager.prune_uninstalled(&mut db)?
+    };
+
+    if stale.is_empty() {
+        println!(
+            "Database is in sync with pacman ({} packages).",
+            db.packages.len()
+        );
+        return Ok(());
+    }
+
+    if dry_run {
+        println!("Packages in the database that are no longer installed:");
+    } else {
+        println!("Removed from the database, no longer installed:");
+    }
+    for pkg in stale {
+        println!("- {}", pkg);
+    }
+    Ok(())
+}
+
+pub fn tag(manager: &Manager, package: &str, tags: &[String], remove: bool) -> Result<()> {
+    let _lock = manager.lock_db()?;
+    let mut db = manager.load_db();
+    if !db.tag(package, tags, remove) {
+        return Err(Error::NotInstalled(package.to_string()));
+    }
+    db.save()?;
+    let tags = &db.packages[package].tags;
+    if tags.is_empty() {
+        println!("{} has no tags", package);
+    } else {
+        println!("{} is tagged {}", package, tags.join(", "));
+    }
+    Ok(())
+}
+
+pub fn note(manager: &Manager, package: &str, text: Option<&str>, clear: bool) -> Result<()> {
+    let _lock = manager.lock_db()?;
+    let mut db = manager.load_db();
+    let Some(record) = db.get(package) else {
+        return Err(Error::NotInstalled(package.to_string()));
+    };
+    if text.is_none() && !clear {
+        match &record.note {
+            Some(note) => println!("{}", note),
+            None => println!("No note for {}", package),
+        }
+        return Ok(());
+    }
+    db.set_note(package, text);
+    db.save()?;
+    if clear {
+        println!("Removed the note of {}", package);
+    } else {
+        println!("Saved the note of {}", package);
+    }
+    Ok(())
+}
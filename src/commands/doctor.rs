@@ -0,0 +1,36 @@
+// `crafty doctor`: check the environment and suggest fixes
+
+use crate::style::{self, Style};
+use crafty_core::{
+    Manager,
+    doctor::{Check, Status},
+};
+
+pub fn doctor(manager: &Manager) {
+    let checks = manager.diagnose();
+    let width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+    for check in &checks {
+        print_check(check, width);
+    }
+
+    let count = |status| checks.iter().filter(|c| c.status == status).count();
+    let (errors, warnings) = (count(Status::Error), count(Status::Warning));
+    println!();
+    if errors + warnings == 0 {
+        println!("{} Everything looks fine.", style::check());
+    } else {
+        println!("{} error(s), {} warning(s).", errors, warnings);
+    }
+}
+
+fn print_check(check: &Check, width: usize) {
+    let mark = match check.status {
+        Status::Ok => style::check(),
+        Status::Warning => style::paint("!", Style::Warning),
+        Status::Error => style::paint("✗", Style::Error),
+    };
+    println!("{} {:<width$}  {}", mark, check.name, check.detail);
+    if let Some(fix) = &check.fix {
+        println!("  {:<width$}  {}", "", style::paint(fix, Style::Marker));
+    }
+}
@@ -0,0 +1,91 @@
+// Replicating the managed package set on another machine
+
+use super::{install, read_input};
+use crate::{report, style};
+use crafty_core::{Manager, Result, export::Export};
+use std::path::Path;
+
+pub fn export(manager: &Manager) {
+    println!("{}", manager.export().to_json());
+}
+
+pub fn import(manager: &Manager, file: &Path, dry_run: bool, yes: bool) -> Result<()> {
+    let export = Export::from_json(&read_input(file)?)?;
+    log::info!("Fetching package list from ArchCraft GitHub...");
+    let plan = manager.import_plan(&export)?;
+
+    if !plan.unavailable.is_empty() {
+        println!("No longer in the ArchCraft repository, skipping:");
+        for pkg in &plan.unavailable {
+            println!("- {}", pkg);
+        }
+    }
+    if plan.install.is_empty() && plan.adopt.is_empty() {
+        println!(
+            "Nothing to do, {} package(s) already installed.",
+            plan.present.len()
+        );
+    }
+    if dry_run {
+        for pkg in &plan.adopt {
+            println!("- would adopt {}", pkg);
+        }
+        for pkg in &plan.install {
+            println!("- would install {}", pkg);
+        }
+        return Ok(());
+    }
+
+    // Confirmed like install does, before the lock is taken
+    let installs = if plan.install.is_empty() {
+        Vec::new()
+    } else {
+        install::proceed(manager, &plan.install, None, yes)?
+    };
+    if !plan.install.is_empty() && installs.is_empty() {
+        return Ok(());
+    }
+
+    let _lock = manager.lock_db()?;
+    let mut db = manager.load_db();
+    for pkg in &plan.adopt {
+        db.add(manager.installer.as_ref(), pkg)?;
+        println!("- adopted {}", pkg);
+    }
+
+    let mut failed = Vec::new();
+    for pkg in &installs {
+        println!("Installing {}", pkg);
+        // Keep going, one broken package shouldn't stop the whole import
+        if let Err(e) = install::install(manager, pkg) {
+            log::error!("{}", e);
+            report::record(|s| s.fail(pkg, &e));
+            failed.push(pkg.as_str());
+        }
+    }
+
+    // install() saved its own copy of the database, start from that one
+    let mut db = manager.load_db();
+    for package in &export.packages {
+        if package.held {
+            db.held.insert(package.name.clone());
+        }
+        // Packages that failed to install aren't tracked and keep nothing
+        db.tag(&package.name, &package.tags, false);
+        if let Some(note) = &package.note {
+            db.set_note(&package.name, Some(note));
+        }
+    }
+    db.save()?;
+
+    if failed.is_empty() {
+        log::info!(
+            "{} Imported {} package(s)",
+            style::check(),
+            installs.len() + plan.adopt.len()
+        );
+    } else {
+        log::error!("Failed to install: {}", failed.join(", "));
+    }
+    Ok(())
+}
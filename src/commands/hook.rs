@@ -0,0 +1,114 @@
+// Shell integration: suggesting ArchCraft packages for commands that aren't
+// installed, and completion calling back into crafty
+
+use crate::style::{self, Style};
+use clap::ValueEnum;
+use crafty_core::{Error, Result, archive::package_name_from_file, files::FilesIndex};
+use std::env;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+// This binary's absolute path quoted for all three shells, so the scripts
+// don't depend on $PATH
+fn quoted_exe() -> Result<String> {
+    let exe = env::current_exe()?;
+    Ok(format!(
+        "'{}'",
+        exe.display().to_string().replace('\'', r"'\''")
+    ))
+}
+
+// A handler for `shell` to be sourced from its rc file. It calls this binary
+// by its absolute path, a missing crafty would otherwise trigger the handler again.
+pub fn command_not_found(shell: Shell) -> Result<()> {
+    let exe = quoted_exe()?;
+    let script = match shell {
+        Shell::Bash => format!(
+            r#"command_not_found_handle() {{
+    if ! {exe} hook lookup -- "$1" >&2 2>/dev/null; then
+        printf 'bash: %s: command not found\n' "$1" >&2
+    fi
+    return 127
+}}"#
+        ),
+        Shell::Zsh => format!(
+            r#"command_not_found_handler() {{
+    if ! {exe} hook lookup -- "$1" >&2 2>/dev/null; then
+        printf 'zsh: command not found: %s\n' "$1" >&2
+    fi
+    return 127
+}}"#
+        ),
+        Shell::Fish => format!(
+            r#"function fish_command_not_found
+    if not {exe} hook lookup -- $argv[1] >&2 2>/dev/null
+        __fish_default_command_not_found_handler $argv
+    end
+end"#
+        ),
+    };
+    println!("{}", script);
+    Ok(())
+}
+
+// Completion for `shell` asking `crafty __complete` for candidates, with the
+// words up to the cursor. Without any the shell completes file names.
+pub fn completion(shell: Shell) -> Result<()> {
+    let exe = quoted_exe()?;
+    let script = match shell {
+        Shell::Bash => format!(
+            r#"_crafty() {{
+    local IFS=$'\n'
+    COMPREPLY=($({exe} __complete -- "${{COMP_WORDS[@]:1:COMP_CWORD}}" 2>/dev/null))
+}}
+complete -o default -F _crafty crafty"#
+        ),
+        Shell::Zsh => format!(
+            r#"_crafty() {{
+    local -a candidates
+    candidates=("${{(@f)$({exe} __complete -- "${{(@)words[2,CURRENT]}}" 2>/dev/null)}}")
+    if [[ -n $candidates[1] ]]; then
+        compadd -a candidates
+    else
+        _files
+    fi
+}}
+compdef _crafty crafty"#
+        ),
+        Shell::Fish => format!(
+            r#"function __crafty_complete
+    set -l candidates ({exe} __complete -- (commandline -opc)[2..-1] (commandline -ct) 2>/dev/null)
+    if test (count $candidates) -gt 0
+        printf '%s\n' $candidates
+    else
+        __fish_complete_path (commandline -ct)
+    end
+end
+complete -c crafty -f -a '(__crafty_complete)'"#
+        ),
+    };
+    println!("{}", script);
+    Ok(())
+}
+
+// Called by the handler: only the cached files index is read, an unknown
+// command shouldn't wait for the network
+pub fn lookup(command: &str) -> Result<()> {
+    let providers = FilesIndex::load().providers(command);
+    if providers.is_empty() {
+        return Err(Error::NotFound(command.to_string()));
+    }
+    println!(
+        "{} is not installed, but can be installed from the ArchCraft repository with:",
+        style::paint(command, Style::Package)
+    );
+    for package_file in providers {
+        println!("  crafty install {}", package_name_from_file(&package_file));
+    }
+    Ok(())
+}
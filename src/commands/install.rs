@@ -0,0 +1,529 @@
+// Downloading, installing, upgrading, removing and adopting packages
+
+use super::{confirm, print_config_conflicts, print_warnings};
+use crate::{
+    report,
+    style::{self, Style},
+    table::Table,
+};
+use crafty_core::{
+    Error, Manager, Result,
+    aur::AurPackage,
+    conflicts::{Inspection, Problem},
+    lock::LockFile,
+    manager::{Preview, Verification},
+    resolver::StagedPackage,
+    util::format_size,
+};
+use std::path::Path;
+
+pub fn install(manager: &Manager, pkg: &str) -> Result<()> {
+    // Download the requested package plus every dependency that only the ArchCraft repo provides
+    let staged = manager.stage(pkg)?;
+    install_staged(manager, pkg, &staged)
+}
+
+// A package file on disk, tracked like one from the repository afterwards
+pub fn install_file(manager: &Manager, path: &Path, yes: bool) -> Result<()> {
+    if !confirm_install(&[manager.preview_file(path)?], yes) {
+        return Ok(());
+    }
+    let staged = manager.stage_file(path)?;
+    let name = format!("{} {}", staged[0].name, staged[0].version);
+    install_staged(manager, &name, &staged)
+}
+
+// `archcraft-openbox-*`: every matching package in a single transaction
+pub fn install_glob(manager: &Manager, pattern: &str, yes: bool) -> Result<()> {
+    log::info!("Fetching package list from ArchCraft GitHub...");
+    let packages = manager.expand_glob(pattern)?;
+    if packages.is_empty() {
+        return Err(Error::NotFound(pattern.to_string()));
+    }
+    println!("Packages matching '{}' ({}):", pattern, packages.len());
+    let packages = proceed(manager, &packages, None, yes)?;
+    if packages.is_empty() {
+        return Ok(());
+    }
+    let staged = manager.stage_many(&packages)?;
+    install_staged(manager, pattern, &staged)
+}
+
+// `@name`: every package of the group in a single transaction
+pub fn install_group(
+    manager: &Manager,
+    name: &str,
+    lock: Option<&LockFile>,
+    yes: bool,
+) -> Result<()> {
+    let group = manager.group(name)?;
+    println!(
+        "Group {} ({} packages): {}",
+        group.name,
+        group.packages.len(),
+        group.packages.join(", ")
+    );
+    let packages = proceed(manager, &group.packages, lock, yes)?;
+    if packages.is_empty() {
+        return Ok(());
+    }
+    let staged = stage_all(manager, &packages, lock)?;
+    install_staged(manager, &format!("@{}", group.name), &staged)
+}
+
+// Packages named on the command line, stdin or a list file, in a single
+// transaction; with `aur`, the ones no repository has are built from the AUR after it
+pub fn install_many(
+    manager: &Manager,
+    pkgs: &[String],
+    lock: Option<&LockFile>,
+    aur: bool,
+    yes: bool,
+) -> Result<()> {
+    if pkgs.is_empty() {
+        println!("No package names given.");
+        return Ok(());
+    }
+    // A lock file pins repository files, the AUR has no place in it
+    let (pkgs, from_aur) = match lock {
+        Some(_) => (pkgs.to_vec(), Vec::new()),
+        None => split_aur(manager, pkgs, aur)?,
+    };
+    if !pkgs.is_empty() {
+        let pkgs = proceed(manager, &pkgs, lock, yes)?;
+        if !pkgs.is_empty() {
+            let staged = stage_all(manager, &pkgs, lock)?;
+            install_staged(manager, &pkgs.join(", "), &staged)?;
+        }
+    }
+    install_aur(manager, &from_aur, yes)
+}
+
+// Names no configured repository has are looked up in the AUR: returned to be
+// built when `enabled`, otherwise only pointed out
+fn split_aur(
+    manager: &Manager,
+    pkgs: &[String],
+    enabled: bool,
+) -> Result<(Vec<String>, Vec<AurPackage>)> {
+    let mut found = Vec::new();
+    let mut missing = Vec::new();
+    for pkg in pkgs {
+        match manager.repo.find_package_file(pkg)? {
+            Some(_) => found.push(pkg.clone()),
+            None => missing.push(pkg.clone()),
+        }
+    }
+    if missing.is_empty() {
+        return Ok((found, Vec::new()));
+    }
+
+    let aur = manager.aur_info(&missing).unwrap_or_else(|e| {
+        log::warn!("Couldn't look the missing packages up in the AUR: {}", e);
+        Vec::new()
+    });
+    for pkg in &missing {
+        let Some(package) = aur.iter().find(|p| &p.name == pkg) else {
+            return Err(Error::NotFound(pkg.clone()));
+        };
+        if !enabled {
+            println!(
+                "{} isn't in the ArchCraft repositories, but the AUR has {} {}.",
+                style::paint(pkg, Style::Package),
+                pkg,
+                style::paint(&package.version, Style::Version)
+            );
+        }
+    }
+    if !enabled {
+        println!("Run 'crafty install --aur' or set `aur` in the config to build it from there.");
+        return Err(Error::NotFound(missing[0].clone()));
+    }
+    Ok((found, aur))
+}
+
+// AUR packages are built from user-submitted PKGBUILDs, so they get a prompt of their own
+fn install_aur(manager: &Manager, packages: &[AurPackage], yes: bool) -> Result<()> {
+    if packages.is_empty() {
+        return Ok(());
+    }
+    let mut table = Table::new(&["Package", "Old Version", "New Version", "Repository"]);
+    for package in packages {
+        let repository = if package.out_of_date {
+            style::paint("aur (flagged out of date)", Style::Warning)
+        } else {
+            String::from("aur")
+        };
+        table.row(vec![
+            style::paint(&package.name, Style::Package),
+            manager
+                .installer
+                .installed_version(&package.name)
+                .unwrap_or_default(),
+            style::paint(&package.version, Style::Version),
+            repository,
+        ]);
+    }
+    println!();
+    table.print();
+    println!();
+    log::warn!("AUR packages are not reviewed by ArchCraft, check their PKGBUILD before building");
+    if !(yes || confirm("Build and install from the AUR?", true)) {
+        return Ok(());
+    }
+
+    for package in packages {
+        let report = manager.install_aur(package)?;
+        report::record(|s| s.add_install(&report));
+        print_warnings(&report.warnings);
+        log::info!("{} Installed: {} (AUR)", style::check(), package.name);
+    }
+    Ok(())
+}
+
+// pacman-style overview of what is about to be downloaded, returning the
+// packages to go ahead with; those already at the repository's version are
+// left out, and none when the user declines
+pub fn proceed(
+    manager: &Manager,
+    pkgs: &[String],
+    lock: Option<&LockFile>,
+    yes: bool,
+) -> Result<Vec<String>> {
+    let mut previews = manager.preview(pkgs, lock)?;
+    previews.retain(|preview| {
+        let current = preview.installed.as_deref() == Some(preview.version.as_str());
+        if current {
+            log::info!("Skipping {} {} (up to date)", preview.name, preview.version);
+            report::record(|s| s.skip(&preview.name, "up to date"));
+        }
+        !current
+    });
+    if previews.is_empty() || !confirm_install(&previews, yes) {
+        return Ok(Vec::new());
+    }
+    Ok(previews.into_iter().map(|p| p.name).collect())
+}
+
+fn confirm_install(previews: &[Preview], yes: bool) -> bool {
+    let mut table =
+        Table::new(&["Package", "Old Version", "New Version", "Download Size"]).align_right(3);
+    let mut total = 0;
+    for preview in previews {
+        let size = match (preview.cached, preview.size) {
+            (true, _) => String::from("cached"),
+            (false, Some(size)) => {
+                total += size;
+                format_size(size)
+            }
+            (false, None) => String::from("?"),
+        };
+        table.row(vec![
+            style::paint(&preview.name, Style::Package),
+            preview.installed.clone().unwrap_or_default(),
+            style::paint(&preview.version, Style::Version),
+            size,
+        ]);
+    }
+    println!();
+    table.print();
+    println!();
+    println!("Total Download Size: {}", format_size(total));
+    yes || confirm("Proceed with installation?", true)
+}
+
+fn stage_all(
+    manager: &Manager,
+    pkgs: &[String],
+    lock: Option<&LockFile>,
+) -> Result<Vec<StagedPackage>> {
+    let Some(lock) = lock else {
+        return manager.stage_many(pkgs);
+    };
+    let mut staged: Vec<StagedPackage> = Vec::new();
+    for pkg in pkgs {
+        for package in manager.stage_locked(lock, pkg)? {
+            if !staged.iter().any(|p| p.name == package.name) {
+                staged.push(package);
+            }
+        }
+    }
+    Ok(staged)
+}
+
+// Same as install, but with exactly the files recorded in the lock file
+pub fn install_locked(manager: &Manager, lock: &LockFile, pkg: &str) -> Result<()> {
+    let staged = manager.stage_locked(lock, pkg)?;
+    install_staged(manager, pkg, &staged)
+}
+
+pub fn download(manager: &Manager, pkg: &str, dir: &Path, lock: Option<&LockFile>) -> Result<()> {
+    let download = manager.download(pkg, dir, lock)?;
+    match &download.verified {
+        Verification::Lock => log::info!("Checksum matches the lock file"),
+        Verification::Repository => log::info!("Checksum matches the repository"),
+        Verification::Unverified(reason) => {
+            log::warn!("Could not verify {} ({})", download.file, reason)
+        }
+    }
+    log::info!("sha256 {}", download.sha256);
+    // The path alone on stdout, so scripts can pick it up
+    println!("{}", download.path.display());
+    Ok(())
+}
+
+pub fn install_staged(manager: &Manager, pkg: &str, staged: &[StagedPackage]) -> Result<()> {
+    let dependencies: Vec<&StagedPackage> = staged.iter().filter(|p| p.dependency).collect();
+    if !dependencies.is_empty() {
+        println!("Resolved dependencies from the ArchCraft repository:");
+        for dep in dependencies {
+            println!("- {}", dep.file);
+        }
+    }
+
+    print_inspections(&manager.inspect(staged));
+    let report = manager.commit(staged)?;
+    report::record(|s| s.add_install(&report));
+    print_warnings(&report.warnings);
+    for backup in &report.backups {
+        println!(
+            "Backed up {} config file(s) of {}, 'crafty restore {}' rolls them back",
+            backup.files().len(),
+            backup.package,
+            backup.package
+        );
+    }
+    print_config_conflicts(&report.config_conflicts);
+    if let Some(first) = report.unregistered.first() {
+        for unregistered in &report.unregistered {
+            let error = Error::NotRegistered(unregistered.clone());
+            report::record(|s| s.fail(&unregistered.name, &error));
+            if unregistered.name != first.name {
+                log::error!("{}", error);
+            }
+        }
+        return Err(Error::NotRegistered(first.clone()));
+    }
+    log::info!("{} Installed: {}", style::check(), pkg);
+    Ok(())
+}
+
+// Warn about files pacman is going to trip over before it gets the chance
+fn print_inspections(inspections: &[Inspection]) {
+    let mut conflicts = false;
+    for inspection in inspections {
+        log::warn!("{} would write over existing files:", inspection.package);
+        for problem in &inspection.problems {
+            match problem {
+                Problem::Owned { path, owner } => {
+                    conflicts = true;
+                    log::warn!("- {} (owned by {})", path.display(), owner)
+                }
+                Problem::Untracked { path } => {
+                    conflicts = true;
+                    log::warn!("- {} (not owned by any package)", path.display())
+                }
+                Problem::Home { path } => log::warn!("- {} (in a home directory)", path.display()),
+            }
+        }
+    }
+    if conflicts {
+        log::warn!("pacman will refuse these, pass `-- --overwrite <glob>` to replace them");
+    }
+}
+
+pub fn upgrade(manager: &Manager, pkg: Option<&str>, yes: bool) -> Result<()> {
+    // The plan is made and confirmed without the lock, so a prompt left open
+    // doesn't block other crafty runs; packages removed with plain pacman
+    // aren't resurrected
+    let mut db = manager.load_db();
+    for stale in manager.stale_packages(&db) {
+        db.packages.remove(&stale);
+    }
+
+    let names = match pkg {
+        None => {
+            for held in db.sorted().into_iter().filter(|p| manager.is_held(&db, p)) {
+                log::info!("Skipping {} (held)", held);
+                report::record(|s| s.skip(held, "held"));
+            }
+            let updates = manager.pending_updates(&db)?;
+            if updates.is_empty() {
+                println!("All crafty-managed packages are up to date.");
+                return Ok(());
+            }
+            let names: Vec<String> = updates.into_iter().map(|u| u.name).collect();
+            proceed(manager, &names, None, yes)?
+        }
+        Some(pkg) if manager.is_held(&db, pkg) => {
+            report::record(|s| s.skip(pkg, "held"));
+            log::info!(
+                "Skipping {} (held), run 'crafty unhold {}' to upgrade it again.",
+                pkg,
+                pkg
+            );
+            return Ok(());
+        }
+        Some(pkg) if db.contains(pkg) => proceed(manager, &[pkg.to_string()], None, yes)?,
+        Some(pkg) => return Err(Error::NotInstalled(pkg.to_string())),
+    };
+    if names.is_empty() {
+        return Ok(());
+    }
+
+    let _lock = manager.lock_db()?;
+    let mut db = manager.load_db();
+    let pruned = manager.prune_uninstalled(&mut db)?;
+    if !pruned.is_empty() {
+        println!(
+            "Dropped from the database, no longer installed: {}",
+            pruned.join(", ")
+        );
+    }
+    // Another run may have removed or held some while the prompt was open
+    let (names, changed): (Vec<String>, Vec<String>) = names
+        .into_iter()
+        .partition(|name| db.contains(name) && !manager.is_held(&db, name));
+    for name in &changed {
+        log::warn!("Skipping {}, it was removed or held in the meantime", name);
+        report::record(|s| s.skip(name, "changed in the meantime"));
+    }
+    match (pkg, names.as_slice()) {
+        (_, []) => {}
+        (Some(pkg), _) => {
+            log::info!("Upgrading {}", pkg);
+            install(manager, pkg)?;
+        }
+        // Everything is downloaded first, then installed in one pacman transaction
+        (None, _) => {
+            let staged = manager.stage_many(&names)?;
+            install_staged(manager, "all upgrades", &staged)?;
+        }
+    }
+    Ok(())
+}
+
+// Every crafty-managed package in one pacman transaction, the rest is skipped
+pub fn remove(manager: &Manager, pkgs: &[String], orphans: bool, yes: bool) -> Result<()> {
+    let db = manager.load_db();
+    let (managed, unmanaged): (Vec<String>, Vec<String>) =
+        pkgs.iter().cloned().partition(|p| db.contains(p));
+    for pkg in &unmanaged {
+        log::warn!("Skipping {}, it wasn't installed through crafty", pkg);
+        report::record(|s| s.skip(pkg, "not managed by crafty"));
+    }
+    if managed.is_empty() {
+        println!("Nothing to remove.");
+        return Ok(());
+    }
+    if !proceed_removal(manager, &managed, yes) {
+        return Ok(());
+    }
+
+    log::info!("Removing {}", managed.join(", "));
+    let report = match manager.remove_many(&managed) {
+        Ok(report) => report,
+        Err(e) => {
+            // pacman transactions are all or nothing
+            report::record(|s| managed.iter().for_each(|p| s.fail(p, &e)));
+            return Err(e);
+        }
+    };
+    report::record(|s| s.add_remove(&report));
+    print_warnings(&report.warnings);
+    for removed in &report.removed {
+        log::info!("{} Removed: {}", style::check(), removed.name);
+    }
+    if !report.pruned.is_empty() {
+        println!(
+            "Dropped from the database, no longer installed: {}",
+            report.pruned.join(", ")
+        );
+    }
+    print_config_conflicts(&report.config_conflicts);
+
+    if report.orphans.is_empty() {
+        return Ok(());
+    }
+    println!("ArchCraft packages no longer required by any package:");
+    for orphan in &report.orphans {
+        println!("- {}", orphan);
+    }
+    if !orphans {
+        println!("Run 'crafty remove --orphans <package>' to clean them up as well.");
+        return Ok(());
+    }
+
+    if !proceed_removal(manager, &report.orphans, yes) {
+        return Ok(());
+    }
+    let removed = manager.remove_orphans(&report.orphans)?;
+    report::record(|s| s.add_remove(&removed));
+    print_warnings(&removed.warnings);
+    log::info!(
+        "{} Removed {} orphaned package(s)",
+        style::check(),
+        removed.removed.len()
+    );
+    print_config_conflicts(&removed.config_conflicts);
+    Ok(())
+}
+
+// The packages and versions about to go, true to go ahead
+pub fn proceed_removal(manager: &Manager, pkgs: &[String], yes: bool) -> bool {
+    let mut table = Table::new(&["Package", "Version"]);
+    for pkg in pkgs {
+        table.row(vec![
+            style::paint(pkg, Style::Package),
+            manager.installer.installed_version(pkg).unwrap_or_default(),
+        ]);
+    }
+    println!();
+    table.print();
+    println!();
+    yes || confirm("Proceed with removal?", true)
+}
+
+pub fn adopt(manager: &Manager, all: bool, dry_run: bool) -> Result<()> {
+    log::info!("Fetching package list from ArchCraft GitHub...");
+    let candidates = manager.adopt_candidates(all)?;
+    if candidates.is_empty() {
+        println!("No untracked ArchCraft packages found.");
+        return Ok(());
+    }
+
+    let _lock = manager.lock_db()?;
+    let mut db = manager.load_db();
+    for (name, version) in &candidates {
+        if dry_run {
+            println!("- would adopt {} {}", name, version);
+        } else {
+            db.add(manager.installer.as_ref(), name)?;
+            println!("- adopted {} {}", name, version);
+        }
+    }
+    if !dry_run {
+        log::info!("{} Adopted {} package(s)", style::check(), candidates.len());
+    }
+    Ok(())
+}
+
+pub fn hold(manager: &Manager, packages: &[String], hold: bool) -> Result<()> {
+    let _lock = manager.lock_db()?;
+    let mut db = manager.load_db();
+    for pkg in packages {
+        if hold {
+            if !db.contains(pkg) {
+                println!("Note: {} is not installed via crafty", pkg);
+            }
+            db.held.insert(pkg.clone());
+            println!("Holding {}", pkg);
+        } else if db.held.remove(pkg) {
+            println!("No longer holding {}", pkg);
+        } else {
+            println!("{} was not held", pkg);
+        }
+    }
+    db.save()?;
+    Ok(())
+}
@@ -0,0 +1,31 @@
+// Pinning the exact repository files for reproducible installs
+
+use crate::style;
+use crafty_core::{Manager, Result, manifest::Manifest};
+use std::path::Path;
+
+pub fn lock(manager: &Manager, manifest: Option<&Path>, output: &Path) -> Result<()> {
+    // Lock what the manifest asks for, otherwise everything crafty manages
+    let roots: Vec<String> = match manifest {
+        Some(file) => Manifest::load(file)?
+            .packages
+            .into_iter()
+            .map(|p| p.name)
+            .collect(),
+        None => manager.load_db().sorted().into_iter().cloned().collect(),
+    };
+    if roots.is_empty() {
+        println!("Nothing to lock.");
+        return Ok(());
+    }
+
+    let lock = manager.lock(&roots)?;
+    lock.save(output)?;
+    log::info!(
+        "{} Locked {} package(s) in {}",
+        style::check(),
+        lock.packages.len(),
+        output.display()
+    );
+    Ok(())
+}
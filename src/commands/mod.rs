@@ -0,0 +1,103 @@
+// Command handlers: each drives the crafty-core Manager and prints the outcome
+
+use crafty_core::{Error, Result, pacnew::ConfigConflict};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+pub mod apply;
+pub mod backup;
+pub mod complete;
+pub mod daemon;
+pub mod db;
+pub mod doctor;
+pub mod export;
+pub mod hook;
+pub mod install;
+pub mod lock;
+pub mod pacdiff;
+pub mod query;
+pub mod self_update;
+pub mod stats;
+pub mod theme;
+pub mod updates;
+pub mod verify;
+
+// `-` reads from stdin, e.g. an export piped over ssh
+fn read_input(file: &Path) -> Result<String> {
+    let read = if file == Path::new("-") {
+        let mut data = String::new();
+        io::stdin().read_to_string(&mut data).map(|_| data)
+    } else {
+        fs::read_to_string(file)
+    };
+    read.map_err(|e| Error::InvalidFile(format!("Failed to read {}: {}", file.display(), e)))
+}
+
+// Package names from the command line with `-` standing for those on stdin,
+// plus the ones listed in `from_file`. One name per line, anything after it
+// (e.g. a version), `#` comments and blank lines are ignored
+pub fn package_args(args: &[String], from_file: Option<&Path>) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    let lists = args
+        .iter()
+        .filter(|a| *a == "-")
+        .map(Path::new)
+        .chain(from_file);
+    for list in lists {
+        let data = read_input(list)?;
+        names.extend(
+            data.lines()
+                .map(|line| line.split('#').next().unwrap_or_default())
+                .filter_map(|line| line.split_whitespace().next())
+                .map(str::to_string),
+        );
+    }
+    names.extend(args.iter().filter(|a| *a != "-").cloned());
+    // Keep the first occurrence of names listed twice
+    let mut seen = std::collections::HashSet::new();
+    names.retain(|name| seen.insert(name.clone()));
+    Ok(names)
+}
+
+// Non-fatal problems reported by crafty-core, e.g. an unwritable history log
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        log::warn!("{}", warning);
+    }
+}
+
+// `.pacnew`/`.pacsave` files a transaction left behind
+fn print_config_conflicts(conflicts: &[ConfigConflict]) {
+    if conflicts.is_empty() {
+        return;
+    }
+    println!("Config files needing attention:");
+    for conflict in conflicts {
+        println!(
+            "- {} ({})",
+            conflict.conflict_path().display(),
+            conflict.package
+        );
+    }
+    println!("Run 'crafty pacdiff --merge' to review them.");
+}
+
+// Ask a yes/no question, an empty answer picks `default`; with stdin closed
+// there is nobody to answer, which declines
+fn confirm(question: &str, default: bool) -> bool {
+    let choices = if default { "[Y/n]" } else { "[y/N]" };
+    print!("{} {} ", question, choices);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    if !matches!(io::stdin().read_line(&mut answer), Ok(n) if n > 0) {
+        println!();
+        return false;
+    }
+    match answer.trim().to_lowercase().as_str() {
+        "" => default,
+        answer => matches!(answer, "y" | "yes"),
+    }
+}
@@ -0,0 +1,93 @@
+// Reviewing .pacnew/.pacsave files left next to modified config files
+
+use super::confirm;
+use crafty_core::{Error, Manager, Result, pacnew::ConfigConflict};
+use std::{ffi::CString, os::unix::ffi::OsStrExt, path::Path, process::Command};
+
+pub fn pacdiff(manager: &Manager, merge: bool, tool: Option<&str>) -> Result<()> {
+    let conflicts = manager.config_conflicts();
+    if conflicts.is_empty() {
+        println!("No .pacnew or .pacsave files found for crafty-managed packages.");
+        return Ok(());
+    }
+
+    println!(
+        "Config files needing attention ({} total):",
+        conflicts.len()
+    );
+    for conflict in &conflicts {
+        println!(
+            "- {} ({})",
+            conflict.conflict_path().display(),
+            conflict.package
+        );
+    }
+    if !merge {
+        println!("Run 'crafty pacdiff --merge' to review them.");
+        return Ok(());
+    }
+
+    let env_tool = std::env::var("DIFFPROG").ok().filter(|t| !t.is_empty());
+    let tool = tool
+        .map(str::to_string)
+        .or_else(|| manager.config.diff_tool.clone())
+        .or(env_tool)
+        .unwrap_or_else(|| String::from("vimdiff"));
+    for conflict in &conflicts {
+        merge_one(manager, &tool, conflict)?;
+    }
+    Ok(())
+}
+
+fn merge_one(manager: &Manager, tool: &str, conflict: &ConfigConflict) -> Result<()> {
+    let other = conflict.conflict_path();
+    println!(
+        "Comparing {} with {}",
+        conflict.path.display(),
+        other.display()
+    );
+
+    // Root-owned files can only be edited as root
+    let privileged = !writable(&conflict.path);
+    let mut cmd = if privileged {
+        manager.installer.privileged_command(tool)
+    } else {
+        Command::new(tool)
+    };
+    let status = cmd
+        .arg(&conflict.path)
+        .arg(&other)
+        .status()
+        .map_err(|e| Error::Config(format!("Failed to run {}: {}", tool, e)))?;
+    if !status.success() {
+        log::warn!("{} exited with {}", tool, status);
+    }
+
+    if !confirm(&format!("Remove {}?", other.display()), false) {
+        return Ok(());
+    }
+    let removed = if privileged {
+        manager
+            .installer
+            .privileged_command("rm")
+            .arg("--")
+            .arg(&other)
+            .status()
+            .is_ok_and(|s| s.success())
+    } else {
+        std::fs::remove_file(&other).is_ok()
+    };
+    if removed {
+        println!("Removed {}", other.display());
+    } else {
+        log::error!("Failed to remove {}", other.display());
+    }
+    Ok(())
+}
+
+fn writable(path: &Path) -> bool {
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
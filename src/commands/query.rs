@@ -0,0 +1,535 @@
+// Read-only commands: search, list, files, provides, history, changelog and why
+
+use crate::{
+    style::{self, Style},
+    table::Table,
+};
+use clap::ValueEnum;
+use crafty_core::{
+    Error, Manager, Result,
+    archive::{list_archive, package_name_from_file, parse_package_file},
+    aur,
+    descriptions::DescriptionIndex,
+    files::FilesIndex,
+    history::{self, Action},
+    pacman,
+    repo::RepoPackage,
+    search::SearchPattern,
+    util::{format_size, format_timestamp, natural_cmp},
+};
+use regex::Regex;
+use std::collections::HashMap;
+
+/// Order of `list` and `search` tables.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum SortKey {
+    Name,
+    /// Largest first
+    Size,
+    Version,
+}
+
+pub fn search(
+    manager: &Manager,
+    keywords: &[String],
+    regex: bool,
+    sort: Option<SortKey>,
+) -> Result<()> {
+    let patterns = patterns(keywords, regex)?;
+
+    let query = keywords.join(" ");
+    log::info!("Searching for '{}' in ArchCraft GitHub...", query);
+    let packages = manager.repo.search(&patterns)?;
+    if packages.is_empty() {
+        println!("No packages found for '{}'", query);
+        return Ok(());
+    }
+
+    let details: HashMap<String, RepoPackage> = manager
+        .repo
+        .package_details()?
+        .into_iter()
+        .map(|p| (p.file.clone(), p))
+        .collect();
+    let mut rows: Vec<(RepoPackage, String)> = packages
+        .iter()
+        .enumerate()
+        .filter_map(|(rank, hit)| {
+            let package = details.get(&hit.file)?.clone();
+            let mut name = highlight(&package.name, &hit.positions);
+            // Relevance order is lost once sorted by something else
+            if rank == 0 && sort.is_none() {
+                name = format!("{} {}", name, style::paint("(best match)", Style::Marker));
+            }
+            Some((package, name))
+        })
+        .collect();
+    if let Some(key) = sort {
+        sort_rows(&mut rows, key);
+    }
+    println!("Found packages:");
+    print_packages(manager, rows);
+    Ok(())
+}
+
+fn patterns(keywords: &[String], regex: bool) -> Result<Vec<SearchPattern>> {
+    if !regex {
+        return Ok(keywords.iter().cloned().map(SearchPattern::Fuzzy).collect());
+    }
+    keywords
+        .iter()
+        .map(|k| compile(k).map(SearchPattern::Regex))
+        .collect()
+}
+
+fn compile(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern)
+        .map_err(|e| Error::InvalidArgument(format!("Invalid regular expression: {}", e)))
+}
+
+// `search --desc`: what a package does rather than what it's called
+pub fn search_descriptions(
+    manager: &Manager,
+    keywords: &[String],
+    regex: bool,
+    update: bool,
+) -> Result<()> {
+    let patterns = patterns(keywords, regex)?;
+    let mut index = DescriptionIndex::load();
+    if update {
+        log::info!("Fetching package list from ArchCraft GitHub...");
+        index.update(
+            manager.repo.as_ref(),
+            &manager.config.staging_dir(),
+            |n, total, file| println!("[{}/{}] Indexing {}", n, total, file),
+        )?;
+    }
+
+    let query = keywords.join(" ");
+    let hits = index.search(&patterns);
+    if hits.is_empty() {
+        println!(
+            "No package description mentions '{}' ({} packages indexed).",
+            query,
+            index.packages.len()
+        );
+        if !update {
+            println!(
+                "Run 'crafty search --desc --update {}' to index the whole repository.",
+                query
+            );
+        }
+        return Ok(());
+    }
+
+    let mut table = Table::new(&["Name", "Version", "Description"]);
+    for hit in hits {
+        let (name, version) = parse_package_file(&hit.file)
+            .unwrap_or_else(|| (package_name_from_file(&hit.file), String::new()));
+        let description: String = hit
+            .description
+            .chars()
+            .enumerate()
+            .map(|(i, c)| {
+                if hit.positions.contains(&i) {
+                    style::paint(c, Style::Highlight)
+                } else {
+                    c.to_string()
+                }
+            })
+            .collect();
+        table.row(vec![
+            style::paint(&name, Style::Package),
+            style::paint(&version, Style::Version),
+            description,
+        ]);
+    }
+    println!("Found packages:");
+    table.print();
+    Ok(())
+}
+
+fn sort_rows(rows: &mut [(RepoPackage, String)], key: SortKey) {
+    match key {
+        SortKey::Name => rows.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name)),
+        SortKey::Size => rows.sort_by_key(|(p, _)| std::cmp::Reverse(p.size)),
+        SortKey::Version => rows.sort_by(|(a, _), (b, _)| natural_cmp(&a.version, &b.version)),
+    }
+}
+
+// Name, version, arch, size and the installed version of repository packages,
+// and which repository they come from when several are configured
+fn print_packages(manager: &Manager, rows: Vec<(RepoPackage, String)>) {
+    let installed: HashMap<String, String> = manager
+        .installer
+        .installed_package_versions(false)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let sources = manager.config.repos.len() > 1;
+    let mut headers = vec!["Name", "Version", "Arch", "Size", "Installed"];
+    if sources {
+        headers.push("Repo");
+    }
+    let mut table = Table::new(&headers).align_right(3);
+    for (package, name) in rows {
+        let installed = match installed.get(&package.name) {
+            Some(version) if *version == package.version => style::paint(version, Style::Marker),
+            Some(version) => style::paint(version, Style::Warning),
+            None => String::new(),
+        };
+        let mut row = vec![
+            name,
+            style::paint(&package.version, Style::Version),
+            package.arch,
+            package
+                .size
+                .map(format_size)
+                .unwrap_or_else(|| String::from("-")),
+            installed,
+        ];
+        if sources {
+            row.push(package.source);
+        }
+        table.row(row);
+    }
+    table.print();
+}
+
+// Emphasize the matched characters, painting the whole text as a package name
+fn highlight(text: &str, positions: &[usize]) -> String {
+    text.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if positions.contains(&i) {
+                style::paint(c, Style::Highlight)
+            } else {
+                style::paint(c, Style::Package)
+            }
+        })
+        .collect()
+}
+
+pub fn list(
+    manager: &Manager,
+    installed: bool,
+    regex: Option<&str>,
+    tag: Option<&str>,
+    sort: SortKey,
+) -> Result<()> {
+    let filter = regex.map(compile).transpose()?;
+    if installed {
+        list_installed_packages(manager, filter.as_ref(), tag);
+        Ok(())
+    } else {
+        list_packages(manager, filter.as_ref(), sort)
+    }
+}
+
+pub fn list_groups(manager: &Manager) -> Result<()> {
+    log::info!("Fetching package list from ArchCraft GitHub...");
+    let groups = manager.groups()?;
+    if groups.is_empty() {
+        println!("No package groups found.");
+        return Ok(());
+    }
+    println!("Package groups ({} total):", groups.len());
+    for group in groups {
+        let curated = if group.curated { " [curated]" } else { "" };
+        println!(
+            "- @{} ({} packages){}",
+            group.name,
+            group.packages.len(),
+            curated
+        );
+    }
+    Ok(())
+}
+
+fn list_packages(manager: &Manager, filter: Option<&Regex>, sort: SortKey) -> Result<()> {
+    log::info!("Fetching package list from ArchCraft GitHub...");
+    let mut rows: Vec<(RepoPackage, String)> = manager
+        .repo
+        .package_details()?
+        .into_iter()
+        .filter(|p| filter.is_none_or(|re| re.is_match(&p.name)))
+        .map(|p| {
+            let name = style::paint(&p.name, Style::Package);
+            (p, name)
+        })
+        .collect();
+    sort_rows(&mut rows, sort);
+    println!("Available packages ({} total):", rows.len());
+    print_packages(manager, rows);
+    Ok(())
+}
+
+fn list_installed_packages(manager: &Manager, filter: Option<&Regex>, tag: Option<&str>) {
+    let db = manager.load_db();
+    if db.packages.is_empty() {
+        println!("No packages installed through crafty.");
+        return;
+    }
+
+    let names: Vec<&String> = match tag {
+        Some(tag) => db.tagged(tag),
+        None => db.sorted(),
+    }
+    .into_iter()
+    .filter(|name| filter.is_none_or(|re| re.is_match(name)))
+    .collect();
+    if let Some(tag) = tag
+        && names.is_empty()
+    {
+        println!("No installed packages tagged {}.", tag);
+        return;
+    }
+    let query: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+    let local = manager.installer.query_local_packages(&query);
+    // Versions are only decoration here, list what we can while offline
+    let remote = manager.repo.remote_versions().unwrap_or_default();
+
+    println!("Installed packages ({} total):", names.len());
+    for name in names {
+        let Some(info) = local.iter().find(|p| &p.name == name) else {
+            println!(
+                "- {} {}",
+                style::paint(name, Style::Package),
+                style::paint("(not installed according to pacman)", Style::Warning)
+            );
+            continue;
+        };
+        let update = match remote.get(name.as_str()) {
+            Some(latest) if pacman::is_newer(latest, &info.version) => format!(
+                " {}",
+                style::paint(format!("[update available: {}]", latest), Style::Warning)
+            ),
+            Some(_) => String::new(),
+            None if db
+                .get(name)
+                .is_some_and(|p| p.origin.as_deref() == Some(aur::ORIGIN)) =>
+            {
+                format!(" {}", style::paint("[aur]", Style::Marker))
+            }
+            None => format!(" {}", style::paint("[not in repository]", Style::Marker)),
+        };
+        let held = if manager.is_held(&db, name) {
+            format!(" {}", style::paint("[held]", Style::Marker))
+        } else {
+            String::new()
+        };
+        let record = db.get(name);
+        let tags = match record.map(|p| &p.tags) {
+            Some(tags) if !tags.is_empty() => {
+                format!(
+                    " {}",
+                    style::paint(format!("[{}]", tags.join(", ")), Style::Marker)
+                )
+            }
+            _ => String::new(),
+        };
+        println!(
+            "- {} {} (installed {}){}{}{}",
+            style::paint(&info.name, Style::Package),
+            style::paint(&info.version, Style::Version),
+            info.install_date,
+            update,
+            held,
+            tags
+        );
+        if let Some(note) = record.and_then(|p| p.note.as_deref()) {
+            println!("  {}", note);
+        }
+    }
+}
+
+pub fn files(manager: &Manager, pkg: &str, remote: bool) -> Result<()> {
+    if !remote && let Some(files) = manager.installer.package_files(pkg) {
+        println!("Files installed by {}:", pkg);
+        for file in files {
+            println!("{}", file);
+        }
+        return Ok(());
+    }
+
+    let package_file = manager
+        .repo
+        .find_package_file(pkg)?
+        .ok_or_else(|| Error::NotFound(pkg.to_string()))?;
+
+    // Reuse the cached listing, otherwise fetch the archive without installing it
+    let mut index = FilesIndex::load();
+    let entries = match index.packages.get(&package_file) {
+        Some(entries) => entries.clone(),
+        None => {
+            let path = manager
+                .repo
+                .download_package(&package_file, &manager.config.staging_dir())?;
+            let entries = list_archive(&path).ok_or(Error::InvalidArchive(path))?;
+            index.packages.insert(package_file.clone(), entries.clone());
+            index.save();
+            entries
+        }
+    };
+
+    println!("Files in {}:", package_file);
+    for entry in entries {
+        println!("/{}", entry);
+    }
+    Ok(())
+}
+
+pub fn provides(manager: &Manager, path: &str, update: bool) -> Result<()> {
+    // Installed files are answered by the package manager directly
+    for owner in manager.installer.owners(path) {
+        println!("{} is owned by installed package {}", path, owner);
+    }
+
+    let mut index = FilesIndex::load();
+    if update {
+        log::info!("Fetching package list from ArchCraft GitHub...");
+        index.update(
+            manager.repo.as_ref(),
+            &manager.config.staging_dir(),
+            |n, total, file| println!("[{}/{}] Indexing {}", n, total, file),
+        )?;
+    }
+
+    let owners = index.owners(path);
+    if owners.is_empty() {
+        println!(
+            "No indexed ArchCraft package ships '{}' ({} packages indexed).",
+            path,
+            index.packages.len()
+        );
+        if !update {
+            println!(
+                "Run 'crafty provides --update {}' to index the whole repository.",
+                path
+            );
+        }
+        return Ok(());
+    }
+
+    println!("ArchCraft packages shipping '{}':", path);
+    for (package_file, entry) in owners {
+        println!("- {} ({})", package_name_from_file(&package_file), entry);
+    }
+    Ok(())
+}
+
+pub fn history(manager: &Manager, pkg: Option<&str>) {
+    let entries: Vec<history::Entry> = history::load(manager.state_dir())
+        .into_iter()
+        .filter(|e| pkg.is_none_or(|p| e.package == p))
+        .collect();
+
+    if entries.is_empty() {
+        match pkg {
+            Some(pkg) => println!("No recorded transactions for {}.", pkg),
+            None => println!("No recorded transactions."),
+        }
+        return;
+    }
+
+    for entry in entries {
+        let versions = match (&entry.old_version, &entry.new_version) {
+            (Some(old), Some(new)) => format!("{} -> {}", old, new),
+            (None, Some(new)) => new.clone(),
+            (Some(old), None) => old.clone(),
+            (None, None) => String::from("?"),
+        };
+        let action = match entry.action {
+            Action::Install => "installed",
+            Action::Upgrade => "upgraded",
+            Action::Remove => "removed",
+        };
+        let outcome = if entry.success { "" } else { " (failed)" };
+        println!(
+            "[{}] {} {} ({}){}",
+            format_timestamp(entry.timestamp),
+            action,
+            entry.package,
+            versions,
+            outcome
+        );
+    }
+}
+
+pub fn changelog(manager: &Manager, pkg: &str) -> Result<()> {
+    log::info!("Fetching history of {} from ArchCraft GitHub...", pkg);
+    let changelog = manager.changelog(pkg)?;
+    match &changelog.installed {
+        Some(installed) if *installed == changelog.latest => println!(
+            "{} {} is installed, which is the latest version.",
+            changelog.name, installed
+        ),
+        Some(installed) => println!(
+            "{}: installed {}, latest {}",
+            changelog.name, installed, changelog.latest
+        ),
+        None => println!(
+            "{}: not installed, latest {}",
+            changelog.name, changelog.latest
+        ),
+    }
+
+    if changelog.commits.is_empty() {
+        println!("No commits found.");
+        return Ok(());
+    }
+    for commit in &changelog.commits {
+        // Date part of the ISO timestamp and the first line of the message
+        let date = commit.date.get(..10).unwrap_or(&commit.date);
+        let summary = commit.message.lines().next().unwrap_or_default();
+        let sha = commit.sha.get(..7).unwrap_or(&commit.sha);
+        println!("[{}] {} {}", date, sha, summary);
+    }
+    Ok(())
+}
+
+pub fn why(manager: &Manager, pkg: &str) -> Result<()> {
+    let reason = manager.why(pkg)?;
+    let installed = if reason.explicit {
+        "explicitly installed"
+    } else {
+        "installed as a dependency"
+    };
+    let managed = if reason.managed {
+        "through crafty"
+    } else {
+        "outside crafty"
+    };
+    println!(
+        "{} {} is {} {}.",
+        reason.name, reason.version, installed, managed
+    );
+
+    if reason.required_by.is_empty() {
+        println!("Nothing depends on it, removing it won't break other packages.");
+    } else {
+        println!("Required by:");
+        for dependent in &reason.required_by {
+            let tag = if dependent.managed {
+                " (ArchCraft)"
+            } else {
+                ""
+            };
+            println!("- {}{}", dependent.name, tag);
+        }
+        if !reason.needed_by.is_empty() {
+            println!(
+                "Needed by explicitly installed: {}",
+                reason.needed_by.join(", ")
+            );
+        }
+        log::warn!(
+            "Removing {} alone would break the packages above.",
+            reason.name
+        );
+    }
+    if !reason.optional_for.is_empty() {
+        println!("Optional for: {}", reason.optional_for.join(", "));
+    }
+    Ok(())
+}
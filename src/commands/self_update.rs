@@ -0,0 +1,32 @@
+// `crafty self-update`: replace a standalone binary with the latest release
+
+use crate::style;
+use crafty_core::{Manager, Result};
+
+const CURRENT: &str = env!("CARGO_PKG_VERSION");
+
+pub fn self_update(manager: &Manager, check: bool) -> Result<()> {
+    log::info!("Checking for a newer crafty release...");
+    let release = manager.latest_release()?;
+    if !release.is_newer_than(CURRENT) {
+        println!("crafty {} is up to date.", CURRENT);
+        return Ok(());
+    }
+    if check {
+        println!(
+            "crafty {} is available (installed: {}).",
+            release.version, CURRENT
+        );
+        return Ok(());
+    }
+
+    log::info!("Downloading {}", release.binary.url);
+    manager.self_update(&release)?;
+    log::info!(
+        "{} Updated crafty {} -> {}",
+        style::check(),
+        CURRENT,
+        release.version
+    );
+    Ok(())
+}
@@ -0,0 +1,68 @@
+// `crafty stats`: counts, sizes and timestamps of the managed packages
+
+use crate::style::{self, Style};
+use crafty_core::{
+    Manager, Result,
+    util::{format_size, format_timestamp},
+};
+
+pub fn stats(manager: &Manager, json: bool) -> Result<()> {
+    if !json {
+        log::info!("Fetching package list from ArchCraft GitHub...");
+    }
+    let stats = manager.stats();
+    if json {
+        println!("{}", stats.to_json());
+        return Ok(());
+    }
+
+    let mut installed = stats.installed.to_string();
+    if stats.held > 0 {
+        installed.push_str(&format!(", {} held", stats.held));
+    }
+    if stats.aur > 0 {
+        installed.push_str(&format!(", {} from the AUR", stats.aur));
+    }
+    let mut size = format_size(stats.installed_size);
+    if stats.unknown_size > 0 {
+        size.push_str(&format!(
+            " (not recorded for {} package(s))",
+            stats.unknown_size
+        ));
+    }
+    let time = |timestamp: Option<u64>| match timestamp {
+        Some(timestamp) => format!("{} UTC", format_timestamp(timestamp)),
+        None => String::from("never"),
+    };
+    let pending = match (stats.pending_updates, stats.checked_at) {
+        (Some(count), None) => count.to_string(),
+        (Some(count), checked) => format!("{} as of the last check, {}", count, time(checked)),
+        (None, _) => String::from("unknown, the repository can't be reached"),
+    };
+
+    let rows = [
+        ("Installed", installed),
+        ("Installed size", size),
+        ("Cache size", format_size(stats.cache_size)),
+        ("Last refresh", time(stats.last_refresh)),
+        ("Last upgrade", time(stats.last_upgrade)),
+        ("Pending updates", pending),
+    ];
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, value) in &rows {
+        println!("{:<width$}  {}", name, value);
+    }
+
+    if !stats.recent.is_empty() {
+        println!();
+        println!("Recently updated in the repository:");
+        for change in &stats.recent {
+            println!(
+                "- {} {}",
+                style::paint(&change.name, Style::Package),
+                style::paint(change.date.get(..10).unwrap_or(&change.date), Style::Marker)
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+// Installing and removing ArchCraft theme bundles as a whole
+
+use super::{
+    install::{install_staged, proceed, proceed_removal},
+    print_config_conflicts, print_warnings,
+};
+use crate::{
+    report,
+    style::{self, Style},
+};
+use crafty_core::{Manager, Result};
+
+pub fn list(manager: &Manager) -> Result<()> {
+    log::info!("Fetching package list from ArchCraft GitHub...");
+    let themes = manager.themes()?;
+    if themes.is_empty() {
+        println!("No themes found in the repository.");
+        return Ok(());
+    }
+
+    let installed = manager
+        .installer
+        .installed_package_names()
+        .unwrap_or_default();
+    println!("Available themes ({} total):", themes.len());
+    for theme in themes {
+        let present = theme
+            .packages
+            .iter()
+            .filter(|p| installed.contains(*p))
+            .count();
+        let status = match present {
+            0 => String::new(),
+            n if n == theme.packages.len() => {
+                format!(" {}", style::paint("[installed]", Style::Marker))
+            }
+            n => format!(
+                " {}",
+                style::paint(
+                    format!("[{}/{} installed]", n, theme.packages.len()),
+                    Style::Marker
+                )
+            ),
+        };
+        println!(
+            "- {} ({} packages){}",
+            theme.name,
+            theme.packages.len(),
+            status
+        );
+    }
+    Ok(())
+}
+
+pub fn install(manager: &Manager, name: &str, yes: bool) -> Result<()> {
+    let theme = manager.theme(name)?;
+    println!(
+        "Theme {} ({} packages): {}",
+        theme.name,
+        theme.packages.len(),
+        theme.packages.join(", ")
+    );
+    let packages = proceed(manager, &theme.packages, None, yes)?;
+    if packages.is_empty() {
+        return Ok(());
+    }
+    // One transaction for the whole bundle, so it never ends up half installed
+    let staged = manager.stage_many(&packages)?;
+    install_staged(manager, &format!("theme {}", theme.name), &staged)
+}
+
+pub fn remove(manager: &Manager, name: &str, yes: bool) -> Result<()> {
+    let theme = manager.theme(name)?;
+    let installed = manager
+        .installer
+        .installed_package_names()
+        .unwrap_or_default();
+    let packages: Vec<String> = theme
+        .packages
+        .into_iter()
+        .filter(|p| installed.contains(p))
+        .collect();
+    if packages.is_empty() {
+        println!("No package of theme {} is installed.", name);
+        return Ok(());
+    }
+    if !proceed_removal(manager, &packages, yes) {
+        return Ok(());
+    }
+
+    log::info!("Removing theme {}: {}", name, packages.join(", "));
+    let report = manager.remove_many(&packages)?;
+    report::record(|s| s.add_remove(&report));
+    print_warnings(&report.warnings);
+    log::info!("{} Removed theme {}", style::check(), name);
+    print_config_conflicts(&report.config_conflicts);
+    Ok(())
+}
@@ -0,0 +1,54 @@
+// `upgrade --check`: report pending updates and optionally notify the desktop
+
+use crafty_core::{Manager, Result, updates::PendingUpdate};
+use std::process::Command;
+
+pub fn check(manager: &Manager, notify: bool) -> Result<()> {
+    let state = manager.check_updates()?;
+    let updates = &state.updates;
+    if updates.is_empty() {
+        println!("All crafty-managed packages are up to date.");
+        return Ok(());
+    }
+
+    println!("{} update(s) available:", updates.len());
+    for update in updates {
+        println!(
+            "- {} {} -> {}",
+            update.name, update.installed, update.available
+        );
+    }
+    if notify {
+        notify_updates(updates);
+    }
+    Ok(())
+}
+
+// Freedesktop notification through libnotify's notify-send
+fn notify_updates(updates: &[PendingUpdate]) {
+    let summary = if updates.len() == 1 {
+        String::from("1 ArchCraft package can be updated")
+    } else {
+        format!("{} ArchCraft packages can be updated", updates.len())
+    };
+    let mut body: Vec<String> = updates
+        .iter()
+        .take(10)
+        .map(|u| format!("{} {} → {}", u.name, u.installed, u.available))
+        .collect();
+    if updates.len() > 10 {
+        body.push(format!("and {} more", updates.len() - 10));
+    }
+
+    let sent = Command::new("notify-send")
+        .arg("--app-name=crafty")
+        .arg("--icon=system-software-update")
+        .arg(&summary)
+        .arg(body.join("\n"))
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+    if !sent {
+        log::warn!("Failed to send desktop notification (is notify-send installed?)");
+    }
+}
@@ -0,0 +1,100 @@
+// `crafty verify`: managed packages checked against pacman and the recorded
+// checksums, and optionally reinstalled
+
+use super::{confirm, install::install_staged};
+use crate::{
+    report,
+    style::{self, Style},
+};
+use crafty_core::{
+    Error, Manager, Result,
+    verify::{ArchiveState, PackageCheck},
+};
+
+pub fn verify(manager: &Manager, pkg: Option<&str>, reinstall: bool, yes: bool) -> Result<()> {
+    let checks = manager.verify(pkg)?;
+    let damaged: Vec<&PackageCheck> = checks.iter().filter(|c| !c.is_ok()).collect();
+    if damaged.is_empty() {
+        println!(
+            "{} {} package(s) verified, no problems found.",
+            style::check(),
+            checks.len()
+        );
+        return Ok(());
+    }
+    for check in &damaged {
+        print_check(check);
+    }
+
+    let broken: Vec<String> = damaged
+        .iter()
+        .filter(|c| c.is_broken())
+        .map(|c| c.name.clone())
+        .collect();
+    let names = || damaged.iter().map(|c| c.name.clone()).collect();
+    if broken.is_empty() {
+        return Err(Error::Damaged(names()));
+    }
+    if !reinstall {
+        println!("Run 'crafty verify --reinstall' to repair them.");
+        return Err(Error::Damaged(names()));
+    }
+    if !yes && !confirm(&format!("Reinstall {}?", broken.join(", ")), true) {
+        return Err(Error::Damaged(names()));
+    }
+
+    let reinstall = manager.stage_reinstall(&checks)?;
+    for (name, error) in &reinstall.failed {
+        log::error!("Can't reinstall {}: {}", name, error);
+        report::record(|s| s.fail(name, error));
+    }
+    if !reinstall.staged.is_empty() {
+        install_staged(manager, &broken.join(", "), &reinstall.staged)?;
+    }
+    if !reinstall.failed.is_empty() {
+        let names = reinstall.failed.into_iter().map(|(name, _)| name).collect();
+        return Err(Error::Damaged(names));
+    }
+    Ok(())
+}
+
+fn print_check(check: &PackageCheck) {
+    let name = style::paint(&check.name, Style::Package);
+    let Some(installed) = &check.installed else {
+        println!(
+            "{}: no longer installed, 'crafty db sync' drops it from the database",
+            name
+        );
+        return;
+    };
+    println!("{} {}", name, style::paint(installed, Style::Version));
+    if check.version_mismatch() {
+        println!(
+            "  {} crafty installed {}",
+            style::paint("version", Style::Warning),
+            check.recorded.as_deref().unwrap_or_default()
+        );
+    }
+    for file in check.missing() {
+        println!(
+            "  {} {}",
+            style::paint("missing", Style::Warning),
+            file.path.display()
+        );
+    }
+    for file in check.modified() {
+        println!(
+            "  {} {} ({})",
+            style::paint("modified", Style::Warning),
+            file.path.display(),
+            file.reason
+        );
+    }
+    if let ArchiveState::Corrupt(path) = &check.archive {
+        println!(
+            "  {} {} doesn't match the checksum recorded at install time",
+            style::paint("archive", Style::Warning),
+            path.display()
+        );
+    }
+}
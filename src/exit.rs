@@ -0,0 +1,60 @@
+// Exit codes, so scripts can tell "nothing to do" from "repository unreachable"
+
+use crafty_core::{Error, summary::Summary};
+
+pub const SUCCESS: i32 = 0;
+// Anything without a more specific code
+pub const FAILURE: i32 = 1;
+// The code clap exits with for invalid usage, reused for arguments it can't check
+pub const USAGE: i32 = 2;
+pub const NOT_FOUND: i32 = 3;
+pub const DOWNLOAD: i32 = 4;
+pub const PACMAN: i32 = 5;
+pub const PARTIAL: i32 = 6;
+pub const NOTHING_TO_DO: i32 = 7;
+
+pub const HELP: &str = "\
+Exit codes:
+  0  success
+  1  any other error
+  2  invalid usage, e.g. a malformed regular expression
+  3  a package, group, theme or lock entry was not found, or isn't installed
+  4  the repository couldn't be reached or a download failed verification
+  5  pacman or a hook failed, nothing was changed by that transaction
+  6  partial success: some of the requested packages failed, others were changed
+  7  nothing to do, e.g. everything is up to date";
+
+pub fn for_error(error: &Error) -> i32 {
+    match error {
+        Error::NotFound(_)
+        | Error::NotInstalled(_)
+        | Error::UnknownGroup(_)
+        | Error::UnknownTheme(_)
+        | Error::NotLocked(_) => NOT_FOUND,
+        Error::InvalidArgument(_) => USAGE,
+        Error::Index(_)
+        | Error::Http(_)
+        | Error::Interrupted(_)
+        | Error::RateLimited { .. }
+        | Error::InvalidArchive(_)
+        | Error::NoSpace { .. }
+        | Error::ChecksumMismatch { .. } => DOWNLOAD,
+        Error::Pacman(_) | Error::Transaction(_) | Error::NotRegistered(_) | Error::Hook(_) => {
+            PACMAN
+        }
+        _ => FAILURE,
+    }
+}
+
+// A transaction command's outcome, judged by what its summary recorded
+pub fn for_transaction(result: &Result<(), Error>, summary: &Summary) -> i32 {
+    let changed = !summary.changes.is_empty();
+    match result {
+        Err(_) if changed => PARTIAL,
+        Err(e) => for_error(e),
+        Ok(()) if !summary.failed.is_empty() && changed => PARTIAL,
+        Ok(()) if !summary.failed.is_empty() => FAILURE,
+        Ok(()) if !changed => NOTHING_TO_DO,
+        Ok(()) => SUCCESS,
+    }
+}
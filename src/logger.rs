@@ -0,0 +1,224 @@
+// `log` backend: messages on the terminal or in the systemd journal filtered
+// by -v/-q, and optionally everything from debug up appended to a log file
+// for postmortems
+
+use crate::style::{self, Style};
+use clap::ValueEnum;
+use crafty_core::{
+    paths,
+    util::{format_timestamp, now},
+};
+use log::{
+    Level, LevelFilter, Log, Metadata, Record,
+    kv::{self, Key, Value, VisitSource},
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    os::unix::net::UnixDatagram,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+/// Records only meant for the journal and the log file, such as per-package
+/// results the terminal already shows as a table.
+pub const EVENTS: &str = "crafty::events";
+
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Target {
+    #[default]
+    Terminal,
+    /// systemd's journal, with the fields of structured records, e.g. from a timer
+    Journal,
+}
+
+struct Logger {
+    level: LevelFilter,
+    // Takes the place of the terminal when set
+    journal: Option<UnixDatagram>,
+    file: Option<Mutex<File>>,
+    file_level: LevelFilter,
+}
+
+/// `~/.local/state/crafty/crafty.log`
+pub fn file_path() -> PathBuf {
+    paths::state_dir().join("crafty.log")
+}
+
+// -q shows errors only, -v adds debug and -vv trace messages
+pub fn init(verbose: u8, quiet: bool, log_file: bool, target: Target) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    let path = file_path();
+    let file = log_file
+        .then(|| {
+            fs::create_dir_all(path.parent().unwrap()).ok()?;
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .ok()
+        })
+        .flatten();
+    if log_file && file.is_none() {
+        eprintln!("Failed to open the log file {}", path.display());
+    }
+    let journal = match target {
+        Target::Terminal => None,
+        Target::Journal => {
+            let journal = UnixDatagram::unbound()
+                .and_then(|socket| socket.connect(JOURNAL_SOCKET).map(|_| socket));
+            if let Err(e) = &journal {
+                eprintln!(
+                    "Failed to connect to the journal, logging to the terminal: {}",
+                    e
+                );
+            }
+            journal.ok()
+        }
+    };
+    let file_level = level.max(LevelFilter::Debug);
+    let max = if file.is_some() { file_level } else { level };
+    let logger = Logger {
+        level,
+        journal,
+        file: file.map(Mutex::new),
+        file_level,
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(max);
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Debug output of the HTTP stack would drown crafty's own
+        metadata.level() <= Level::Warn || metadata.target().starts_with("crafty")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if record.level() <= self.level
+            && let Some(journal) = &self.journal
+        {
+            // Too large for a datagram is the only likely failure, nothing to report it to
+            let _ = journal.send(&journal_entry(record));
+        } else if record.level() <= self.level && record.target() != EVENTS {
+            match record.level() {
+                Level::Info => println!("{}", record.args()),
+                Level::Error => eprintln!(
+                    "{} {}",
+                    style::paint_err("error:", Style::Error),
+                    record.args()
+                ),
+                Level::Warn => eprintln!(
+                    "{} {}",
+                    style::paint_err("warning:", Style::Warning),
+                    record.args()
+                ),
+                level => eprintln!(
+                    "[{} {}] {}",
+                    level.as_str().to_lowercase(),
+                    record.target(),
+                    record.args()
+                ),
+            }
+        }
+        if record.level() <= self.file_level
+            && let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let mut fields = Fields(String::new());
+            let _ = record.key_values().visit(&mut fields);
+            let _ = writeln!(
+                file,
+                "{} {:<5} {}: {}{}",
+                format_timestamp(now()),
+                record.level(),
+                record.target(),
+                record.args(),
+                fields.0
+            );
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let _ = file.flush();
+        }
+    }
+}
+
+// The journal's native protocol: KEY=value lines, or the value's length in
+// little-endian after the key when it spans lines
+fn journal_entry(record: &Record) -> Vec<u8> {
+    let priority = match record.level() {
+        Level::Error => "3",
+        Level::Warn => "4",
+        Level::Info => "6",
+        Level::Debug | Level::Trace => "7",
+    };
+    let mut entry = JournalEntry(Vec::new());
+    entry.field("MESSAGE", &record.args().to_string());
+    entry.field("PRIORITY", priority);
+    entry.field("SYSLOG_IDENTIFIER", "crafty");
+    entry.field("CRAFTY_TARGET", record.target());
+    let _ = record.key_values().visit(&mut entry);
+    entry.0
+}
+
+struct JournalEntry(Vec<u8>);
+
+impl JournalEntry {
+    fn field(&mut self, key: &str, value: &str) {
+        self.0.extend_from_slice(key.as_bytes());
+        if value.contains('\n') {
+            self.0.push(b'\n');
+            self.0
+                .extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            self.0.push(b'=');
+        }
+        self.0.extend_from_slice(value.as_bytes());
+        self.0.push(b'\n');
+    }
+}
+
+impl<'kvs> VisitSource<'kvs> for JournalEntry {
+    // `old_version` becomes CRAFTY_OLD_VERSION, journal field names are upper case
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let name: String = key
+            .as_str()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.field(&format!("CRAFTY_{}", name), &value.to_string());
+        Ok(())
+    }
+}
+
+// ` key=value` pairs appended to log file lines
+struct Fields(String);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.push_str(&format!(" {}={}", key, value));
+        Ok(())
+    }
+}
@@ -1,341 +1,907 @@
-use clap::{Parser, Subcommand};
-use regex::Regex;
-
-use reqwest::blocking::get;
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::{
-    collections::HashSet,
-    fs::{self},
-    path::PathBuf,
-    process::Command,
+mod commands;
+mod exit;
+mod logger;
+mod report;
+mod service;
+mod style;
+mod table;
+mod tui;
+
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use commands::{
+    apply, backup, complete, daemon, db, doctor, export, hook, install, pacdiff, query,
+    self_update, stats, theme, updates, verify,
+};
+use crafty_core::{
+    Config, Event, Manager,
+    lock::{self, LockFile},
+    paths,
 };
+use std::{path::PathBuf, sync::Arc, time::Instant};
 
 #[derive(Parser)]
 #[command(name = "crafty")]
 #[command(about = "Tool to manage ArchCraft packages from GitHub", long_about = None)]
+#[command(after_help = exit::HELP)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// How to gain root privileges for pacman, overriding the `elevate` config key
+    #[arg(long, global = true, value_enum)]
+    elevate: Option<Elevate>,
+    /// Show debug output (HTTP requests, pacman invocations), twice for trace output
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// When to use colors; NO_COLOR turns them off in auto mode
+    #[arg(long, global = true, value_enum, default_value_t, value_name = "WHEN")]
+    color: style::ColorChoice,
+    /// Append debug output to ~/.local/state/crafty/crafty.log, like the `log_file` config key
+    #[arg(long, global = true)]
+    log_file: bool,
+    /// Where log messages go; the journal gets structured fields with each message
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        value_name = "TARGET"
+    )]
+    log_target: logger::Target,
+    /// Branch or tag of the pkgs repository to use, overriding the `git_ref` config key
+    #[arg(
+        long = "ref",
+        visible_alias = "branch",
+        global = true,
+        value_name = "REF"
+    )]
+    git_ref: Option<String>,
+    /// Cap the download speed, e.g. 500K or 2M bytes per second, overriding
+    /// the `limit_rate` config key
+    #[arg(long, global = true, value_name = "RATE")]
+    limit_rate: Option<String>,
+    /// Install into the system under DIR instead of /, e.g. a chroot, an ISO
+    /// build tree or a throwaway directory; it gets its own crafty database,
+    /// and sync databases are fetched into it if it has none yet
+    #[arg(long, global = true, value_name = "DIR")]
+    root: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Elevate {
+    Sudo,
+    Doas,
+    Pkexec,
+    /// Run pacman directly, e.g. when crafty itself runs as root
+    None,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Install a package from ArchCraft GitHub
-    Install { package: String },
+    Install {
+        /// Package name, `@group` for every package of a group, a pattern
+        /// with `*`/`?` such as 'archcraft-openbox-*', or `-` to read names from stdin
+        #[arg(required_unless_present_any = ["file", "from_file"])]
+        package: Option<String>,
+        /// Install every package listed in this file, one name per line
+        #[arg(long, value_name = "LIST", conflicts_with_all = ["package", "file"])]
+        from_file: Option<PathBuf>,
+        /// Install a local package file instead, e.g. one built with makepkg
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["package", "locked"])]
+        file: Option<PathBuf>,
+        /// Build packages missing from the ArchCraft repositories from the AUR
+        #[arg(long, conflicts_with = "locked")]
+        aur: bool,
+        #[command(flatten)]
+        pacman: PacmanOptions,
+        #[command(flatten)]
+        lock: LockOptions,
+        #[command(flatten)]
+        summary: SummaryOptions,
+    },
     /// Upgrade a previously installed package
-    Upgrade { package: Option<String> },
+    Upgrade {
+        package: Option<String>,
+        #[command(flatten)]
+        pacman: PacmanOptions,
+        #[command(flatten)]
+        summary: SummaryOptions,
+        /// Only report available updates and record them in the state file
+        #[arg(long)]
+        check: bool,
+        /// Send a desktop notification when updates are found
+        #[arg(long)]
+        notify: bool,
+    },
+    /// Download and verify a package archive without installing it
+    Download {
+        package: String,
+        /// Directory to save the archive in
+        #[arg(short, long, value_name = "DIR", default_value = ".")]
+        output: PathBuf,
+        #[command(flatten)]
+        lock: LockOptions,
+    },
     /// Search for a package in the ArchCraft GitHub repository
-    Search { keyword: String },
-    /// Remove a package from the system
-    Remove { package: String },
+    Search {
+        /// One or more fuzzy patterns, e.g. `obx polybr`
+        #[arg(required = true)]
+        keywords: Vec<String>,
+        /// Treat the keywords as regular expressions matched against package names
+        #[arg(long)]
+        regex: bool,
+        /// Order the results by this column instead of by relevance
+        #[arg(long, value_enum)]
+        sort: Option<query::SortKey>,
+        /// Match package descriptions instead of names; every keyword must occur
+        #[arg(long, conflicts_with = "sort")]
+        desc: bool,
+        /// Download and index the descriptions of packages not indexed yet
+        #[arg(long, requires = "desc")]
+        update: bool,
+    },
+    /// Remove packages installed through crafty in one transaction
+    Remove {
+        /// Package names, `-` reads them from stdin
+        #[arg(required_unless_present = "from_file")]
+        packages: Vec<String>,
+        /// Remove every package listed in this file, one name per line
+        #[arg(long, value_name = "LIST")]
+        from_file: Option<PathBuf>,
+        #[command(flatten)]
+        pacman: PacmanOptions,
+        /// Also remove ArchCraft packages that are no longer needed by anything
+        #[arg(long, visible_alias = "cascade")]
+        orphans: bool,
+        #[command(flatten)]
+        summary: SummaryOptions,
+    },
     /// List all packages available in the ArchCraft GitHub repository
-    List,
+    List {
+        /// Show packages installed through crafty instead, with versions and install dates
+        #[arg(long)]
+        installed: bool,
+        /// Only list packages whose name matches this regular expression
+        #[arg(long, value_name = "PATTERN")]
+        regex: Option<String>,
+        /// Only list installed packages carrying this tag, see `crafty tag`
+        #[arg(long, requires = "installed")]
+        tag: Option<String>,
+        /// Show package groups usable as `crafty install @group` instead
+        #[arg(long, conflicts_with_all = ["installed", "regex"])]
+        groups: bool,
+        /// Order of the available packages
+        #[arg(long, value_enum, default_value_t = query::SortKey::Name, conflicts_with = "installed")]
+        sort: query::SortKey,
+    },
+    /// Add already-installed ArchCraft packages to crafty's database
+    Adopt {
+        /// Consider every installed package, not only foreign ones (pacman -Qm)
+        #[arg(long)]
+        all: bool,
+        /// Only show which packages would be adopted
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Find which ArchCraft package ships a file
+    Provides {
+        /// Absolute path or bare file name, e.g. /usr/share/themes/Arc-Dark
+        path: String,
+        /// Download and index every repository package not indexed yet
+        #[arg(long)]
+        update: bool,
+    },
+    /// List the files of an installed or remote package
+    Files {
+        package: String,
+        /// List the repository archive even if the package is installed
+        #[arg(long)]
+        remote: bool,
+    },
+    /// Show repository commits between the installed and the latest version of a package
+    Changelog { package: String },
+    /// Explain why a package is installed and what depends on it
+    Why { package: String },
+    /// Put back the configs a package overwrote under $HOME and /etc
+    Restore {
+        package: String,
+        /// Backup to restore as listed by --list, the latest one by default
+        timestamp: Option<u64>,
+        /// Show the available backups instead of restoring
+        #[arg(long, conflicts_with = "timestamp")]
+        list: bool,
+    },
+    /// Exclude packages from upgrades
+    Hold {
+        #[arg(required = true)]
+        packages: Vec<String>,
+    },
+    /// Allow held packages to be upgraded again
+    Unhold {
+        #[arg(required = true)]
+        packages: Vec<String>,
+    },
+    /// Label a package installed through crafty, e.g. `crafty tag archcraft-openbox work-laptop`
+    Tag {
+        package: String,
+        #[arg(required = true)]
+        tags: Vec<String>,
+        /// Take the tags off instead
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Attach a note to a package installed through crafty, or show it
+    Note {
+        package: String,
+        /// The note, replacing an earlier one; without it the current note is shown
+        text: Option<String>,
+        /// Delete the note
+        #[arg(long, conflicts_with = "text")]
+        clear: bool,
+    },
+    /// Show the log of past installs, upgrades and removals
+    History {
+        /// Only show transactions involving this package
+        package: Option<String>,
+    },
+    /// Print the packages installed through crafty as JSON, e.g. `crafty export > packages.json`
+    Export,
+    /// Install the packages of an export that are missing on this machine
+    Import {
+        /// File written by `crafty export`, or `-` for stdin
+        file: PathBuf,
+        /// Only show what would be installed or adopted
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        pacman: PacmanOptions,
+    },
+    /// Install, upgrade and hold packages as listed in a manifest
+    Apply {
+        /// TOML manifest with a [packages] table, e.g. `archcraft-openbox = ">=1.0"`
+        manifest: PathBuf,
+        /// Also remove crafty-managed packages the manifest doesn't list
+        #[arg(long)]
+        prune: bool,
+        /// Only show the changes that would be made
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        lock: LockOptions,
+        #[command(flatten)]
+        pacman: PacmanOptions,
+    },
+    /// Record the exact package files and checksums for reproducible installs
+    Lock {
+        /// Lock the packages of this manifest instead of every crafty-managed package
+        #[arg(long, value_name = "FILE")]
+        manifest: Option<PathBuf>,
+        /// Where to write the lock file
+        #[arg(short, long, default_value = lock::FILE_NAME)]
+        output: PathBuf,
+    },
+    /// Manage ArchCraft theme bundles (GTK, Openbox, polybar, wallpapers, ...)
+    Theme {
+        #[command(subcommand)]
+        command: ThemeCommands,
+    },
+    /// List .pacnew/.pacsave files of crafty-managed packages and merge them
+    Pacdiff {
+        /// Open each file in a diff tool, then offer to delete the .pacnew/.pacsave
+        #[arg(long)]
+        merge: bool,
+        /// Diff tool to use, overriding the `diff_tool` config key and $DIFFPROG
+        #[arg(long, value_name = "PROGRAM")]
+        tool: Option<String>,
+    },
+    /// Browse, install and remove packages in an interactive full-screen interface
+    Tui,
+    /// Manage the systemd timer that checks for updates periodically
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommands,
+    },
+    /// Replace this binary with the latest crafty release from GitHub
+    SelfUpdate {
+        /// Only report whether a newer release exists
+        #[arg(long)]
+        check: bool,
+    },
+    /// Check pacman, privileges, network access and crafty's own files, and suggest fixes
+    Doctor,
+    /// Summarize the managed packages: counts, sizes, pending updates and recent changes
+    Stats {
+        /// Print the figures as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check installed files and versions of crafty-managed packages against
+    /// pacman (`pacman -Qkk`) and the checksums recorded at install time
+    Verify {
+        /// Only check this package
+        package: Option<String>,
+        /// Reinstall the damaged packages from the download cache or the repository
+        #[arg(long)]
+        reinstall: bool,
+        #[command(flatten)]
+        pacman: PacmanOptions,
+    },
+    /// Maintain crafty's local package database
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
+    },
+    /// Check for updates periodically and serve the result on a local socket
+    /// for status bars to poll
+    #[command(args_conflicts_with_subcommands = true)]
+    Daemon {
+        #[command(subcommand)]
+        command: Option<DaemonCommands>,
+        /// How often to check, as a systemd time span (e.g. 30min, 6h);
+        /// the `check_interval` config key by default
+        #[arg(long)]
+        interval: Option<String>,
+        /// Download pending updates after each check, without installing them
+        #[arg(long)]
+        download: bool,
+    },
+    /// Print shell integration to source from the shell's startup file
+    Hook {
+        #[command(subcommand)]
+        command: HookCommands,
+    },
+    /// Print completion candidates for the words up to the cursor, used by
+    /// the completion scripts
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        words: Vec<String>,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
-struct PackageDb {
-    packages: HashSet<String>,
+#[derive(Args, Clone, Debug, Default)]
+struct PacmanOptions {
+    /// Pass --noconfirm to pacman so no questions are asked
+    #[arg(long)]
+    noconfirm: bool,
+    /// Don't ask before downloading, installing or removing packages
+    #[arg(short, long)]
+    yes: bool,
+    /// Extra arguments handed to pacman verbatim, e.g. `-- --overwrite '*'`
+    #[arg(last = true, value_name = "PACMAN_ARGS")]
+    pacman_args: Vec<String>,
 }
 
-impl PackageDb {
-    fn path() -> PathBuf {
-        dirs::home_dir()
-            .unwrap()
-            .join(".config")
-            .join(".crafty")
-            .join("installed.json")
-    }
-
-    fn load() -> Self {
-        let path = Self::path();
-        if path.exists() {
-            let data = fs::read_to_string(&path).unwrap_or_default();
-            serde_json::from_str(&data).unwrap_or_default()
-        } else {
-            Self::default()
-        }
-    }
-
-    fn save(&self) {
-        let path = Self::path();
-        let dir = path.parent().unwrap();
-        fs::create_dir_all(dir).unwrap();
-        let data = serde_json::to_string_pretty(self).unwrap();
-        fs::write(path, data).unwrap();
-    }
-
-    fn add(&mut self, pkg: &str) {
-        self.packages.insert(pkg.to_string());
-        self.save();
-    }
-
-    fn remove(&mut self, pkg: &str) {
-        self.packages.remove(pkg);
-        self.save();
-    }
-
-    fn contains(&self, pkg: &str) -> bool {
-        self.packages.contains(pkg)
-    }
+#[derive(Args, Clone, Debug, Default)]
+struct SummaryOptions {
+    /// Print the final summary as JSON
+    #[arg(long)]
+    json: bool,
 }
 
-fn main() {
-    let cli = Cli::parse();
+#[derive(Args, Clone, Debug)]
+struct LockOptions {
+    /// Install exactly the files recorded in the lock file
+    #[arg(long)]
+    locked: bool,
+    /// Lock file used with --locked
+    #[arg(long, value_name = "FILE", default_value = lock::FILE_NAME)]
+    lock_file: PathBuf,
+}
 
-    match &cli.command {
-        Commands::Install { package } => install_package(package),
-        Commands::Upgrade { package } => upgrade_package(package.as_deref().unwrap_or("")),
-        Commands::Search { keyword } => search_repo(keyword),
-        Commands::Remove { package } => remove_package(package),
-        Commands::List => list_packages(),
+impl PacmanOptions {
+    // pacman not asking implies crafty shouldn't either
+    fn assume_yes(&self) -> bool {
+        self.yes || self.noconfirm
     }
 }
 
-fn install_package(pkg: &str) {
-    // Construct the base URL for the raw GitHub repository
-    let base_url = "https://github.com/archcraft-os/pkgs/raw/refs/heads/main/x86_64/";
-
-    // Attempt to find the correct package file by listing available files
-    let package_file = match find_package_file(pkg) {
-        Some(file) => file,
-        None => {
-            eprintln!("Package '{}' not found in the repository.", pkg);
-            return;
-        }
-    };
-
-    let url = format!("{}{}", base_url, package_file);
-    let zst_path = format!("/tmp/{}", package_file);
-    let tar_path = zst_path.replace(".zst", "");
-
-    println!("Downloading from {}", url);
-    let response = reqwest::blocking::get(&url).expect("Download failed");
-    let bytes = response.bytes().expect("Failed to read bytes");
-    fs::write(&zst_path, &bytes).expect("Failed to write file");
-
-    // Validate the downloaded file
-    if !is_valid_zst(&zst_path) {
-        eprintln!("Downloaded file is not a valid zstd archive.");
-        return;
+impl LockOptions {
+    fn load(&self) -> crafty_core::Result<Option<LockFile>> {
+        self.locked
+            .then(|| LockFile::load(&self.lock_file))
+            .transpose()
     }
+}
 
-    println!("Trying to install using pacman...");
-    let status = Command::new("sudo")
-        .arg("pacman")
-        .arg("-U")
-        .arg(&zst_path)
-        .status()
-        .expect("Failed to run pacman");
+#[derive(Subcommand)]
+enum ThemeCommands {
+    /// List themes found in the repository with their package counts
+    List,
+    /// Install every package of a theme in one transaction
+    Install {
+        name: String,
+        #[command(flatten)]
+        pacman: PacmanOptions,
+    },
+    /// Remove the installed packages of a theme
+    Remove {
+        name: String,
+        #[command(flatten)]
+        pacman: PacmanOptions,
+    },
+}
 
-    if !status.success() {
-        println!("Pacman failed to install the .zst file. Trying to decompress and retry...");
+#[derive(Subcommand)]
+enum ServiceCommands {
+    /// Install and enable a user-level service and timer running `upgrade --check`
+    Install {
+        /// How often to check, as a systemd time span (e.g. 6h, 1d)
+        #[arg(long)]
+        interval: Option<String>,
+    },
+    /// Disable and remove the timer and service units
+    Uninstall,
+    /// Show when the next check is scheduled
+    Status,
+}
 
-        let unzstd_status = Command::new("unzstd")
-            .arg(&zst_path)
-            .arg("-o")
-            .arg(&tar_path)
-            .status()
-            .expect("Failed to decompress zst");
+#[derive(Subcommand)]
+enum DaemonCommands {
+    /// Print the status of the running daemon, e.g. from a polybar or waybar module
+    Status {
+        #[arg(long, value_enum, default_value_t)]
+        format: daemon::Format,
+    },
+}
 
-        if !unzstd_status.success() {
-            eprintln!("Failed to decompress .zst file");
-            return;
-        }
+#[derive(Subcommand)]
+enum HookCommands {
+    /// Suggest ArchCraft packages for unknown commands
+    ///
+    /// e.g. `eval "$(crafty hook command-not-found --shell bash)"` in ~/.bashrc.
+    /// Packages are found in the files index, which installs and
+    /// `crafty provides --update` fill.
+    CommandNotFound {
+        #[arg(long, value_enum)]
+        shell: hook::Shell,
+    },
+    /// Complete subcommands, flags and package names
+    ///
+    /// e.g. `eval "$(crafty hook completion --shell bash)"` in ~/.bashrc; zsh
+    /// needs compinit loaded first. Package names come from the cached index
+    /// and crafty's database, so completing never waits for the network.
+    Completion {
+        #[arg(long, value_enum)]
+        shell: hook::Shell,
+    },
+    /// Print the packages providing COMMAND, used by the command-not-found handler
+    #[command(hide = true)]
+    Lookup { command: String },
+}
 
-        let retry_status = Command::new("sudo")
-            .arg("pacman")
-            .arg("-U")
-            .arg(&tar_path)
-            .status()
-            .expect("Failed to install decompressed tar");
+#[derive(Subcommand)]
+enum DbCommands {
+    /// Drop packages that were removed outside of crafty from the database
+    Sync {
+        /// Only report stale entries without changing the database
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
 
-        if !retry_status.success() {
-            eprintln!("Pacman failed to install decompressed package");
-            return;
+impl From<Elevate> for crafty_core::Elevate {
+    fn from(elevate: Elevate) -> Self {
+        match elevate {
+            Elevate::Sudo => Self::Sudo,
+            Elevate::Doas => Self::Doas,
+            Elevate::Pkexec => Self::Pkexec,
+            Elevate::None => Self::None,
         }
     }
-
-    println!("✅ Installed: {}", pkg);
-
-    let re = Regex::new(r"^(?P<name>.+)-\d+(\.\d+)*-\d+-[^-]+\.pkg\.tar\.zst$").unwrap();
-    let pkg_real_name = re
-        .captures(&package_file)
-        .and_then(|caps| caps.name("name").map(|m| m.as_str().to_string()))
-        .unwrap_or_else(|| package_file.to_string());
-    let mut db = PackageDb::load();
-    db.add(&pkg_real_name);
 }
 
-fn upgrade_package(pkg: &str) {
-    let db = PackageDb::load();
-    if pkg.is_empty() {
-        for installed_pkg in db.packages.iter() {
-            println!("Upgrading {}", installed_pkg);
-            install_package(installed_pkg);
+impl From<&PacmanOptions> for crafty_core::PacmanOptions {
+    fn from(options: &PacmanOptions) -> Self {
+        Self {
+            noconfirm: options.noconfirm,
+            extra_args: options.pacman_args.clone(),
         }
-    } else if db.contains(pkg) {
-        println!("Upgrading {}", pkg);
-        install_package(pkg);
-    } else {
-        println!("Package '{}' is not installed via archcraft-tool.", pkg);
     }
 }
 
-fn search_repo(keyword: &str) {
-    println!("Searching for '{}' in ArchCraft GitHub...", keyword);
-    match find_packages_by_keyword(keyword) {
-        Some(packages) if !packages.is_empty() => {
-            println!("Found packages:");
-            for pkg in packages {
-                println!("- {}", pkg);
-            }
+// Progress reported by crafty-core, printed the way crafty always did
+fn print_event(event: &Event) {
+    match event {
+        Event::Retrying {
+            url,
+            error,
+            attempt,
+            attempts,
+            wait,
+        } => log::warn!(
+            "Request to {} failed ({}), retrying in {} ms [{}/{}]",
+            url,
+            error,
+            wait.as_millis(),
+            attempt,
+            attempts
+        ),
+        Event::Downloading { url } => log::info!("Downloading from {}", url),
+        Event::Cached { path } => log::info!("Using cached {}", path.display()),
+        Event::FallingBack {
+            failed,
+            error,
+            next,
+        } => log::warn!(
+            "Download from {} failed ({}), trying {}",
+            failed,
+            error,
+            next
+        ),
+        Event::WaitingForLock { path } => log::info!(
+            "Waiting for another crafty process to release {}...",
+            path.display()
+        ),
+        Event::Installing { files } => log::info!(
+            event = "transaction_start",
+            files = *files;
+            "Trying to install using pacman..."
+        ),
+        Event::RetryingDecompressed => {
+            log::info!("This pacman can't read .zst packages. Trying to decompress and retry...")
         }
-        _ => {
-            println!("No packages found for '{}'", keyword);
+        Event::Building {
+            package,
+            helper: Some(helper),
+        } => log::info!("Building {} from the AUR with {}...", package, helper),
+        Event::Building {
+            package,
+            helper: None,
+        } => log::info!("Building {} from the AUR with makepkg...", package),
+        Event::RunningHooks { stage, package } => {
+            log::info!("Running {} hooks for {}", stage.name(), package)
         }
     }
 }
 
-fn remove_package(pkg: &str) {
-    println!("Removing package {}", pkg);
-
-    let status = Command::new("sudo")
-        .arg("pacman")
-        .arg("-Rns")
-        .arg(pkg)
-        .status()
-        .expect("Failed to remove package");
-
-    if status.success() {
-        println!("✅ Removed: {}", pkg);
-        let mut db = PackageDb::load();
-        db.remove(pkg);
-    } else {
-        eprintln!("Failed to remove package");
+// What a failed single-package command was working on, for the JSON summary;
+// the human one is followed by the error anyway
+fn failed_package(command: &Commands) -> Option<&str> {
+    match command {
+        Commands::Install {
+            package: Some(package),
+            summary,
+            ..
+        }
+        | Commands::Upgrade {
+            package: Some(package),
+            check: false,
+            summary,
+            ..
+        } if summary.json && package != "-" => Some(package),
+        _ => None,
     }
 }
 
-fn list_packages() {
-    println!("Fetching package list from ArchCraft GitHub...");
-    match get_all_packages() {
-        Some(pkgs) if !pkgs.is_empty() => {
-            println!("Available packages ({} total):", pkgs.len());
-            for pkg in pkgs {
-                println!("- {}", pkg);
+fn main() {
+    let cli = Cli::parse();
+    style::init(cli.color);
+    // Before anything is read from the old ~/.config/.crafty
+    let migrated = paths::migrate_legacy();
+    let loaded = Config::load();
+    let log_file = cli.log_file || loaded.as_ref().is_ok_and(|c| c.log_file);
+    logger::init(cli.verbose, cli.quiet, log_file, cli.log_target);
+    match migrated {
+        Ok(moved) => {
+            for (from, to) in moved {
+                log::info!("Moved {} to {}", from.display(), to.display());
             }
         }
-        _ => {
-            println!("Failed to fetch package list.");
-        }
+        Err(e) => log::warn!(
+            "Failed to move {} to the XDG directories: {}",
+            paths::legacy_dir().display(),
+            e
+        ),
     }
-}
-
-// Helper function to validate if a file is a valid zstd archive
-fn is_valid_zst(path: &str) -> bool {
-    if let Ok(magic) = fs::read(path) {
-        magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd])
-    } else {
-        false
+    let mut config = loaded.unwrap_or_else(|e| {
+        log::warn!("Ignoring {}", e);
+        Config::default()
+    });
+    // The CLI flag wins over the config
+    if let Some(elevate) = cli.elevate {
+        config.elevate = elevate.into();
     }
-}
-
-fn find_package_file(pkg: &str) -> Option<String> {
-    let url = "https://github.com/archcraft-os/pkgs/tree/main/x86_64";
-    let resp = get(url).ok()?.text().ok()?;
-
-    // Extract the embedded JSON
-    let start_marker = r#"<script type="application/json" data-target="react-app.embeddedData">"#;
-    let end_marker = "</script>";
-
-    let start = resp.find(start_marker)? + start_marker.len();
-    let end = resp[start..].find(end_marker)? + start;
-
-    let json_str = &resp[start..end];
-    let json: Value = serde_json::from_str(json_str).ok()?;
-
-    // Navigate to tree.items
-    let items = json.pointer("/payload/tree/items")?.as_array()?;
-
-    // Regex to match the specific package
-    let re = Regex::new(&format!(
-        r"^(?:archcraft-)?{}-[\d\.]+-\d+-(any|x86_64)\.pkg\.tar\.zst$",
-        regex::escape(pkg)
-    ))
-    .ok()?;
-
-    for item in items {
-        if let Some(name) = item.get("name").and_then(|n| n.as_str()) {
-            if re.is_match(name) {
-                return Some(name.to_string());
-            }
-        }
+    if let Some(git_ref) = &cli.git_ref {
+        config.git_ref = git_ref.clone();
+    }
+    if let Some(rate) = &cli.limit_rate {
+        config.limit_rate = Some(rate.clone());
+    }
+    // Before the database is read, it lives in the root
+    if let Some(root) = &cli.root
+        && let Err(e) = paths::set_root(root)
+    {
+        log::error!("Failed to prepare {}: {}", root.display(), e);
+        std::process::exit(exit::FAILURE);
     }
 
-    None
-}
-
-fn fetch_json_from_github() -> Option<Value> {
-    let url = "https://github.com/archcraft-os/pkgs/tree/main/x86_64";
-    let resp = get(url).ok()?.text().ok()?;
-
-    // Extract the embedded JSON
-    let start_marker = r#"<script type="application/json" data-target="react-app.embeddedData">"#;
-    let end_marker = "</script>";
-
-    let start = resp.find(start_marker)? + start_marker.len();
-    let end = resp[start..].find(end_marker)? + start;
-
-    let json_str = &resp[start..end];
-    let json: Value = serde_json::from_str(json_str).ok()?;
-
-    // Navigate to tree.items
-    let items = json.pointer("/payload/tree/items")?.as_array()?;
-    Some(Value::Array(items.clone()))
-}
-fn find_packages_by_keyword(keyword: &str) -> Option<Vec<String>> {
-
-    let Some(items) = fetch_json_from_github() else { return None; };
-    // Regex to match package files and extract package name
-    let pkg_re = Regex::new(r"^(?P<pkg_name>.+)-[\d\.]+-\d+-(any|x86_64)\.pkg\.tar\.zst$").ok()?;
+    let pacman = match &cli.command {
+        Commands::Install { pacman, .. }
+        | Commands::Upgrade { pacman, .. }
+        | Commands::Remove { pacman, .. }
+        | Commands::Verify { pacman, .. }
+        | Commands::Import { pacman, .. }
+        | Commands::Apply { pacman, .. }
+        | Commands::Theme {
+            command: ThemeCommands::Install { pacman, .. } | ThemeCommands::Remove { pacman, .. },
+        } => pacman.into(),
+        _ => crafty_core::PacmanOptions::default(),
+    };
+    let mut manager = match Manager::new(config) {
+        Ok(manager) => manager
+            .with_events(Arc::new(print_event))
+            .with_pacman_options(pacman),
+        Err(e) => {
+            log::error!("Failed to set up HTTP client: {}", e);
+            std::process::exit(exit::for_error(&e));
+        }
+    };
 
-    let mut matching_packages = Vec::new();
-    if let Some(array) = items.as_array() {
-        for item in array {
-            if let Some(name) = item.get("name").and_then(|n| n.as_str()) {
-                if let Some(captures) = pkg_re.captures(name) {
-                    if let Some(pkg_name) = captures.name("pkg_name") {
-                        // Search only in the package name part (without version and extension)
-                        if pkg_name.as_str().to_lowercase().contains(&keyword.to_lowercase()) {
-                            matching_packages.push(name.to_string());
-                        }
-                    }
+    let started = Instant::now();
+    let result = match &cli.command {
+        Commands::Install {
+            file: Some(file),
+            pacman,
+            ..
+        } => install::install_file(&manager, file, pacman.assume_yes()),
+        Commands::Install {
+            package,
+            from_file,
+            aur,
+            pacman,
+            lock,
+            ..
+        } if from_file.is_some() || package.as_deref() == Some("-") => {
+            commands::package_args(package.as_slice(), from_file.as_deref()).and_then(|pkgs| {
+                let lock = lock.load()?;
+                install::install_many(
+                    &manager,
+                    &pkgs,
+                    lock.as_ref(),
+                    *aur || manager.config.aur,
+                    pacman.assume_yes(),
+                )
+            })
+        }
+        Commands::Install {
+            package: Some(package),
+            pacman,
+            lock: LockOptions { locked: false, .. },
+            ..
+        } if package.contains(['*', '?']) => {
+            install::install_glob(&manager, package, pacman.assume_yes())
+        }
+        Commands::Install {
+            package: Some(package),
+            aur,
+            pacman,
+            lock,
+            ..
+        } => lock
+            .load()
+            .and_then(|lock| match package.strip_prefix('@') {
+                Some(group) => {
+                    install::install_group(&manager, group, lock.as_ref(), pacman.assume_yes())
                 }
+                None => install::install_many(
+                    &manager,
+                    std::slice::from_ref(package),
+                    lock.as_ref(),
+                    *aur || manager.config.aur,
+                    pacman.assume_yes(),
+                ),
+            }),
+        // clap requires one of the two
+        Commands::Install { .. } => unreachable!(),
+        Commands::Upgrade {
+            package,
+            pacman,
+            check,
+            notify,
+            ..
+        } => {
+            if *check {
+                updates::check(&manager, *notify || manager.config.notify)
+            } else {
+                install::upgrade(&manager, package.as_deref(), pacman.assume_yes())
+            }
+        }
+        Commands::Download {
+            package,
+            output,
+            lock,
+        } => lock
+            .load()
+            .and_then(|lock| install::download(&manager, package, output, lock.as_ref())),
+        Commands::Search {
+            keywords,
+            regex,
+            desc: true,
+            update,
+            ..
+        } => query::search_descriptions(&manager, keywords, *regex, *update),
+        Commands::Search {
+            keywords,
+            regex,
+            sort,
+            ..
+        } => query::search(&manager, keywords, *regex, *sort),
+        Commands::Remove {
+            packages,
+            from_file,
+            pacman,
+            orphans,
+            ..
+        } => commands::package_args(packages, from_file.as_deref())
+            .and_then(|pkgs| install::remove(&manager, &pkgs, *orphans, pacman.assume_yes())),
+        Commands::List { groups: true, .. } => query::list_groups(&manager),
+        Commands::List {
+            installed,
+            regex,
+            tag,
+            sort,
+            ..
+        } => query::list(
+            &manager,
+            *installed,
+            regex.as_deref(),
+            tag.as_deref(),
+            *sort,
+        ),
+        Commands::Adopt { all, dry_run } => install::adopt(&manager, *all, *dry_run),
+        Commands::Provides { path, update } => query::provides(&manager, path, *update),
+        Commands::Files { package, remote } => query::files(&manager, package, *remote),
+        Commands::Changelog { package } => query::changelog(&manager, package),
+        Commands::Why { package } => query::why(&manager, package),
+        Commands::Restore {
+            package,
+            timestamp,
+            list,
+        } => backup::restore(&manager, package, *timestamp, *list),
+        Commands::Hold { packages } => install::hold(&manager, packages, true),
+        Commands::Unhold { packages } => install::hold(&manager, packages, false),
+        Commands::Tag {
+            package,
+            tags,
+            remove,
+        } => db::tag(&manager, package, tags, *remove),
+        Commands::Note {
+            package,
+            text,
+            clear,
+        } => db::note(&manager, package, text.as_deref(), *clear),
+        Commands::History { package } => {
+            query::history(&manager, package.as_deref());
+            Ok(())
+        }
+        Commands::Export => {
+            export::export(&manager);
+            Ok(())
+        }
+        Commands::Import {
+            file,
+            dry_run,
+            pacman,
+        } => export::import(&manager, file, *dry_run, pacman.assume_yes()),
+        Commands::Apply {
+            manifest,
+            prune,
+            dry_run,
+            lock,
+            pacman,
+        } => lock.load().and_then(|lock| {
+            apply::apply(
+                &manager,
+                manifest,
+                *prune,
+                *dry_run,
+                lock.as_ref(),
+                pacman.assume_yes(),
+            )
+        }),
+        Commands::Lock { manifest, output } => {
+            commands::lock::lock(&manager, manifest.as_deref(), output)
+        }
+        Commands::Theme { command } => match command {
+            ThemeCommands::List => theme::list(&manager),
+            ThemeCommands::Install { name, pacman } => {
+                theme::install(&manager, name, pacman.assume_yes())
+            }
+            ThemeCommands::Remove { name, pacman } => {
+                theme::remove(&manager, name, pacman.assume_yes())
+            }
+        },
+        Commands::Pacdiff { merge, tool } => pacdiff::pacdiff(&manager, *merge, tool.as_deref()),
+        Commands::Tui => tui::run(&manager),
+        Commands::Service { command } => {
+            match command {
+                ServiceCommands::Install { interval } => service::install(
+                    interval
+                        .as_deref()
+                        .unwrap_or(&manager.config.check_interval),
+                ),
+                ServiceCommands::Uninstall => service::uninstall(),
+                ServiceCommands::Status => service::status(),
             }
+            Ok(())
         }
+        Commands::SelfUpdate { check } => self_update::self_update(&manager, *check),
+        Commands::Doctor => {
+            doctor::doctor(&manager);
+            Ok(())
+        }
+        Commands::Stats { json } => stats::stats(&manager, *json),
+        Commands::Verify {
+            package,
+            reinstall,
+            pacman,
+        } => verify::verify(
+            &manager,
+            package.as_deref(),
+            *reinstall,
+            pacman.assume_yes(),
+        ),
+        Commands::Db { command } => match command {
+            DbCommands::Sync { dry_run } => db::sync(&manager, *dry_run),
+        },
+        Commands::Daemon {
+            command: Some(DaemonCommands::Status { format }),
+            ..
+        } => daemon::status(*format),
+        Commands::Daemon {
+            interval, download, ..
+        } => daemon::run(&mut manager, interval.as_deref(), *download),
+        Commands::Hook { command } => match command {
+            HookCommands::CommandNotFound { shell } => hook::command_not_found(*shell),
+            HookCommands::Completion { shell } => hook::completion(*shell),
+            HookCommands::Lookup { command } => hook::lookup(command),
+        },
+        Commands::Complete { words } => complete::complete(&manager, Cli::command(), words),
+    };
+
+    if let Err(e) = &result
+        && let Some(package) = failed_package(&cli.command)
+    {
+        report::record(|s| s.fail(package, e));
     }
+    // Only commands that run transactions have something to summarize
+    let transaction = match &cli.command {
+        Commands::Install { summary, .. }
+        | Commands::Upgrade {
+            summary,
+            check: false,
+            ..
+        }
+        | Commands::Remove { summary, .. } => {
+            report::print(&manager, started, summary.json);
+            true
+        }
+        Commands::Theme {
+            command: ThemeCommands::Install { .. } | ThemeCommands::Remove { .. },
+        }
+        | Commands::Import { dry_run: false, .. }
+        | Commands::Apply { dry_run: false, .. }
+        | Commands::Tui
+        | Commands::Verify {
+            reinstall: true, ..
+        } => {
+            report::print(&manager, started, false);
+            true
+        }
+        _ => false,
+    };
 
-    Some(matching_packages)
+    let code = match &result {
+        _ if transaction => report::exit_code(&result),
+        Ok(()) => exit::SUCCESS,
+        Err(e) => exit::for_error(e),
+    };
+    if let Err(e) = result {
+        log::error!(exit_code = code; "{}", e);
+    }
+    std::process::exit(code);
 }
-
-fn get_all_packages() -> Option<Vec<String>> {
-
-    let items = fetch_json_from_github()?;
-
-    // Regex to match package files
-    let pkg_re = Regex::new(r"^(.+)-[\d\.]+-\d+-(any|x86_64)\.pkg\.tar\.zst$").ok()?;
-
-    let packages: Vec<String> = items
-        .as_array()
-        .unwrap_or(&vec![])
-        .iter()
-        .filter_map(|item| {
-            item.get("name")
-                .and_then(|n| n.as_str())
-                .filter(|name| pkg_re.is_match(name))
-                .map(|s| s.to_string())
-        })
-        .collect();
-
-    Some(packages)
-}
\ No newline at end of file
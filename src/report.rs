@@ -0,0 +1,149 @@
+// Summary printed after transactions, collected while the commands run
+
+use crate::{
+    exit,
+    logger::EVENTS,
+    style::{self, Style},
+    table::Table,
+};
+use crafty_core::{
+    Manager,
+    manager::PackageChange,
+    summary::Summary,
+    util::{format_size, natural_cmp},
+};
+use std::{sync::Mutex, time::Instant};
+
+static SUMMARY: Mutex<Summary> = Mutex::new(Summary::new());
+
+/// Add to the summary of this run.
+pub fn record(f: impl FnOnce(&mut Summary)) {
+    f(&mut SUMMARY.lock().unwrap());
+}
+
+/// Exit code of a transaction command, see [`exit::for_transaction`].
+pub fn exit_code(result: &crafty_core::Result<()>) -> i32 {
+    exit::for_transaction(result, &SUMMARY.lock().unwrap())
+}
+
+pub fn print(manager: &Manager, started: Instant, json: bool) {
+    let mut summary = SUMMARY.lock().unwrap();
+    summary.downloaded_bytes = manager.repo.downloaded_bytes();
+    summary.elapsed_secs = started.elapsed().as_secs_f64();
+    log_events(&summary);
+    if json {
+        println!("{}", summary.to_json());
+        return;
+    }
+    if summary.is_empty() {
+        return;
+    }
+
+    println!();
+    let mut table = Table::new(&["Action", "Package", "Details"]);
+    for change in &summary.changes {
+        table.row(vec![
+            action(change).to_string(),
+            style::paint(&change.name, Style::Package),
+            versions(change),
+        ]);
+    }
+    for skipped in &summary.skipped {
+        table.row(vec![
+            style::paint("skipped", Style::Marker),
+            style::paint(&skipped.name, Style::Package),
+            skipped.reason.clone(),
+        ]);
+    }
+    for failure in &summary.failed {
+        table.row(vec![
+            style::paint("failed", Style::Error),
+            style::paint(&failure.name, Style::Package),
+            failure.error.clone(),
+        ]);
+    }
+    table.print();
+
+    println!(
+        "Downloaded {} in {:.1}s",
+        format_size(summary.downloaded_bytes),
+        summary.elapsed_secs
+    );
+    if !summary.warnings.is_empty() {
+        println!("{} warning(s):", summary.warnings.len());
+        for warning in &summary.warnings {
+            println!("- {}", style::paint(warning, Style::Warning));
+        }
+    }
+}
+
+// Structured records of the results for the journal and the log file
+fn log_events(summary: &Summary) {
+    for change in &summary.changes {
+        log::info!(
+            target: EVENTS,
+            event = "package",
+            action = action(change),
+            package = change.name.as_str(),
+            old_version = change.old_version.as_deref().unwrap_or_default(),
+            new_version = change.new_version.as_deref().unwrap_or_default();
+            "{} {}", action(change), change.name
+        );
+    }
+    for skipped in &summary.skipped {
+        log::info!(
+            target: EVENTS,
+            event = "package",
+            action = "skipped",
+            package = skipped.name.as_str(),
+            reason = skipped.reason.as_str();
+            "skipped {}: {}", skipped.name, skipped.reason
+        );
+    }
+    for failure in &summary.failed {
+        log::warn!(
+            target: EVENTS,
+            event = "package",
+            action = "failed",
+            package = failure.name.as_str(),
+            error = failure.error.as_str();
+            "failed {}: {}", failure.name, failure.error
+        );
+    }
+    log::info!(
+        target: EVENTS,
+        event = "transaction_end",
+        changed = summary.changes.len(),
+        failed = summary.failed.len(),
+        downloaded_bytes = summary.downloaded_bytes,
+        elapsed_secs = summary.elapsed_secs;
+        "{} package(s) changed, {} failed",
+        summary.changes.len(),
+        summary.failed.len()
+    );
+}
+
+fn action(change: &PackageChange) -> &'static str {
+    match (&change.old_version, &change.new_version) {
+        (None, _) if change.dependency => "installed (dependency)",
+        (None, _) => "installed",
+        (Some(_), None) => "removed",
+        (Some(old), Some(new)) => match natural_cmp(new, old) {
+            std::cmp::Ordering::Greater => "upgraded",
+            std::cmp::Ordering::Less => "downgraded",
+            std::cmp::Ordering::Equal => "reinstalled",
+        },
+    }
+}
+
+fn versions(change: &PackageChange) -> String {
+    match (&change.old_version, &change.new_version) {
+        (Some(old), Some(new)) if old != new => format!(
+            "{} -> {}",
+            style::paint(old, Style::Version),
+            style::paint(new, Style::Version)
+        ),
+        (_, Some(version)) | (Some(version), None) => style::paint(version, Style::Version),
+        (None, None) => String::new(),
+    }
+}
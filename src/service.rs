@@ -0,0 +1,114 @@
+// systemd user units that run `crafty upgrade --check` periodically
+
+use crate::style;
+use std::{env, fs, path::PathBuf, process::Command};
+
+const UNIT_NAME: &str = "crafty-check";
+
+fn unit_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap().join(".config"))
+        .join("systemd")
+        .join("user")
+}
+
+fn service_unit(exe: &str) -> String {
+    format!(
+        "[Unit]
+Description=Check for ArchCraft package updates with crafty
+Wants=network-online.target
+After=network-online.target
+
+[Service]
+Type=oneshot
+ExecStart={} upgrade --check --log-target journal
+",
+        exe
+    )
+}
+
+fn timer_unit(interval: &str) -> String {
+    format!(
+        "[Unit]
+Description=Periodically check for ArchCraft package updates
+
+[Timer]
+OnBootSec=5min
+OnUnitActiveSec={}
+Persistent=true
+
+[Install]
+WantedBy=timers.target
+",
+        interval
+    )
+}
+
+fn systemctl(args: &[&str]) -> bool {
+    Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+pub fn install(interval: &str) {
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            log::error!("Failed to locate the crafty executable: {}", e);
+            return;
+        }
+    };
+
+    let dir = unit_dir();
+    let service = dir.join(format!("{}.service", UNIT_NAME));
+    let timer = dir.join(format!("{}.timer", UNIT_NAME));
+    let written = fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&service, service_unit(&exe.to_string_lossy())))
+        .and_then(|_| fs::write(&timer, timer_unit(interval)));
+    if let Err(e) = written {
+        log::error!("Failed to write units to {}: {}", dir.display(), e);
+        return;
+    }
+    println!("Wrote {}", service.display());
+    println!("Wrote {}", timer.display());
+
+    if systemctl(&["daemon-reload"])
+        && systemctl(&["enable", "--now", &format!("{}.timer", UNIT_NAME)])
+    {
+        log::info!(
+            "{} Update checks scheduled every {}",
+            style::check(),
+            interval
+        );
+    } else {
+        log::error!(
+            "Failed to enable the timer, try 'systemctl --user enable --now {}.timer'",
+            UNIT_NAME
+        );
+    }
+}
+
+pub fn uninstall() {
+    let _ = systemctl(&["disable", "--now", &format!("{}.timer", UNIT_NAME)]);
+    let dir = unit_dir();
+    for unit in [
+        format!("{}.service", UNIT_NAME),
+        format!("{}.timer", UNIT_NAME),
+    ] {
+        let path = dir.join(unit);
+        if path.exists() {
+            match fs::remove_file(&path) {
+                Ok(()) => println!("Removed {}", path.display()),
+                Err(e) => log::error!("Failed to remove {}: {}", path.display(), e),
+            }
+        }
+    }
+    let _ = systemctl(&["daemon-reload"]);
+}
+
+pub fn status() {
+    let _ = systemctl(&["list-timers", &format!("{}.timer", UNIT_NAME)]);
+}
@@ -0,0 +1,86 @@
+// Terminal colors, controlled by --color and NO_COLOR (https://no-color.org)
+
+use clap::ValueEnum;
+use std::{fmt::Display, sync::OnceLock};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal and NO_COLOR is unset
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Style {
+    Success,
+    Error,
+    Warning,
+    /// Package names
+    Package,
+    Version,
+    /// Status markers such as `[installed]`
+    Marker,
+    /// Characters matched by a search
+    Highlight,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Success => "1;32",
+            Style::Error => "1;31",
+            Style::Warning => "1;33",
+            Style::Package => "1",
+            Style::Version => "32",
+            Style::Marker => "36",
+            Style::Highlight => "1;33",
+        }
+    }
+}
+
+static CHOICE: OnceLock<ColorChoice> = OnceLock::new();
+
+pub fn init(choice: ColorChoice) {
+    let _ = CHOICE.set(choice);
+}
+
+fn enabled(fd: libc::c_int) -> bool {
+    match CHOICE.get().copied().unwrap_or_default() {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                && unsafe { libc::isatty(fd) == 1 }
+        }
+    }
+}
+
+/// Whether output to stdout is colored.
+pub fn color_stdout() -> bool {
+    enabled(libc::STDOUT_FILENO)
+}
+
+fn wrap(text: impl Display, style: Style, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", style.code(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// `text` styled for stdout.
+pub fn paint(text: impl Display, style: Style) -> String {
+    wrap(text, style, color_stdout())
+}
+
+/// `text` styled for stderr.
+pub fn paint_err(text: impl Display, style: Style) -> String {
+    wrap(text, style, enabled(libc::STDERR_FILENO))
+}
+
+/// Marker in front of a completed operation.
+pub fn check() -> String {
+    paint("✓", Style::Success)
+}
@@ -0,0 +1,71 @@
+// Column-aligned tables for list-style output
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+pub struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+    right_aligned: Vec<usize>,
+}
+
+impl Table {
+    pub fn new(headers: &[&'static str]) -> Self {
+        Self {
+            headers: headers.to_vec(),
+            rows: Vec::new(),
+            right_aligned: Vec::new(),
+        }
+    }
+
+    /// Align column `column` to the right, e.g. for sizes.
+    pub fn align_right(mut self, column: usize) -> Self {
+        self.right_aligned.push(column);
+        self
+    }
+
+    /// Cells may already be colored, escape codes don't count towards the width.
+    pub fn row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+
+    pub fn print(&self) {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(visible_width(cell));
+            }
+        }
+
+        let headers: Vec<String> = self.headers.iter().map(|h| h.to_string()).collect();
+        println!("{}", self.render(&headers, &widths));
+        let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        println!("{}", rule.join("  "));
+        for row in &self.rows {
+            println!("{}", self.render(row, &widths));
+        }
+    }
+
+    fn render(&self, cells: &[String], widths: &[usize]) -> String {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(column, (cell, width))| {
+                let padding = " ".repeat(width - visible_width(cell));
+                if self.right_aligned.contains(&column) {
+                    format!("{}{}", padding, cell)
+                } else {
+                    format!("{}{}", cell, padding)
+                }
+            })
+            .collect();
+        line.join("  ").trim_end().to_string()
+    }
+}
+
+fn visible_width(cell: &str) -> usize {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    let ansi = ANSI.get_or_init(|| Regex::new(r"\x1b\[[0-9;]*m").unwrap());
+    ansi.replace_all(cell, "").chars().count()
+}
@@ -0,0 +1,394 @@
+// Full-screen package browser drawn with plain ANSI escape sequences
+
+use crate::commands::install::{install_staged, remove};
+use crafty_core::{Error, Manager, Result, archive::parse_package_file};
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    None,
+    Install,
+    Remove,
+}
+
+struct Entry {
+    name: String,
+    version: String,
+    file: String,
+    installed: Option<String>,
+    managed: bool,
+    mark: Mark,
+}
+
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Enter,
+    Esc,
+    Backspace,
+    Char(char),
+    Other,
+}
+
+struct App {
+    entries: Vec<Entry>,
+    filter: String,
+    filtering: bool,
+    // Indices into `entries` matching the current filter
+    visible: Vec<usize>,
+    selected: usize,
+    scroll: usize,
+    status: String,
+}
+
+// Puts the terminal in raw mode on the alternate screen and restores it on drop
+struct RawTerminal {
+    original: libc::termios,
+}
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = original;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        print!("\x1b[?1049h\x1b[?25l");
+        io::stdout().flush()?;
+        Ok(Self { original })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original) };
+    }
+}
+
+pub fn run(manager: &Manager) -> Result<()> {
+    if unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 {
+        return Err(Error::InvalidArgument(String::from(
+            "The TUI needs an interactive terminal",
+        )));
+    }
+
+    log::info!("Fetching package list from ArchCraft GitHub...");
+    let files = manager.repo.packages()?;
+
+    let db = manager.load_db();
+    let installed: HashMap<String, String> = manager
+        .installer
+        .installed_package_versions(false)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    let mut entries: Vec<Entry> = files
+        .into_iter()
+        .filter_map(|file| {
+            let (name, version) = parse_package_file(&file)?;
+            Some(Entry {
+                installed: installed.get(&name).cloned(),
+                managed: db.contains(&name),
+                name,
+                version,
+                file,
+                mark: Mark::None,
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut app = App {
+        visible: (0..entries.len()).collect(),
+        entries,
+        filter: String::new(),
+        filtering: false,
+        selected: 0,
+        scroll: 0,
+        status: String::from("/ filter  space mark  x apply  q quit"),
+    };
+
+    let confirmed = {
+        let _terminal = RawTerminal::enter().map_err(|e| {
+            Error::Io(io::Error::new(
+                e.kind(),
+                format!("Failed to set up the terminal: {}", e),
+            ))
+        })?;
+        app.event_loop()
+    };
+
+    if confirmed {
+        app.apply(manager)?;
+    }
+    Ok(())
+}
+
+impl App {
+    // Returns true when the user confirmed the queued actions
+    fn event_loop(&mut self) -> bool {
+        loop {
+            self.draw();
+            let key = read_key();
+            if self.filtering {
+                match key {
+                    Key::Enter | Key::Esc => self.filtering = false,
+                    Key::Backspace => {
+                        self.filter.pop();
+                        self.refilter();
+                    }
+                    Key::Char(c) => {
+                        self.filter.push(c);
+                        self.refilter();
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            match key {
+                Key::Char('q') | Key::Esc => return false,
+                Key::Up | Key::Char('k') => self.selected = self.selected.saturating_sub(1),
+                Key::Down | Key::Char('j') => self.move_down(1),
+                Key::PageUp => self.selected = self.selected.saturating_sub(self.list_height()),
+                Key::PageDown => self.move_down(self.list_height()),
+                Key::Char('/') => self.filtering = true,
+                Key::Char(' ') => self.toggle_mark(),
+                Key::Char('x') | Key::Enter => {
+                    let queued = self.queued().count();
+                    if queued == 0 {
+                        self.status = String::from("Nothing marked, use space to queue packages");
+                    } else {
+                        self.status = format!("Apply {} queued action(s)? [y/N]", queued);
+                        self.draw();
+                        if matches!(read_key(), Key::Char('y') | Key::Char('Y')) {
+                            return true;
+                        }
+                        self.status = String::from("Cancelled");
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn move_down(&mut self, by: usize) {
+        let last = self.visible.len().saturating_sub(1);
+        self.selected = (self.selected + by).min(last);
+    }
+
+    fn refilter(&mut self) {
+        let needle = self.filter.to_lowercase();
+        self.visible = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.name.to_lowercase().contains(&needle))
+            .map(|(i, _)| i)
+            .collect();
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    // Packages crafty installed are queued for removal, ones that aren't
+    // installed for installation; crafty leaves the others alone
+    fn toggle_mark(&mut self) {
+        let Some(&index) = self.visible.get(self.selected) else {
+            return;
+        };
+        let entry = &mut self.entries[index];
+        entry.mark = match entry.mark {
+            Mark::None if entry.managed => Mark::Remove,
+            Mark::None if entry.installed.is_some() => {
+                self.status = format!("{} wasn't installed through crafty", entry.name);
+                return;
+            }
+            Mark::None => Mark::Install,
+            _ => Mark::None,
+        };
+        self.move_down(1);
+    }
+
+    fn queued(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().filter(|e| e.mark != Mark::None)
+    }
+
+    fn marked(&self, mark: Mark) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|e| e.mark == mark)
+            .map(|e| e.name.clone())
+            .collect()
+    }
+
+    // One transaction installing the marked packages and one removing them,
+    // confirmed in the event loop already. The first failure is returned once
+    // both had their go.
+    fn apply(&self, manager: &Manager) -> Result<()> {
+        let installs = self.marked(Mark::Install);
+        let installed = if installs.is_empty() {
+            Ok(())
+        } else {
+            manager
+                .stage_many(&installs)
+                .and_then(|staged| install_staged(manager, &installs.join(", "), &staged))
+        };
+        let removals = self.marked(Mark::Remove);
+        let removed = if removals.is_empty() {
+            Ok(())
+        } else {
+            remove(manager, &removals, false, true)
+        };
+        match (installed, removed) {
+            (Err(first), Err(second)) => {
+                log::error!("{}", second);
+                Err(first)
+            }
+            (installed, removed) => installed.and(removed),
+        }
+    }
+
+    fn list_height(&self) -> usize {
+        // Header, filter line, detail pane and status line
+        terminal_size().0.saturating_sub(9).max(1)
+    }
+
+    fn draw(&mut self) {
+        let (rows, cols) = terminal_size();
+        let height = self.list_height();
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + height {
+            self.scroll = self.selected + 1 - height;
+        }
+
+        let mut out = String::from("\x1b[H\x1b[2J");
+        let queued = self.queued().count();
+        let header = format!(
+            "crafty  {} packages, {} shown, {} queued",
+            self.entries.len(),
+            self.visible.len(),
+            queued
+        );
+        out.push_str(&format!("\x1b[1m{}\x1b[0m\r\n", fit(&header, cols)));
+        let cursor = if self.filtering { "_" } else { "" };
+        out.push_str(&fit(&format!("Filter: {}{}", self.filter, cursor), cols));
+        out.push_str("\r\n");
+
+        for row in 0..height {
+            let Some(&index) = self.visible.get(self.scroll + row) else {
+                out.push_str("\r\n");
+                continue;
+            };
+            let entry = &self.entries[index];
+            let mark = match entry.mark {
+                Mark::Install => "+",
+                Mark::Remove => "-",
+                Mark::None => " ",
+            };
+            let state = match (&entry.installed, entry.managed) {
+                (Some(_), true) => "[crafty]",
+                (Some(_), false) => "[installed]",
+                (None, _) => "",
+            };
+            let line = fit(
+                &format!(
+                    "{} {:<40} {:<16} {}",
+                    mark, entry.name, entry.version, state
+                ),
+                cols,
+            );
+            if self.scroll + row == self.selected {
+                out.push_str(&format!("\x1b[7m{}\x1b[0m\r\n", line));
+            } else {
+                out.push_str(&line);
+                out.push_str("\r\n");
+            }
+        }
+
+        out.push_str(&"─".repeat(cols));
+        out.push_str("\r\n");
+        if let Some(entry) = self.visible.get(self.selected).map(|&i| &self.entries[i]) {
+            let installed = entry.installed.as_deref().unwrap_or("not installed");
+            let details = [
+                format!("Name:      {}", entry.name),
+                format!("Available: {}", entry.version),
+                format!(
+                    "Installed: {}{}",
+                    installed,
+                    if entry.managed {
+                        " (managed by crafty)"
+                    } else {
+                        ""
+                    }
+                ),
+                format!("File:      {}", entry.file),
+            ];
+            for line in details {
+                out.push_str(&fit(&line, cols));
+                out.push_str("\r\n");
+            }
+        } else {
+            out.push_str("No matching packages\r\n\r\n\r\n\r\n");
+        }
+        out.push_str(&format!("\x1b[{};1H", rows));
+        out.push_str(&fit(&self.status, cols));
+
+        print!("{}", out);
+        let _ = io::stdout().flush();
+    }
+}
+
+fn fit(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+// (rows, columns), defaulting to 24x80 when the size can't be determined
+fn terminal_size() -> (usize, usize) {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+        && size.ws_row > 0
+    {
+        (size.ws_row as usize, size.ws_col as usize)
+    } else {
+        (24, 80)
+    }
+}
+
+// Escape sequences arrive in a single read, which tells a lone Esc apart from arrow keys
+fn read_key() -> Key {
+    let mut buf = [0u8; 8];
+    let n = match io::stdin().read(&mut buf) {
+        Ok(0) | Err(_) => return Key::Esc,
+        Ok(n) => n,
+    };
+    match &buf[..n] {
+        [0x1b] => Key::Esc,
+        [0x1b, b'[', b'A'] | [0x1b, b'O', b'A'] => Key::Up,
+        [0x1b, b'[', b'B'] | [0x1b, b'O', b'B'] => Key::Down,
+        [0x1b, b'[', b'5', b'~'] => Key::PageUp,
+        [0x1b, b'[', b'6', b'~'] => Key::PageDown,
+        [b'\r'] | [b'\n'] => Key::Enter,
+        [0x7f] | [0x08] => Key::Backspace,
+        [0x03] => Key::Esc,
+        bytes => match std::str::from_utf8(bytes)
+            .ok()
+            .and_then(|s| s.chars().next())
+        {
+            Some(c) if !c.is_control() => Key::Char(c),
+            _ => Key::Other,
+        },
+    }
+}